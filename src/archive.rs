@@ -0,0 +1,181 @@
+//! Compressed archive for the file backend
+//!
+//! When the history file grows past `max_entries`, its oldest entries are
+//! moved into gzip-compressed segments in a directory next to it, one per
+//! day of rotation, e.g. `.mhist.archive/2026-10-18.mhist.gz`. Segments hold
+//! history lines in the usual `timestamp | directory | command` format;
+//! rotating again on the same day appends another gzip member to that day's
+//! segment.
+
+use crate::error::{Error, Result};
+use crate::secure_file;
+use chrono::NaiveDate;
+use flate2::Compression;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// File name suffix of archive segments
+const SEGMENT_SUFFIX: &str = ".mhist.gz";
+
+/// The archive segments of one history file
+#[derive(Debug, Clone)]
+pub struct Archive {
+    dir: PathBuf,
+}
+
+impl Archive {
+    /// The archive of `history_file`, kept next to it
+    pub fn for_history(history_file: &Path) -> Self {
+        let mut dir = history_file.as_os_str().to_owned();
+        dir.push(".archive");
+        Self {
+            dir: PathBuf::from(dir),
+        }
+    }
+
+    /// Directory holding the segments
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Name of the segment that entries rotated on `date` go to
+    pub fn segment_name(date: NaiveDate) -> String {
+        date.format("%Y-%m-%d").to_string()
+    }
+
+    /// Names of the segments, oldest first
+    pub fn segments(&self) -> Result<Vec<String>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut names: Vec<String> = fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                entry
+                    .file_name()
+                    .to_str()
+                    .and_then(|name| name.strip_suffix(SEGMENT_SUFFIX))
+                    .filter(|name| NaiveDate::parse_from_str(name, "%Y-%m-%d").is_ok())
+                    .map(str::to_string)
+            })
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    /// Path of the segment called `name`
+    pub fn segment_path(&self, name: &str) -> Result<PathBuf> {
+        if NaiveDate::parse_from_str(name, "%Y-%m-%d").is_err() {
+            return Err(Error::invalid_arguments(format!(
+                "invalid archive segment '{}', expected a date such as 2024-01-31",
+                name
+            )));
+        }
+        Ok(self.dir.join(format!("{}{}", name, SEGMENT_SUFFIX)))
+    }
+
+    /// Append history lines to the segment called `name`
+    pub fn append(&self, name: &str, lines: &[String]) -> Result<()> {
+        if lines.is_empty() {
+            return Ok(());
+        }
+
+        let path = self.segment_path(name)?;
+        fs::create_dir_all(&self.dir)?;
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        for line in lines {
+            writeln!(encoder, "{}", line)?;
+        }
+        encoder.finish()?.sync_all()?;
+        Ok(())
+    }
+
+    /// The history lines stored in the segment called `name`
+    pub fn read(&self, name: &str) -> Result<Vec<String>> {
+        let path = self.segment_path(name)?;
+        if !path.exists() {
+            return Err(Error::invalid_arguments(format!(
+                "no archive segment '{}'",
+                name
+            )));
+        }
+
+        let reader = BufReader::new(MultiGzDecoder::new(File::open(&path)?));
+        reader
+            .lines()
+            .map(|line| line.map_err(Error::from))
+            .collect()
+    }
+
+    /// Replace the lines of the segment called `name`, zeroing the old
+    /// contents; an empty segment is removed
+    pub fn rewrite(&self, name: &str, lines: &[String]) -> Result<()> {
+        let path = self.segment_path(name)?;
+        if lines.is_empty() {
+            if path.exists() {
+                secure_file::remove(&path)?;
+            }
+            return Ok(());
+        }
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        for line in lines {
+            writeln!(encoder, "{}", line)?;
+        }
+        fs::create_dir_all(&self.dir)?;
+        secure_file::replace(&path, &encoder.finish()?)
+    }
+
+    /// Size of the segment called `name` on disk, in bytes
+    pub fn size(&self, name: &str) -> Result<u64> {
+        Ok(fs::metadata(self.segment_path(name)?)?.len())
+    }
+
+    /// Delete every segment
+    pub fn remove_all(&self) -> Result<()> {
+        for name in self.segments()? {
+            self.rewrite(&name, &[])?;
+        }
+        if self.dir.exists() {
+            fs::remove_dir(&self.dir)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_segments_append_and_rewrite() {
+        let dir = tempdir().unwrap();
+        let archive = Archive::for_history(&dir.path().join("history.mhist"));
+        assert!(archive.segments().unwrap().is_empty());
+
+        let lines = |range: std::ops::Range<u32>| -> Vec<String> {
+            range.map(|i| format!("line {}", i)).collect()
+        };
+        archive.append("2024-01-02", &lines(0..2)).unwrap();
+        archive.append("2024-01-02", &lines(2..3)).unwrap();
+        archive.append("2023-12-31", &lines(5..6)).unwrap();
+
+        assert_eq!(archive.segments().unwrap(), ["2023-12-31", "2024-01-02"]);
+        assert_eq!(archive.read("2024-01-02").unwrap(), lines(0..3));
+
+        archive.rewrite("2024-01-02", &lines(1..2)).unwrap();
+        assert_eq!(archive.read("2024-01-02").unwrap(), lines(1..2));
+        archive.rewrite("2023-12-31", &[]).unwrap();
+        assert_eq!(archive.segments().unwrap(), ["2024-01-02"]);
+
+        assert!(archive.read("../history").is_err());
+        archive.remove_all().unwrap();
+        assert!(!archive.dir().exists());
+    }
+}
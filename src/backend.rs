@@ -6,6 +6,44 @@
 
 use crate::error::Result;
 use crate::history::HistoryEntry;
+use crate::retention::Expired;
+use chrono::{DateTime, Utc};
+
+/// What happened to a command passed to `log_command`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogOutcome {
+    /// The command was written to history
+    Logged,
+    /// The command was written, but a secret policy asked for a warning
+    Warned { rules: Vec<String> },
+    /// A secret policy refused to store the command at all
+    Dropped { rules: Vec<String> },
+    /// The command was not written (excluded or a filtered duplicate)
+    Skipped,
+}
+
+/// What `shred` removed, and whether any of it is still on disk
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShredReport {
+    /// Commands removed
+    pub commands: usize,
+    /// Stored tokens removed with them
+    pub tokens: usize,
+    /// Removed commands or values that can still be found in the history files
+    pub remnants: usize,
+}
+
+/// A deleted command waiting in the trash
+#[derive(Debug, Clone)]
+pub struct TrashEntry {
+    /// ID to restore it by: the command ID with the database backend, a
+    /// hash of its line in the trash with the file backend
+    pub id: i64,
+    pub entry: HistoryEntry,
+    pub deleted_at: DateTime<Utc>,
+    /// Stored tokens kept with the command
+    pub tokens: usize,
+}
 
 /// Common interface for history storage backends
 ///
@@ -32,17 +70,48 @@ pub trait HistoryProvider {
 
     /// Log a command to history
     ///
-    /// Stores a command in the history with automatic redaction.
-    fn log_command(&mut self, command: &str) -> Result<()>;
+    /// Stores a command in the history with automatic redaction,
+    /// subject to the configured secret policies.
+    fn log_command(&mut self, command: &str) -> Result<LogOutcome>;
 
     /// Clear all history
     ///
-    /// Removes all entries from the history.
+    /// Moves all entries to the trash, from where they can be restored
+    /// until the trash is emptied or they expire.
     fn clear(&mut self) -> Result<()>;
 
+    /// Delete all history for good
+    ///
+    /// Removes all entries and empties the trash.
+    fn purge(&mut self) -> Result<()>;
+
     /// Delete entries by indices
     ///
-    /// Removes specific entries from history by their position.
+    /// Moves specific entries to the trash by their position.
     /// Indices should be in the order returned by get_entries().
     fn delete_entries(&mut self, indices: &[usize]) -> Result<usize>;
+
+    /// Delete entries by indices for good, bypassing the trash
+    ///
+    /// Indices should be in the order returned by get_entries().
+    fn purge_entries(&mut self, indices: &[usize]) -> Result<usize>;
+
+    /// Apply the retention policies
+    ///
+    /// Moves expired commands to the trash and returns them; with `dry_run`
+    /// only returns them.
+    fn apply_retention(&mut self, dry_run: bool) -> Result<Vec<Expired>>;
+
+    /// List the trash, most recently deleted first
+    #[must_use = "Query results should be used"]
+    fn list_trash(&self) -> Result<Vec<TrashEntry>>;
+
+    /// Put a command from the trash back into the history
+    ///
+    /// Returns false if there is no such entry in the trash.
+    fn restore_from_trash(&mut self, id: i64) -> Result<bool>;
+
+    /// Delete everything in the trash for good, returning how many commands
+    /// were removed
+    fn empty_trash(&mut self) -> Result<usize>;
 }
@@ -1,6 +1,6 @@
 //! Command-line argument structures for Mortimer
 
-use clap::Args;
+use clap::{Args, Subcommand};
 use std::path::PathBuf;
 
 #[derive(Args)]
@@ -20,12 +20,17 @@ pub struct LogArgs {
     /// Skip redaction for this command
     #[arg(long)]
     pub no_redact: bool,
+
+    /// Exit code of the command, for `failed` retention policies (database
+    /// backend only)
+    #[arg(short = 'E', long)]
+    pub exit_code: Option<i32>,
 }
 
 #[derive(Args)]
 pub struct SearchArgs {
-    /// Search term
-    #[arg(value_name = "TERM")]
+    /// Search query, e.g. 'docker build dir:~/src host:ci after:"2 days ago" exit:0'
+    #[arg(value_name = "QUERY")]
     pub term: String,
 
     /// Filter by directory
@@ -60,13 +65,31 @@ pub struct SearchArgs {
     #[arg(long)]
     pub show_dirs: bool,
 
-    /// Search within specific time range (format: YYYY-MM-DD)
+    #[command(flatten)]
+    pub time: TimeFilterArgs,
+
+    /// Also search archived entries (file backend)
     #[arg(long)]
+    pub include_archive: bool,
+}
+
+/// Time filter shared by the commands that select entries by date
+///
+/// Dates are local, e.g. `yesterday`, `"3h ago"`, `"last monday"`,
+/// `2025-10-01T14:00`, `2025-W40` or `2025-10-01..2025-10-07`.
+#[derive(Args)]
+pub struct TimeFilterArgs {
+    /// Only entries from this date on
+    #[arg(long, value_name = "DATE")]
     pub since: Option<String>,
 
-    /// Search before specific date (format: YYYY-MM-DD)
-    #[arg(long)]
+    /// Only entries from before this date
+    #[arg(long, value_name = "DATE")]
     pub before: Option<String>,
+
+    /// Only entries within this date or range of dates
+    #[arg(long, value_name = "DATE")]
+    pub date: Option<String>,
 }
 
 #[derive(Args)]
@@ -87,10 +110,13 @@ pub struct ImportArgs {
     #[arg(long)]
     pub no_dedup: bool,
 
-    /// Import entries from last N days only
+    /// Import entries from last N days only, within the time filters if given
     #[arg(long)]
     pub days: Option<u32>,
 
+    #[command(flatten)]
+    pub time: TimeFilterArgs,
+
     /// Show progress during import
     #[arg(long)]
     pub progress: bool,
@@ -118,9 +144,46 @@ pub struct ExportArgs {
     #[arg(short = 'D', long)]
     pub directory: Option<String>,
 
-    /// Export entries from last N days only
+    /// Export entries from last N days only, within the time filters if given
     #[arg(long)]
     pub days: Option<u32>,
+
+    #[command(flatten)]
+    pub time: TimeFilterArgs,
+
+    /// Also export archived entries (file backend)
+    #[arg(long)]
+    pub include_archive: bool,
+}
+
+#[derive(Args)]
+pub struct ScanArgs {
+    /// History file to scan (defaults to the shell's configured history file)
+    #[arg(value_name = "FILE", required_unless_present = "shell")]
+    pub file: Option<PathBuf>,
+
+    /// Shell that wrote the file, detected from the file name if omitted
+    #[arg(short = 's', long, value_enum)]
+    pub shell: Option<ShellType>,
+}
+
+#[derive(Args)]
+pub struct ScrubArgs {
+    /// History file to scrub (defaults to the shell's configured history file)
+    #[arg(value_name = "FILE", required_unless_present = "shell")]
+    pub file: Option<PathBuf>,
+
+    /// Shell that wrote the file, detected from the file name if omitted
+    #[arg(short = 's', long, value_enum)]
+    pub shell: Option<ShellType>,
+
+    /// Show how many commands would be redacted without changing the file
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Skip confirmation prompt
+    #[arg(short = 'F', long)]
+    pub force: bool,
 }
 
 #[derive(Args)]
@@ -140,11 +203,14 @@ pub struct StatsArgs {
     /// Show time-based statistics
     #[arg(long)]
     pub time_stats: bool,
+
+    #[command(flatten)]
+    pub time: TimeFilterArgs,
 }
 
 #[derive(Args)]
 pub struct ClearArgs {
-    /// Confirm deletion without prompting
+    /// Delete for good without prompting; a full clear empties the trash too
     #[arg(short = 'F', long)]
     pub force: bool,
 
@@ -152,9 +218,119 @@ pub struct ClearArgs {
     #[arg(long)]
     pub keep: Option<usize>,
 
-    /// Clear entries older than N days
+    /// Clear entries older than N days, within the time filters if given
     #[arg(long)]
     pub older_than: Option<u32>,
+
+    #[command(flatten)]
+    pub time: TimeFilterArgs,
+
+    /// Show how many entries would be cleared without clearing them
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Args)]
+pub struct ArchiveArgs {
+    #[command(subcommand)]
+    pub action: ArchiveAction,
+}
+
+#[derive(Subcommand)]
+pub enum ArchiveAction {
+    /// List archive segments, oldest first
+    List,
+    /// Move the entries of a segment back into the history
+    Restore {
+        /// Segment name, as shown by `archive list`
+        #[arg(value_name = "SEGMENT")]
+        segment: String,
+    },
+}
+
+#[derive(Args)]
+pub struct GcArgs {
+    /// List the commands that would expire without moving them to the trash
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Args)]
+pub struct TrashArgs {
+    #[command(subcommand)]
+    pub action: TrashAction,
+}
+
+#[derive(Subcommand)]
+pub enum TrashAction {
+    /// List deleted commands, most recently deleted first
+    List,
+    /// Put a deleted command back into the history
+    Restore {
+        /// ID shown by `trash list`
+        #[arg(value_name = "ID")]
+        id: i64,
+    },
+    /// Delete everything in the trash for good
+    Empty {
+        /// Empty without prompting
+        #[arg(short = 'F', long)]
+        force: bool,
+    },
+}
+
+#[derive(Args)]
+pub struct ShredArgs {
+    /// ID of the command to remove (database backend)
+    #[arg(
+        value_name = "ID",
+        required_unless_present = "pattern",
+        conflicts_with = "pattern"
+    )]
+    pub command_id: Option<i64>,
+
+    /// Remove every command, or stored token value, matching this regex
+    #[arg(short = 'm', long = "match", value_name = "REGEX")]
+    pub pattern: Option<String>,
+
+    /// List the commands that would be removed without removing them
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Remove without prompting
+    #[arg(short = 'F', long)]
+    pub force: bool,
+}
+
+#[derive(Args)]
+pub struct ForgetArgs {
+    /// Number of commands to forget
+    #[arg(short = 'n', long, default_value = "1")]
+    pub count: usize,
+
+    /// Session to forget commands from: a session ID, or `current` for this shell
+    #[arg(short = 'S', long, value_name = "ID", default_value = "current")]
+    pub session: String,
+
+    /// Also remove the commands from this shell's own history file
+    #[arg(long, value_name = "SHELL")]
+    pub native: Option<ShellType>,
+
+    /// Shell history file to remove them from (defaults to the shell's configured history file)
+    #[arg(long, value_name = "FILE", requires = "native")]
+    pub native_file: Option<PathBuf>,
+
+    /// Remove them for good instead of moving them to the trash
+    #[arg(long)]
+    pub shred: bool,
+
+    /// List the commands that would be forgotten without removing them
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Remove without prompting
+    #[arg(short = 'F', long)]
+    pub force: bool,
 }
 
 #[derive(Args)]
@@ -180,8 +356,45 @@ pub struct ConfigArgs {
     pub get: Option<String>,
 }
 
+#[derive(Args)]
+pub struct ExcludeArgs {
+    /// Show whether this command would be logged, and which rule decides
+    #[arg(long, value_name = "COMMAND")]
+    pub test: Option<String>,
+
+    /// Working directory to test in (default: current directory)
+    #[arg(long, value_name = "DIR", requires = "test")]
+    pub cwd: Option<std::path::PathBuf>,
+
+    /// Hostname to test on (default: this host)
+    #[arg(long, value_name = "NAME", requires = "test")]
+    pub host: Option<String>,
+}
+
+#[derive(Args)]
+pub struct IncognitoArgs {
+    #[command(subcommand)]
+    pub action: IncognitoAction,
+}
+
+#[derive(Subcommand)]
+pub enum IncognitoAction {
+    /// Stop logging commands from this shell session
+    On,
+    /// Resume logging commands from this shell session
+    Off,
+    /// Switch incognito mode on or off
+    Toggle,
+    /// Show whether this shell session is incognito
+    Status,
+}
+
 #[derive(Args)]
 pub struct FzfArgs {
+    /// Only list commands matching this search query
+    #[arg(value_name = "QUERY")]
+    pub query: Option<String>,
+
     /// Show unique commands only
     #[arg(short = 'U', long)]
     pub unique: bool,
@@ -255,6 +468,63 @@ pub struct ValidateArgs {
     pub test: Option<String>,
 }
 
+#[derive(Args)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct RedactArgs {
+    #[command(subcommand)]
+    pub action: Option<RedactAction>,
+
+    /// Command to run through the configured redaction engine
+    #[arg(value_name = "COMMAND")]
+    pub command: Option<String>,
+
+    /// Show every rule match with its span, replacement and outcome
+    #[arg(short = 'E', long)]
+    pub explain: bool,
+}
+
+#[derive(Subcommand)]
+pub enum RedactAction {
+    /// Check the configured rules against a regression corpus
+    Test(RedactTestArgs),
+    /// Allowlist a value that was redacted by mistake
+    Allow(RedactAllowArgs),
+}
+
+#[derive(Args)]
+pub struct RedactTestArgs {
+    /// Corpus file of commands and expected redactions
+    #[arg(value_name = "CORPUS")]
+    pub corpus: PathBuf,
+}
+
+#[derive(Args)]
+pub struct RedactAllowArgs {
+    /// ID of the logged command with the false positive
+    #[arg(value_name = "COMMAND_ID", required_unless_present = "command")]
+    pub command_id: Option<i64>,
+
+    /// Command text with the false positive, instead of a logged command
+    #[arg(long, value_name = "TEXT", conflicts_with = "command_id")]
+    pub command: Option<String>,
+
+    /// Byte span of the match to allow, as listed, e.g. 17..26
+    #[arg(short = 's', long, value_name = "START..END")]
+    pub span: Option<String>,
+
+    /// Allow every value of the same shape instead of this exact value
+    #[arg(short = 'g', long)]
+    pub generalize: bool,
+
+    /// Also put the original value back into the logged command
+    #[arg(short = 'r', long, requires = "command_id")]
+    pub restore: bool,
+
+    /// Show the allowlist entry without saving it
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
 #[derive(Args)]
 pub struct MigrateArgs {
     /// Path to legacy .mhist file
@@ -283,6 +553,33 @@ pub struct MergeArgs {
     /// Show progress during merge
     #[arg(long)]
     pub progress: bool,
+
+    /// File holding the passphrase of the database to merge, if encrypted
+    #[arg(short = 'k', long, value_name = "FILE")]
+    pub key_file: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct DbArgs {
+    #[command(subcommand)]
+    pub action: DbAction,
+}
+
+#[derive(Subcommand)]
+pub enum DbAction {
+    /// Encrypt the history database
+    Encrypt,
+    /// Decrypt the history database
+    Decrypt,
+    /// Change the passphrase of the encrypted history database
+    Rekey(DbRekeyArgs),
+}
+
+#[derive(Args)]
+pub struct DbRekeyArgs {
+    /// File holding the new passphrase, prompted for if not given
+    #[arg(short = 'k', long, value_name = "FILE")]
+    pub new_key_file: Option<PathBuf>,
 }
 
 #[derive(Args)]
@@ -299,13 +596,55 @@ pub struct TokensArgs {
     #[arg(short = 'C', long)]
     pub command_id: Option<i64>,
 
+    /// Filter by placeholder, e.g. "<password:ab12cd34>"
+    #[arg(short = 'P', long)]
+    pub placeholder: Option<String>,
+
+    /// Show each distinct secret once, with the number of commands using it
+    #[arg(short = 'u', long)]
+    pub unique: bool,
+
     /// Show token values (use with caution!)
     #[arg(long)]
     pub show_values: bool,
 
-    /// Export tokens to file
-    #[arg(short = 'O', long)]
-    pub output: Option<PathBuf>,
+    /// Delete stored tokens; placeholders stay in the commands
+    #[arg(long)]
+    pub purge: bool,
+
+    /// Only purge tokens older than N days
+    #[arg(long, requires = "purge")]
+    pub older_than: Option<u32>,
+
+    /// Only purge tokens of this type (e.g. password, api_key)
+    #[arg(long = "type", requires = "purge")]
+    pub token_type: Option<String>,
+
+    /// Purge without prompting for confirmation
+    #[arg(short = 'F', long, requires = "purge")]
+    pub force: bool,
+
+    /// List stored token types by age
+    #[arg(long, conflicts_with = "purge")]
+    pub report: bool,
+
+    /// Export all tokens to an encrypted file
+    #[arg(short = 'O', long, alias = "output", conflicts_with_all = ["import", "purge", "report"])]
+    pub export: Option<PathBuf>,
+
+    /// Import tokens from an encrypted file written by --export
+    #[arg(short = 'I', long, conflicts_with_all = ["purge", "report"])]
+    pub import: Option<PathBuf>,
+
+    /// List when stored token values were revealed, expanded or exported
+    #[arg(long, conflicts_with_all = ["purge", "report", "export", "import"])]
+    pub audit: bool,
+}
+
+#[derive(Args)]
+pub struct ExpandArgs {
+    /// ID of the command to expand
+    pub command_id: i64,
 }
 
 #[derive(Args)]
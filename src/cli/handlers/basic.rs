@@ -1,11 +1,22 @@
 //! Basic command handlers for Mortimer CLI
 
+use crate::backend::LogOutcome;
 use crate::cli::args::*;
 use crate::cli::{CliApp, HistoryBackend};
+use crate::dates::{self, TimeSpan};
 use crate::error::{Error, Result};
+use crate::incognito::Incognito;
+use crate::query::QueryContext;
 use crate::search::SearchQuery;
 
 pub fn handle_log(app: &mut CliApp, args: &LogArgs) -> Result<()> {
+    if let Some(session) = Incognito::current_session()
+        && Incognito::from_config(&app.config).is_active(&session)
+    {
+        app.verbose_println("Incognito mode is on; command not logged");
+        return Ok(());
+    }
+
     if !app.quiet {
         app.verbose_println(&format!("Logging command: {}", args.command));
     }
@@ -22,78 +33,60 @@ pub fn handle_log(app: &mut CliApp, args: &LogArgs) -> Result<()> {
     };
 
     // Log the command
-    if timestamp.is_none() {
+    let outcome = if timestamp.is_none() && args.exit_code.is_none() {
         // Use trait method for simple case
-        app.provider_mut().log_command(&args.command)?;
+        app.provider_mut().log_command(&args.command)?
     } else {
-        // Use backend-specific methods for timestamp support
+        // Use backend-specific methods for timestamp and exit code support
         match &mut app.backend {
             HistoryBackend::File(mgr) => {
-                mgr.log_command_with_timestamp(&args.command, timestamp)?;
+                mgr.log_command_with_timestamp(&args.command, timestamp)?
             }
             HistoryBackend::Database(mgr) => {
-                mgr.log_command_with_timestamp(&args.command, timestamp, None)?;
+                mgr.log_command_with_timestamp(&args.command, timestamp, args.exit_code)?
             }
         }
-    }
+    };
 
-    if !app.quiet {
-        app.verbose_println("Command loggeed successfully");
+    // Warnings go to stderr so they show up in the terminal when the
+    // shell hook logs a command
+    match outcome {
+        LogOutcome::Warned { rules } => {
+            eprintln!(
+                "mortimer: command contains a secret ({}); it was logged redacted",
+                rules.join(", ")
+            );
+        }
+        LogOutcome::Dropped { rules } => {
+            app.verbose_println(&format!(
+                "Command not logged: secret policy 'drop' for {}",
+                rules.join(", ")
+            ));
+        }
+        LogOutcome::Skipped => {
+            app.verbose_println("Command skipped");
+        }
+        LogOutcome::Logged => {
+            if !app.quiet {
+                app.verbose_println("Command loggeed successfully");
+            }
+        }
     }
 
     Ok(())
 }
 
 pub fn handle_search(app: &mut CliApp, args: &SearchArgs) -> Result<()> {
-    // Get entries based on backend
-    let entries = match &app.backend {
-        HistoryBackend::File(mgr) => mgr.get_entries()?,
-        HistoryBackend::Database(mgr) => {
-            // For database, use direct search if no complex filters
-            if args.since.is_none() && args.before.is_none() && !args.regex && !args.exact {
-                let db_results = mgr.search(
-                    &args.term,
-                    args.directory.as_deref(),
-                    None,
-                    Some(args.limit),
-                )?;
-
-                // Display results
-                for result in &db_results {
-                    let mut output = String::new();
-
-                    if args.timestamps {
-                        output.push_str(&format!(
-                            "{} ",
-                            result.timestamp.format("%Y-%m-%d %H:%M:%S")
-                        ));
-                    }
-
-                    if args.show_dirs {
-                        output.push_str(&format!("{} ", result.directory));
-                    }
-
-                    output.push_str(&result.command);
-                    println!("{}", output);
-                }
-
-                if !app.quiet {
-                    println!("\nFound {} results", db_results.len());
-                }
-                return Ok(());
-            }
-
-            // Otherwise, get all and use search engine
-            mgr.get_all_commands()?
-                .into_iter()
-                .map(Into::into)
-                .collect()
-        }
+    // Build search query; with --regex the whole term is the regex
+    let mut query = if args.regex {
+        SearchQuery::new(args.term.clone()).regex()
+    } else {
+        SearchQuery::parse(
+            &args.term,
+            &QueryContext::new(app.current_session_id()?, app.timezone),
+        )?
     };
 
-    // Build search query
-    let mut query = SearchQuery::new(args.term.clone());
-
     if let Some(dir) = &args.directory {
         query = query.with_directory(dir.clone());
     }
@@ -106,43 +99,36 @@ pub fn handle_search(app: &mut CliApp, args: &SearchArgs) -> Result<()> {
         query.case_sensitive = true;
     }
 
-    if args.regex {
-        query = query.regex();
-    }
-
     if args.redacted_only {
         query = query.redacted_only();
     }
 
     query = query.limit(args.limit);
 
-    // Parse time filters
-    if let Some(since_str) = &args.since {
-        let since = chrono::NaiveDate::parse_from_str(since_str, "%Y-%m-%d")
-            .map_err(|_| Error::InvalidTimestamp {
-                timestamp: since_str.clone(),
-            })?
-            .and_hms_opt(0, 0, 0)
-            .unwrap()
-            .and_utc();
-
-        let end = if let Some(before_str) = &args.before {
-            chrono::NaiveDate::parse_from_str(before_str, "%Y-%m-%d")
-                .map_err(|_| Error::InvalidTimestamp {
-                    timestamp: before_str.clone(),
-                })?
-                .and_hms_opt(23, 59, 59)
-                .unwrap()
-                .and_utc()
-        } else {
-            chrono::Utc::now()
-        };
-
-        query = query.with_time_range(since, end);
+    // Time filters narrow whatever the query asks for
+    if let Some(window) = dates::window(
+        args.time.since.as_deref(),
+        args.time.before.as_deref(),
+        args.time.date.as_deref(),
+        &app.timezone,
+    )? {
+        let (start, end) = query
+            .time_range
+            .unwrap_or((TimeSpan::ALL.start, TimeSpan::ALL.end));
+        query = query.with_time_range(start.max(window.start), end.min(window.end));
     }
 
-    // Perform search
-    let results = app.search_engine.search_with_query(&entries, &query)?;
+    // Every backend searches through the search engine, so terms are folded
+    // the same way whatever the query asks for
+    let results = match &app.backend {
+        HistoryBackend::File(mgr) if args.include_archive => app
+            .search_engine
+            .search_with_query(&mgr.get_entries_with_archive()?, &query)?,
+        HistoryBackend::File(mgr) => app
+            .search_engine
+            .search_with_query(&mgr.get_entries()?, &query)?,
+        HistoryBackend::Database(mgr) => mgr.search_with_query(&app.search_engine, &query)?,
+    };
 
     if results.is_empty() {
         if !app.quiet {
@@ -160,7 +146,10 @@ pub fn handle_search(app: &mut CliApp, args: &SearchArgs) -> Result<()> {
         let mut output = String::new();
 
         if args.timestamps {
-            output.push_str(&format!("{} ", result.entry.formatted_timestamp()));
+            output.push_str(&format!(
+                "{} ",
+                result.entry.formatted_timestamp(&app.timezone)
+            ));
         }
 
         if args.show_dirs {
@@ -188,7 +177,11 @@ pub fn handle_recent(app: &mut CliApp, args: &RecentArgs) -> Result<()> {
 
     for entry in entries {
         if args.timestamps {
-            println!("{} {}", entry.formatted_timestamp(), entry.command);
+            println!(
+                "{} {}",
+                entry.formatted_timestamp(&app.timezone),
+                entry.command
+            );
         } else {
             println!("{}", entry.command);
         }
@@ -200,6 +193,23 @@ pub fn handle_recent(app: &mut CliApp, args: &RecentArgs) -> Result<()> {
 pub fn handle_fzf(app: &mut CliApp, args: &FzfArgs) -> Result<()> {
     let mut entries = app.provider().get_entries()?;
 
+    // Narrow the list with the query language; words match as written and
+    // fzf does the fuzzy matching. The widgets pass whatever is half-typed on
+    // the line, so text that does not parse, such as an unterminated quote,
+    // is searched for as written instead of failing
+    if let Some(input) = args
+        .query
+        .as_deref()
+        .filter(|input| !input.trim().is_empty())
+    {
+        let context = QueryContext::new(app.current_session_id()?, app.timezone);
+        let mut query = SearchQuery::parse(input, &context)
+            .unwrap_or_else(|_| SearchQuery::new(input.trim().to_string()));
+        query.fuzzy = false;
+        let keep = app.search_engine.matching_indices(&entries, &query)?;
+        entries = keep.into_iter().map(|idx| entries[idx].clone()).collect();
+    }
+
     // Filter by directory if specified
     if let Some(dir) = &args.directory {
         entries.retain(|entry| entry.directory.contains(dir));
@@ -213,9 +223,9 @@ pub fn handle_fzf(app: &mut CliApp, args: &FzfArgs) -> Result<()> {
 
     // Sort by timestamp
     if args.reverse {
-        entries.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+        entries.sort_by_key(|a| a.timestamp);
     } else {
-        entries.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.timestamp));
     }
 
     // Take the requested number of entries
@@ -2,7 +2,15 @@
 
 use crate::cli::args::*;
 use crate::cli::{CliApp, HistoryBackend};
-use crate::error::Result;
+use crate::config::ExclusionAction;
+use crate::dates::{self, TimeSpan};
+use crate::error::{Error, Result};
+use crate::exclusion::{ExclusionContext, ExclusionRules};
+use crate::redaction::RuleHits;
+use crate::retention::{RetentionPolicies, RetentionSchedule};
+use crate::timezone::DisplayTimezone;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
 use std::io::{self, Write};
 
 pub fn handle_config(app: &mut CliApp, args: &ConfigArgs) -> Result<()> {
@@ -26,9 +34,65 @@ pub fn handle_config(app: &mut CliApp, args: &ConfigArgs) -> Result<()> {
     Ok(())
 }
 
+pub fn handle_exclude(app: &mut CliApp, args: &ExcludeArgs) -> Result<()> {
+    let rules = ExclusionRules::from_config(&app.config)?;
+
+    let Some(command) = &args.test else {
+        let rules = rules.describe();
+        if rules.is_empty() {
+            println!("No exclusion rules configured");
+        }
+        for (action, rule) in rules {
+            let action = match action {
+                ExclusionAction::Exclude => "exclude",
+                ExclusionAction::Allow => "allow",
+            };
+            println!("{:<8} {}", action, rule);
+        }
+        return Ok(());
+    };
+
+    let mut ctx = ExclusionContext::current();
+    if let Some(cwd) = &args.cwd {
+        ctx.cwd = cwd.clone();
+    }
+    if let Some(host) = &args.host {
+        ctx.hostname = host.clone();
+    }
+
+    let decision = rules.decide(command, &ctx);
+    println!(
+        "{}",
+        if decision.excluded {
+            "Excluded: not logged"
+        } else {
+            "Logged"
+        }
+    );
+    match decision.rule {
+        Some(rule) => println!("Rule: {}", rule),
+        None => println!("Rule: none matched"),
+    }
+    for rule in decision.overridden {
+        println!("Overrides: {}", rule);
+    }
+
+    Ok(())
+}
+
 pub fn handle_stats(app: &mut CliApp, args: &StatsArgs) -> Result<()> {
+    if let Some(window) = dates::window(
+        args.time.since.as_deref(),
+        args.time.before.as_deref(),
+        args.time.date.as_deref(),
+        &app.timezone,
+    )? {
+        return print_window_stats(app, args, window);
+    }
+
     match &mut app.backend {
         HistoryBackend::File(mgr) => {
+            let rule_hits = mgr.get_rule_hits()?;
             let stats = mgr.get_stats()?;
 
             println!("History Statistics (File-based)");
@@ -40,25 +104,12 @@ pub fn handle_stats(app: &mut CliApp, args: &StatsArgs) -> Result<()> {
             println!("Duplicates filtered: {}", stats.duplicates_filtered);
 
             if args.redaction {
-                println!("\nRedaction Statistics");
-                println!("===================");
-                println!(
-                    "Total commands processed: {}",
-                    stats.redaction_stats.total_commands
-                );
-                println!(
-                    "Commands redacted: {}",
-                    stats.redaction_stats.redacted_commands
-                );
-                println!(
-                    "Environment variables redacted: {}",
-                    stats.redaction_stats.env_vars_redacted
-                );
+                print_rule_hits(&rule_hits, &app.timezone);
 
-                if !stats.redaction_stats.patterns_matched.is_empty() {
-                    println!("\nPatterns matched:");
-                    for (pattern, count) in &stats.redaction_stats.patterns_matched {
-                        println!("  {}: {}", pattern, count);
+                if !stats.redaction_stats.policy_actions.is_empty() {
+                    println!("\nSecret policy actions:");
+                    for (action, count) in &stats.redaction_stats.policy_actions {
+                        println!("  {}: {}", action, count);
                     }
                 }
             }
@@ -85,11 +136,22 @@ pub fn handle_stats(app: &mut CliApp, args: &StatsArgs) -> Result<()> {
             println!("Redacted commands: {}", stats.redacted_commands);
             println!("Stored tokens: {}", stats.stored_tokens);
 
+            if !stats.policy_events.is_empty() {
+                println!("Secret policy actions:");
+                for (action, count) in &stats.policy_events {
+                    println!("  {}: {}", action, count);
+                }
+            }
+
             if let Some(oldest) = stats.oldest_entry {
-                println!("Oldest entry: {}", oldest.format("%Y-%m-%d %H:%M:%S"));
+                println!("Oldest entry: {}", app.timezone.format(oldest, None));
             }
             if let Some(newest) = stats.newest_entry {
-                println!("Newest entry: {}", newest.format("%Y-%m-%d %H:%M:%S"));
+                println!("Newest entry: {}", app.timezone.format(newest, None));
+            }
+
+            if args.redaction {
+                print_rule_hits(&mgr.get_rule_hits()?, &app.timezone);
             }
         }
     }
@@ -97,6 +159,98 @@ pub fn handle_stats(app: &mut CliApp, args: &StatsArgs) -> Result<()> {
     Ok(())
 }
 
+/// Statistics for the commands run within `window`, from either backend
+fn print_window_stats(app: &CliApp, args: &StatsArgs, window: TimeSpan) -> Result<()> {
+    let entries: Vec<_> = app
+        .provider()
+        .get_entries()?
+        .into_iter()
+        .filter(|entry| window.contains(entry.timestamp))
+        .collect();
+
+    let bound = |time: chrono::DateTime<chrono::Utc>, open: &str| {
+        if time == TimeSpan::ALL.start || time == TimeSpan::ALL.end {
+            open.to_string()
+        } else {
+            app.timezone.format_with(time, None, "%Y-%m-%d %H:%M")
+        }
+    };
+    println!(
+        "History Statistics ({} to {})",
+        bound(window.start, "the beginning"),
+        bound(window.end, "now")
+    );
+    println!("================================");
+
+    let unique: HashSet<&str> = entries.iter().map(|entry| entry.command.as_str()).collect();
+    println!("Total entries: {}", entries.len());
+    println!("Unique commands: {}", unique.len());
+    println!(
+        "Redacted entries: {}",
+        entries.iter().filter(|entry| entry.redacted).count()
+    );
+
+    let frequent = app.search_engine.get_frequent_commands(&entries)?;
+    if !frequent.is_empty() {
+        println!("\nMost used commands:");
+        for (command, count) in frequent.iter().take(10) {
+            println!("  {:>5}  {}", count, command);
+        }
+    }
+
+    if args.directories {
+        println!("\nDirectory Statistics");
+        println!("===================");
+        let mut dirs: HashMap<&str, usize> = HashMap::new();
+        for entry in &entries {
+            *dirs.entry(entry.directory.as_str()).or_insert(0) += 1;
+        }
+        let mut dirs: Vec<_> = dirs.into_iter().collect();
+        dirs.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        for (dir, count) in dirs.iter().take(10) {
+            println!("  {}: {}", dir, count);
+        }
+    }
+
+    Ok(())
+}
+
+fn print_rule_hits(hits: &[RuleHits], timezone: &DisplayTimezone) {
+    println!("\nRedaction Statistics");
+    println!("===================");
+
+    if hits.is_empty() {
+        println!("No redactions recorded");
+        return;
+    }
+
+    let width = hits
+        .iter()
+        .map(|h| h.rule_id.len())
+        .max()
+        .unwrap_or(0)
+        .max("Rule".len());
+    println!(
+        "{:<width$}  {:>7}  {:>7}  {:>7}  Last seen",
+        "Rule",
+        "Total",
+        "7 days",
+        "30 days",
+        width = width
+    );
+    for hit in hits {
+        println!(
+            "{:<width$}  {:>7}  {:>7}  {:>7}  {}",
+            hit.rule_id,
+            hit.total,
+            hit.last_7_days,
+            hit.last_30_days,
+            timezone.format_with(hit.last_seen, None, "%Y-%m-%d %H:%M"),
+            width = width
+        );
+    }
+}
+
 pub fn handle_status(app: &mut CliApp) -> Result<()> {
     println!("Mortimer Status");
     println!("===============\n");
@@ -123,7 +277,15 @@ pub fn handle_status(app: &mut CliApp) -> Result<()> {
                 "Storage: {}",
                 app.config.history_file.with_extension("db").display()
             );
-            println!("Type: Multi-host, session-aware\n");
+            println!("Type: Multi-host, session-aware");
+            println!(
+                "Encrypted: {}\n",
+                if crate::database::is_encrypted(&app.config.history_file.with_extension("db")) {
+                    "yes"
+                } else {
+                    "no"
+                }
+            );
 
             if app.config.history_file.exists() {
                 println!("ℹ️  Note: Legacy .mhist file still exists");
@@ -161,6 +323,18 @@ pub fn handle_status(app: &mut CliApp) -> Result<()> {
             app.config.shell_integration.exclude_commands.len()
         );
     }
+    if !app.config.shell_integration.exclusion_rules.is_empty() {
+        println!(
+            "  Exclusion rules: {}",
+            app.config.shell_integration.exclusion_rules.len()
+        );
+    }
+    if !app.config.retention.policies.is_empty() {
+        println!(
+            "  Retention policies: {}",
+            app.config.retention.policies.len()
+        );
+    }
 
     println!();
 
@@ -187,10 +361,16 @@ pub fn handle_status(app: &mut CliApp) -> Result<()> {
                 println!("  Stored tokens: {}", stats.stored_tokens);
 
                 if let Some(oldest) = stats.oldest_entry {
-                    println!("  Oldest entry: {}", oldest.format("%Y-%m-%d"));
+                    println!(
+                        "  Oldest entry: {}",
+                        app.timezone.format_with(oldest, None, "%Y-%m-%d")
+                    );
                 }
                 if let Some(newest) = stats.newest_entry {
-                    println!("  Newest entry: {}", newest.format("%Y-%m-%d"));
+                    println!(
+                        "  Newest entry: {}",
+                        app.timezone.format_with(newest, None, "%Y-%m-%d")
+                    );
                 }
             }
             Err(e) => {
@@ -205,8 +385,332 @@ pub fn handle_status(app: &mut CliApp) -> Result<()> {
 }
 
 pub fn handle_clear(app: &mut CliApp, args: &ClearArgs) -> Result<()> {
+    // `--older-than` narrows the time filters rather than replacing them
+    let older_than = args.older_than.map(|days| format!("{} days ago", days));
+    let window = dates::narrow(
+        dates::window(None, older_than.as_deref(), None, &app.timezone)?,
+        dates::window(
+            args.time.since.as_deref(),
+            args.time.before.as_deref(),
+            args.time.date.as_deref(),
+            &app.timezone,
+        )?,
+    )?;
+    if args.keep.is_some() || window.is_some() {
+        return clear_some(app, args, window);
+    }
+
+    if args.dry_run {
+        let total = app.provider().get_entries()?.len();
+        println!("Would clear all {} entries", total);
+        return Ok(());
+    }
+
+    if args.force {
+        app.provider_mut().purge()?;
+        if !app.quiet {
+            println!("History deleted permanently");
+        }
+        return Ok(());
+    }
+
+    print!("Move the whole history to the trash? (y/N): ");
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    if !input.trim().eq_ignore_ascii_case("y") {
+        println!("Aborted");
+        return Ok(());
+    }
+
+    app.provider_mut().clear()?;
+
+    if !app.quiet {
+        println!("History moved to the trash (see `mortimer trash list`)");
+    }
+
+    Ok(())
+}
+
+/// Clear the entries selected by `--keep` and the time filters
+fn clear_some(app: &mut CliApp, args: &ClearArgs, window: Option<TimeSpan>) -> Result<()> {
+    let entries = app.provider().get_entries()?;
+    let keep_from = entries.len().saturating_sub(args.keep.unwrap_or(0));
+    let indices: Vec<usize> = entries
+        .iter()
+        .enumerate()
+        .filter(|(idx, entry)| {
+            *idx < keep_from && window.is_none_or(|window| window.contains(entry.timestamp))
+        })
+        .map(|(idx, _)| idx)
+        .collect();
+
+    if indices.is_empty() {
+        if !app.quiet {
+            println!("Nothing to clear");
+        }
+        return Ok(());
+    }
+
+    if args.dry_run {
+        println!("Would clear {} of {} entries", indices.len(), entries.len());
+        return Ok(());
+    }
+
+    if args.force {
+        let purged = app.provider_mut().purge_entries(&indices)?;
+        if !app.quiet {
+            println!("Deleted {} entries permanently", purged);
+        }
+        return Ok(());
+    }
+
+    print!(
+        "Move {} of {} entries to the trash? (y/N): ",
+        indices.len(),
+        entries.len()
+    );
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    if !input.trim().eq_ignore_ascii_case("y") {
+        println!("Aborted");
+        return Ok(());
+    }
+
+    let moved = app.provider_mut().delete_entries(&indices)?;
+    if !app.quiet {
+        println!(
+            "Moved {} entries to the trash (see `mortimer trash list`)",
+            moved
+        );
+    }
+
+    Ok(())
+}
+
+pub fn handle_gc(app: &mut CliApp, args: &GcArgs) -> Result<()> {
+    let policies = RetentionPolicies::from_config(&app.config)?;
+    if policies.is_empty() {
+        println!("No retention policies configured (see `retention.policies`)");
+        return Ok(());
+    }
+
+    let expired = app.provider_mut().apply_retention(args.dry_run)?;
+    if !args.dry_run {
+        RetentionSchedule::from_config(&app.config).mark(chrono::Utc::now())?;
+    }
+    if app.quiet {
+        return Ok(());
+    }
+
+    if expired.is_empty() {
+        println!("No commands have expired");
+        return Ok(());
+    }
+
+    if args.dry_run || app.verbose {
+        println!("{:<19}  {:<20}  Command", "Timestamp", "Policy");
+        for item in &expired {
+            println!(
+                "{:<19}  {:<20}  {}",
+                app.timezone.format(item.timestamp, None),
+                item.policy,
+                item.command
+            );
+        }
+        println!();
+    }
+
+    for (name, description) in policies.describe() {
+        let count = expired.iter().filter(|item| item.policy == name).count();
+        if count > 0 {
+            println!("{:>6}  {} ({})", count, name, description);
+        }
+    }
+    if args.dry_run {
+        println!("{} commands would be moved to the trash", expired.len());
+    } else {
+        println!(
+            "Moved {} commands to the trash (see `mortimer trash list`)",
+            expired.len()
+        );
+    }
+
+    Ok(())
+}
+
+pub fn handle_trash(app: &mut CliApp, args: &TrashArgs) -> Result<()> {
+    match &args.action {
+        TrashAction::List => {
+            let trash = app.provider().list_trash()?;
+            if trash.is_empty() {
+                println!("The trash is empty");
+                return Ok(());
+            }
+
+            println!("{:>10}  {:<19}  {:>6}  Command", "ID", "Deleted", "Tokens");
+            for item in trash {
+                println!(
+                    "{:>10}  {:<19}  {:>6}  {}",
+                    item.id,
+                    app.timezone.format(item.deleted_at, None),
+                    item.tokens,
+                    item.entry.command
+                );
+            }
+            if let Some(days) = app.config.trash.retention_days {
+                println!("\nDeleted commands are kept for {} days", days);
+            }
+        }
+        TrashAction::Restore { id } => {
+            if !app.provider_mut().restore_from_trash(*id)? {
+                return Err(Error::invalid_arguments(format!(
+                    "No entry {} in the trash",
+                    id
+                )));
+            }
+            if !app.quiet {
+                println!("Restored {}", id);
+            }
+        }
+        TrashAction::Empty { force } => {
+            if !force {
+                print!("Delete everything in the trash for good? (y/N): ");
+                io::stdout().flush()?;
+                let mut input = String::new();
+                io::stdin().read_line(&mut input)?;
+                if !input.trim().eq_ignore_ascii_case("y") {
+                    println!("Aborted");
+                    return Ok(());
+                }
+            }
+            let removed = app.provider_mut().empty_trash()?;
+            if !app.quiet {
+                println!("Deleted {} commands from the trash", removed);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn handle_archive(app: &mut CliApp, args: &ArchiveArgs) -> Result<()> {
+    let HistoryBackend::File(mgr) = &mut app.backend else {
+        return Err(Error::custom(
+            "Archive requires file backend. Use --use-file flag.",
+        ));
+    };
+
+    match &args.action {
+        ArchiveAction::List => {
+            let segments = mgr.list_archive()?;
+            if segments.is_empty() {
+                println!("The archive is empty");
+                return Ok(());
+            }
+
+            println!(
+                "{:<10}  {:>8}  {:>10}  {:<19}  {:<19}",
+                "Segment", "Entries", "Size", "First", "Last"
+            );
+            let format_time = |time: Option<chrono::DateTime<chrono::Utc>>| {
+                time.map(|time| app.timezone.format(time, None))
+                    .unwrap_or_default()
+            };
+            for segment in &segments {
+                println!(
+                    "{:<10}  {:>8}  {:>10}  {:<19}  {:<19}",
+                    segment.name,
+                    segment.entries,
+                    segment.size,
+                    format_time(segment.first),
+                    format_time(segment.last)
+                );
+            }
+            let total: usize = segments.iter().map(|segment| segment.entries).sum();
+            println!(
+                "\n{} archived entries in {} segments",
+                total,
+                segments.len()
+            );
+        }
+        ArchiveAction::Restore { segment } => {
+            let restored = mgr.restore_archive(segment)?;
+            if !app.quiet {
+                println!("Restored {} entries from {}", restored, segment);
+                let total = mgr.get_entries()?.len();
+                if total > app.config.max_entries {
+                    println!(
+                        "The history now holds {} entries, more than max_entries ({}); the oldest are archived again on the next logged command",
+                        total, app.config.max_entries
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn handle_shred(app: &mut CliApp, args: &ShredArgs) -> Result<()> {
+    let pattern = args.pattern.as_deref().map(Regex::new).transpose()?;
+
+    // The commands to remove, with their database IDs
+    let targets: Vec<(Option<i64>, String, String)> = match (&app.backend, &pattern) {
+        (HistoryBackend::File(_), None) => {
+            return Err(Error::invalid_arguments(
+                "Command IDs need the database backend; use --match with the file backend",
+            ));
+        }
+        (HistoryBackend::File(mgr), Some(pattern)) => mgr
+            .get_entries()?
+            .into_iter()
+            .filter(|entry| pattern.is_match(&entry.command))
+            .map(|entry| {
+                (
+                    None,
+                    entry.formatted_timestamp(&app.timezone),
+                    entry.command,
+                )
+            })
+            .collect(),
+        (HistoryBackend::Database(mgr), Some(pattern)) => mgr
+            .find_commands_matching(pattern)?
+            .into_iter()
+            .map(|entry| (Some(entry.id.as_i64()), entry.id.to_string(), entry.command))
+            .collect(),
+        (HistoryBackend::Database(mgr), None) => {
+            let id = args
+                .command_id
+                .expect("clap requires an ID without --match");
+            let entry = mgr.get_command(id)?.ok_or(Error::CommandNotFound)?;
+            vec![(Some(id), entry.id.to_string(), entry.command)]
+        }
+    };
+
+    if targets.is_empty() {
+        if !app.quiet {
+            println!("No matching commands");
+        }
+        return Ok(());
+    }
+
+    if args.dry_run || app.verbose {
+        for (_, label, command) in &targets {
+            println!("  {}  {}", label, command);
+        }
+    }
+    if args.dry_run {
+        println!("DRY RUN: Would shred {} commands", targets.len());
+        return Ok(());
+    }
+
     if !args.force {
-        print!("Are you sure you want to clear the history? (y/N): ");
+        print!(
+            "Shred {} commands? They cannot be restored afterwards (y/N): ",
+            targets.len()
+        );
         io::stdout().flush()?;
         let mut input = String::new();
         io::stdin().read_line(&mut input)?;
@@ -216,13 +720,36 @@ pub fn handle_clear(app: &mut CliApp, args: &ClearArgs) -> Result<()> {
         }
     }
 
-    match &mut app.backend {
-        HistoryBackend::File(mgr) => mgr.clear()?,
-        HistoryBackend::Database(mgr) => mgr.clear()?,
-    }
+    let (report, path) = match &mut app.backend {
+        HistoryBackend::File(mgr) => (
+            mgr.shred_matching(pattern.as_ref().expect("checked above"))?,
+            app.config.history_file.clone(),
+        ),
+        HistoryBackend::Database(mgr) => {
+            let ids: Vec<i64> = targets.iter().filter_map(|(id, _, _)| *id).collect();
+            (
+                mgr.shred_commands(&ids)?,
+                app.config.history_file.with_extension("db"),
+            )
+        }
+    };
 
     if !app.quiet {
-        println!("History cleared successfully");
+        println!(
+            "Shredded {} commands and {} stored tokens",
+            report.commands, report.tokens
+        );
+    }
+    if report.remnants == 0 {
+        if !app.quiet {
+            println!("No trace of them remains in {}", path.display());
+        }
+    } else {
+        eprintln!(
+            "Warning: {} of the removed values can still be found in {}",
+            report.remnants,
+            path.display()
+        );
     }
 
     Ok(())
@@ -2,7 +2,12 @@
 
 use crate::cli::args::*;
 use crate::cli::{CliApp, HistoryBackend};
+use crate::config::DB_KEY_ENV;
+use crate::database;
 use crate::error::{Error, Result};
+use crate::incognito::SESSION_ENV;
+use crate::shell_history::NativeHistory;
+use std::io::{self, BufRead, Write};
 
 pub fn handle_migrate(app: &mut CliApp, args: &MigrateArgs) -> Result<()> {
     let mgr = match &mut app.backend {
@@ -51,7 +56,18 @@ pub fn handle_merge(app: &mut CliApp, args: &MergeArgs) -> Result<()> {
         return Ok(());
     }
 
-    let count = mgr.merge_from_database(&args.db_file)?;
+    let key = if let Some(path) = &args.key_file {
+        Some(read_key_file(path)?)
+    } else if crate::database::is_encrypted(&args.db_file) {
+        Some(prompt_passphrase(
+            "Passphrase of the database to merge: ",
+            false,
+        )?)
+    } else {
+        None
+    };
+
+    let count = mgr.merge_from_database(&args.db_file, key.as_deref())?;
 
     if !app.quiet {
         println!("Successfully merged {} commands", count);
@@ -60,6 +76,74 @@ pub fn handle_merge(app: &mut CliApp, args: &MergeArgs) -> Result<()> {
     Ok(())
 }
 
+pub fn handle_db(app: &mut CliApp, args: &DbArgs) -> Result<()> {
+    let db_path = app.config.history_file.with_extension("db");
+    if !db_path.exists() {
+        return Err(Error::HistoryFileNotFound { path: db_path });
+    }
+
+    match &args.action {
+        DbAction::Encrypt => {
+            let key = match app.config.database.key()? {
+                Some(key) => key,
+                None => prompt_passphrase("New database passphrase: ", true)?,
+            };
+            database::encrypt_database(&db_path, &key)?;
+            if !app.quiet {
+                println!("Encrypted {}", db_path.display());
+                println!(
+                    "Set {} or database.key_file so mortimer can open it",
+                    DB_KEY_ENV
+                );
+            }
+        }
+        DbAction::Decrypt => {
+            let key = current_db_key(app)?;
+            database::decrypt_database(&db_path, &key)?;
+            if !app.quiet {
+                println!("Decrypted {}", db_path.display());
+            }
+        }
+        DbAction::Rekey(rekey_args) => {
+            let key = current_db_key(app)?;
+            let new_key = match &rekey_args.new_key_file {
+                Some(path) => read_key_file(path)?,
+                None => prompt_passphrase("New database passphrase: ", true)?,
+            };
+            database::rekey_database(&db_path, &key, &new_key)?;
+            if !app.quiet {
+                println!("Changed the passphrase of {}", db_path.display());
+                println!(
+                    "Update {} or database.key_file to the new passphrase",
+                    DB_KEY_ENV
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The passphrase of the history database, from the configuration or the terminal
+fn current_db_key(app: &CliApp) -> Result<String> {
+    match app.config.database.key()? {
+        Some(key) => Ok(key),
+        None => prompt_passphrase("Database passphrase: ", false),
+    }
+}
+
+fn read_key_file(path: &std::path::Path) -> Result<String> {
+    let key = std::fs::read_to_string(path)?;
+    let key = key.trim_end_matches(['\r', '\n']);
+    if key.is_empty() {
+        return Err(Error::invalid_arguments(format!(
+            "{} is empty",
+            path.display()
+        )));
+    }
+    Ok(key.to_string())
+}
+
 pub fn handle_tokens(app: &mut CliApp, args: &TokensArgs) -> Result<()> {
     let mgr = match &mut app.backend {
         HistoryBackend::Database(mgr) => mgr,
@@ -70,15 +154,53 @@ pub fn handle_tokens(app: &mut CliApp, args: &TokensArgs) -> Result<()> {
         }
     };
 
+    if args.purge {
+        return purge_tokens(mgr, args, app.quiet);
+    }
+    if args.report {
+        return print_token_report(mgr, &app.config);
+    }
+    if let Some(path) = &args.export {
+        let passphrase = read_passphrase(true)?;
+        let count = mgr.export_tokens(path, &passphrase)?;
+        if !app.quiet {
+            println!("Exported {} tokens to {}", count, path.display());
+        }
+        return Ok(());
+    }
+    if let Some(path) = &args.import {
+        let passphrase = read_passphrase(false)?;
+        let summary = mgr.import_tokens(path, &passphrase)?;
+        if !app.quiet {
+            println!("Imported {} tokens", summary.imported);
+            if summary.already_present > 0 {
+                println!("Skipped {} tokens already stored", summary.already_present);
+            }
+            if summary.unmatched > 0 {
+                println!(
+                    "Skipped {} tokens whose command is not in this history (merge it first)",
+                    summary.unmatched
+                );
+            }
+        }
+        return Ok(());
+    }
+
+    if args.audit {
+        return print_token_audit(mgr, &app.timezone);
+    }
+
     let tokens = if let Some(cmd_id) = args.command_id {
         mgr.get_tokens_for_command(cmd_id)?
     } else if let Some(ref session) = args.session {
         mgr.get_tokens_by_session(session)?
     } else if let Some(ref dir) = args.directory {
         mgr.get_tokens_by_directory(dir)?
+    } else if let Some(ref placeholder) = args.placeholder {
+        mgr.get_tokens_by_placeholder(placeholder)?
     } else {
         return Err(Error::invalid_arguments(
-            "Must specify --command-id, --session, or --directory",
+            "Must specify --command-id, --session, --directory, or --placeholder",
         ));
     };
 
@@ -89,6 +211,19 @@ pub fn handle_tokens(app: &mut CliApp, args: &TokensArgs) -> Result<()> {
         return Ok(());
     }
 
+    if args.show_values {
+        if !reveal_confirmed(&app.config, tokens.len(), &mut io::stdin().lock())? {
+            println!("Aborted");
+            return Ok(());
+        }
+        mgr.record_token_reveal(&tokens)?;
+    }
+
+    if args.unique {
+        print_unique_tokens(&tokens, args.show_values);
+        return Ok(());
+    }
+
     println!("=== Stored Tokens ===\n");
     for token in &tokens {
         println!("ID: {}", token.id);
@@ -100,7 +235,7 @@ pub fn handle_tokens(app: &mut CliApp, args: &TokensArgs) -> Result<()> {
         } else {
             println!("Value: <hidden>");
         }
-        println!("Created: {}", token.created_at.format("%Y-%m-%d %H:%M:%S"));
+        println!("Created: {}", app.timezone.format(token.created_at, None));
         println!();
     }
 
@@ -111,6 +246,347 @@ pub fn handle_tokens(app: &mut CliApp, args: &TokensArgs) -> Result<()> {
     Ok(())
 }
 
+pub fn handle_expand(app: &mut CliApp, args: &ExpandArgs) -> Result<()> {
+    let mgr = match &mut app.backend {
+        HistoryBackend::Database(mgr) => mgr,
+        HistoryBackend::File(_) => {
+            return Err(Error::custom(
+                "Expanding commands requires database backend. Use --use-db flag.",
+            ));
+        }
+    };
+
+    let count = mgr.get_tokens_for_command(args.command_id)?.len();
+    if count > 0 && !reveal_confirmed(&app.config, count, &mut io::stdin().lock())? {
+        return Err(Error::expand_failed(
+            args.command_id,
+            "token values were not revealed",
+        ));
+    }
+
+    // Print only the command so shell widgets can capture it
+    println!("{}", mgr.expand_command(args.command_id)?);
+
+    Ok(())
+}
+
+/// Environment variable that supplies the bundle passphrase non-interactively
+const PASSPHRASE_ENV: &str = "MORTIMER_TOKEN_PASSPHRASE";
+
+/// Read the token bundle passphrase from the environment or the terminal
+fn read_passphrase(confirm: bool) -> Result<String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        if passphrase.is_empty() {
+            return Err(Error::invalid_arguments(format!(
+                "{} is empty",
+                PASSPHRASE_ENV
+            )));
+        }
+        return Ok(passphrase);
+    }
+
+    prompt_passphrase("Passphrase: ", confirm)
+}
+
+/// Ask for a passphrase on the terminal, twice if `confirm` is set
+fn prompt_passphrase(prompt: &str, confirm: bool) -> Result<String> {
+    let passphrase = rpassword::prompt_password(prompt)?;
+    if passphrase.is_empty() {
+        return Err(Error::invalid_arguments("Passphrase must not be empty"));
+    }
+    if confirm && rpassword::prompt_password("Confirm passphrase: ")? != passphrase {
+        return Err(Error::invalid_arguments("Passphrases do not match"));
+    }
+
+    Ok(passphrase)
+}
+
+fn purge_tokens(
+    mgr: &crate::history_db::HistoryManagerDb,
+    args: &TokensArgs,
+    quiet: bool,
+) -> Result<()> {
+    if !args.force {
+        let scope = match (&args.token_type, args.older_than) {
+            (Some(t), Some(days)) => format!("{} tokens older than {} days", t, days),
+            (Some(t), None) => format!("all {} tokens", t),
+            (None, Some(days)) => format!("tokens older than {} days", days),
+            (None, None) => "all stored tokens".to_string(),
+        };
+        print!(
+            "Purge {}? Affected commands can no longer be expanded (y/N): ",
+            scope
+        );
+        io::stdout().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        if !input.trim().eq_ignore_ascii_case("y") {
+            println!("Aborted");
+            return Ok(());
+        }
+    }
+
+    let purged = mgr.purge_tokens(args.older_than, args.token_type.as_deref())?;
+    if !quiet {
+        println!("Purged {} tokens", purged);
+    }
+
+    Ok(())
+}
+
+/// Ask the user to type `reveal` before token values are shown
+///
+/// Only asked when `redaction.confirm_token_reveal` is set, and then always;
+/// the shell bindings read the answer from the terminal. The prompt goes to
+/// stderr so a command printed on stdout can still be captured.
+fn reveal_confirmed(
+    config: &crate::config::Config,
+    count: usize,
+    input: &mut impl BufRead,
+) -> Result<bool> {
+    if !config.redaction.confirm_token_reveal {
+        return Ok(true);
+    }
+
+    eprint!(
+        "Type 'reveal' to show {} token values; this is recorded in the audit log: ",
+        count
+    );
+    io::stderr().flush()?;
+    let mut line = String::new();
+    input.read_line(&mut line)?;
+    Ok(line.trim() == "reveal")
+}
+
+fn print_token_audit(
+    mgr: &crate::history_db::HistoryManagerDb,
+    timezone: &crate::timezone::DisplayTimezone,
+) -> Result<()> {
+    let log = mgr.get_token_access_log()?;
+    if log.is_empty() {
+        println!("No token access recorded");
+        return Ok(());
+    }
+
+    println!("=== Token Access Log ===\n");
+    println!(
+        "{:<19} {:<16} {:<8} {:<7} {:>7}  Placeholder",
+        "Time", "Host", "Session", "Action", "Command"
+    );
+    for entry in &log {
+        let session = entry.session_id.as_deref().unwrap_or("-");
+        println!(
+            "{:<19} {:<16} {:<8} {:<7} {:>7}  {}",
+            timezone.format(entry.accessed_at, None),
+            entry.hostname,
+            &session[..session.len().min(8)],
+            entry.action,
+            entry
+                .command_id
+                .map_or_else(|| "-".to_string(), |id| id.to_string()),
+            entry.placeholder
+        );
+    }
+
+    Ok(())
+}
+
+/// Age in days at which tokens count as stale when no retention is configured
+const DEFAULT_STALE_TOKEN_DAYS: u32 = 30;
+
+fn print_token_report(
+    mgr: &crate::history_db::HistoryManagerDb,
+    config: &crate::config::Config,
+) -> Result<()> {
+    let stale_days = config
+        .redaction
+        .token_retention_days
+        .unwrap_or(DEFAULT_STALE_TOKEN_DAYS);
+    let report = mgr.get_token_age_report(stale_days)?;
+
+    if report.is_empty() {
+        println!("No tokens found");
+        return Ok(());
+    }
+
+    let now = chrono::Utc::now();
+    println!("=== Token Age Report ===\n");
+    println!(
+        "{:<16} {:>6} {:>6} {:>10} {:>10} {:>10}",
+        "Type",
+        "Count",
+        "Trash",
+        "Oldest",
+        "Newest",
+        format!(">{}d", stale_days)
+    );
+    for entry in &report {
+        println!(
+            "{:<16} {:>6} {:>6} {:>9}d {:>9}d {:>10}",
+            entry.token_type,
+            entry.count,
+            entry.trashed,
+            (now - entry.oldest).num_days(),
+            (now - entry.newest).num_days(),
+            entry.stale
+        );
+    }
+
+    let stale: usize = report.iter().map(|entry| entry.stale).sum();
+    if stale > 0 {
+        println!(
+            "\n{} tokens are older than {} days and have probably been rotated.",
+            stale, stale_days
+        );
+        println!(
+            "Remove them with: mortimer --use-db tokens --purge --older-than {}",
+            stale_days
+        );
+    }
+
+    Ok(())
+}
+
+/// Print one entry per distinct placeholder
+///
+/// Correlatable placeholders are keyed on the value, so tokens sharing a
+/// placeholder hold the same secret.
+fn print_unique_tokens(tokens: &[crate::database::Token], show_values: bool) {
+    let mut unique: Vec<(&crate::database::Token, usize)> = Vec::new();
+    for token in tokens {
+        match unique.iter_mut().find(|(t, _)| {
+            t.placeholder == token.placeholder && t.original_value == token.original_value
+        }) {
+            Some((_, count)) => *count += 1,
+            None => unique.push((token, 1)),
+        }
+    }
+
+    println!("=== Distinct Tokens ===\n");
+    for (token, count) in &unique {
+        println!("Placeholder: {}", token.placeholder);
+        println!("Type: {}", token.token_type);
+        if show_values {
+            println!("Value: {}", token.original_value);
+        } else {
+            println!("Value: <hidden>");
+        }
+        println!("Commands: {}", count);
+        println!();
+    }
+
+    println!(
+        "Distinct tokens: {} ({} stored)",
+        unique.len(),
+        tokens.len()
+    );
+}
+
+pub fn handle_forget(app: &mut CliApp, args: &ForgetArgs) -> Result<()> {
+    let HistoryBackend::Database(mgr) = &mut app.backend else {
+        return Err(Error::custom(
+            "Forget command requires database backend. Use --use-db flag.",
+        ));
+    };
+
+    let session = if args.session == "current" {
+        mgr.current_session_id()?.ok_or_else(|| {
+            Error::invalid_arguments(format!(
+                "{} is not set; load the shell integration or pass --session",
+                SESSION_ENV
+            ))
+        })?
+    } else {
+        args.session.clone()
+    };
+
+    let targets = mgr.get_commands_to_forget(&session, args.count)?;
+    if targets.is_empty() {
+        if !app.quiet {
+            println!("No commands to forget in session {}", session);
+        }
+        return Ok(());
+    }
+
+    if args.dry_run || app.verbose {
+        for entry in &targets {
+            println!("  {}  {}", entry.id, entry.command);
+        }
+    }
+    if args.dry_run {
+        println!("DRY RUN: Would forget {} commands", targets.len());
+        return Ok(());
+    }
+
+    if !args.force {
+        if args.shred {
+            print!(
+                "Forget {} commands? They cannot be restored afterwards (y/N): ",
+                targets.len()
+            );
+        } else {
+            print!("Move {} commands to the trash? (y/N): ", targets.len());
+        }
+        io::stdout().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        if !input.trim().eq_ignore_ascii_case("y") {
+            println!("Aborted");
+            return Ok(());
+        }
+    }
+
+    // The shell's own history holds the commands as typed, secrets and all
+    let originals = targets
+        .iter()
+        .map(|entry| mgr.original_command(entry.id.as_i64()))
+        .collect::<Result<Vec<_>>>()?;
+
+    let ids: Vec<i64> = targets.iter().map(|entry| entry.id.as_i64()).collect();
+    if args.shred {
+        let report = mgr.shred_commands(&ids)?;
+        if !app.quiet {
+            println!(
+                "Forgot {} commands and {} stored tokens",
+                report.commands, report.tokens
+            );
+        }
+        if report.remnants > 0 {
+            eprintln!(
+                "Warning: {} of the removed values can still be found in {}",
+                report.remnants,
+                app.config.history_file.with_extension("db").display()
+            );
+        }
+    } else {
+        let moved = mgr.trash_commands(&ids)?;
+        if !app.quiet {
+            println!(
+                "Moved {} commands to the trash; `mortimer trash empty` removes them for good",
+                moved
+            );
+        }
+    }
+
+    if args.native.is_some() {
+        let (shell, path) =
+            super::scrub::resolve_history_file(app, &args.native_file, &args.native)?;
+        // Shells that write history on exit have not saved the commands yet
+        if path.exists() {
+            let mut history = NativeHistory::load(shell, &path)?;
+            let removed = history.forget(&originals);
+            if removed > 0 {
+                history.overwrite(&path)?;
+            }
+            if !app.quiet {
+                println!("Removed {} of them from {}", removed, path.display());
+            }
+        }
+    }
+
+    Ok(())
+}
+
 pub fn handle_hosts(app: &mut CliApp, args: &HostsArgs) -> Result<()> {
     let mgr = match &app.backend {
         HistoryBackend::Database(mgr) => mgr,
@@ -126,12 +602,9 @@ pub fn handle_hosts(app: &mut CliApp, args: &HostsArgs) -> Result<()> {
         println!("=== Sessions for Host ID {} ===\n", host_id);
         for session in sessions {
             println!("Session ID: {}", session.id);
-            println!(
-                "Started: {}",
-                session.started_at.format("%Y-%m-%d %H:%M:%S")
-            );
+            println!("Started: {}", app.timezone.format(session.started_at, None));
             if let Some(ended) = session.ended_at {
-                println!("Ended: {}", ended.format("%Y-%m-%d %H:%M:%S"));
+                println!("Ended: {}", app.timezone.format(ended, None));
             } else {
                 println!("Ended: <active>");
             }
@@ -143,7 +616,7 @@ pub fn handle_hosts(app: &mut CliApp, args: &HostsArgs) -> Result<()> {
         for host in hosts {
             println!("ID: {}", host.id);
             println!("Hostname: {}", host.hostname);
-            println!("Created: {}", host.created_at.format("%Y-%m-%d %H:%M:%S"));
+            println!("Created: {}", app.timezone.format(host.created_at, None));
             println!();
         }
     }
@@ -177,12 +650,9 @@ pub fn handle_sessions(app: &mut CliApp, args: &SessionsArgs) -> Result<()> {
         for session in filtered {
             println!("ID: {}", session.id);
             println!("Host ID: {}", session.host_id);
-            println!(
-                "Started: {}",
-                session.started_at.format("%Y-%m-%d %H:%M:%S")
-            );
+            println!("Started: {}", app.timezone.format(session.started_at, None));
             if let Some(ended) = session.ended_at {
-                println!("Ended: {}", ended.format("%Y-%m-%d %H:%M:%S"));
+                println!("Ended: {}", app.timezone.format(ended, None));
             } else {
                 println!("Status: Active");
             }
@@ -194,3 +664,30 @@ pub fn handle_sessions(app: &mut CliApp, args: &SessionsArgs) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_reveal_confirmation() {
+        let mut config = Config::default();
+        let confirmed = |config: &Config, input: &str| {
+            reveal_confirmed(config, 2, &mut input.as_bytes()).unwrap()
+        };
+
+        // Nothing is asked unless the configuration says so
+        assert!(confirmed(&config, ""));
+
+        config.redaction.confirm_token_reveal = true;
+        assert!(!confirmed(&config, ""));
+        assert!(!confirmed(&config, "y\n"));
+        assert!(confirmed(&config, "reveal\n"));
+
+        // There is no flag to skip the question
+        use clap::Parser;
+        assert!(crate::cli::Cli::try_parse_from(["mortimer", "expand", "7", "--yes"]).is_err());
+        assert!(crate::cli::Cli::try_parse_from(["mortimer", "expand", "7", "-y"]).is_err());
+    }
+}
@@ -2,8 +2,23 @@
 
 use crate::cli::args::*;
 use crate::cli::{CliApp, HistoryBackend};
+use crate::dates::{self, TimeSpan};
 use crate::error::Result;
 
+/// The window selected by both `--days` and the time filters
+fn time_window(app: &CliApp, days: Option<u32>, time: &TimeFilterArgs) -> Result<Option<TimeSpan>> {
+    let days = days.map(|days| format!("{} days ago", days));
+    dates::narrow(
+        dates::window(days.as_deref(), None, None, &app.timezone)?,
+        dates::window(
+            time.since.as_deref(),
+            time.before.as_deref(),
+            time.date.as_deref(),
+            &app.timezone,
+        )?,
+    )
+}
+
 pub fn handle_import(app: &mut CliApp, args: &ImportArgs) -> Result<()> {
     let shell_name = match args.shell {
         ShellType::Zsh => "zsh",
@@ -15,17 +30,21 @@ pub fn handle_import(app: &mut CliApp, args: &ImportArgs) -> Result<()> {
         println!("Importing {} history...", shell_name);
     }
 
+    let window = time_window(app, args.days, &args.time)?;
+
     if args.dry_run {
         println!("DRY RUN: Would import from {} history", shell_name);
         return Ok(());
     }
 
     let imported_count = match &mut app.backend {
-        HistoryBackend::File(mgr) => mgr.import_from_shell(shell_name, args.file.clone())?,
+        HistoryBackend::File(mgr) => {
+            mgr.import_from_shell(shell_name, args.file.clone(), window)?
+        }
         HistoryBackend::Database(mgr) => match args.shell {
-            ShellType::Zsh => mgr.import_from_zsh(args.file.clone())?,
-            ShellType::Bash => mgr.import_from_bash(args.file.clone())?,
-            ShellType::Fish => mgr.import_from_fish(args.file.clone())?,
+            ShellType::Zsh => mgr.import_from_zsh(args.file.clone(), window)?,
+            ShellType::Bash => mgr.import_from_bash(args.file.clone(), window)?,
+            ShellType::Fish => mgr.import_from_fish(args.file.clone(), window)?,
         },
     };
 
@@ -40,26 +59,23 @@ pub fn handle_import(app: &mut CliApp, args: &ImportArgs) -> Result<()> {
 }
 
 pub fn handle_export(app: &mut CliApp, args: &ExportArgs) -> Result<()> {
-    let entries = app.provider().get_entries()?;
+    let window = time_window(app, args.days, &args.time)?;
+    let entries = match &app.backend {
+        HistoryBackend::File(mgr) if args.include_archive => mgr.get_entries_with_archive()?,
+        _ => app.provider().get_entries()?,
+    };
 
     // Filter entries if needed
     let filtered_entries: Vec<_> = entries
         .into_iter()
         .filter(|entry| {
-            if let Some(dir) = &args.directory {
-                if !entry.directory.contains(dir) {
-                    return false;
-                }
-            }
-
-            if let Some(days) = args.days {
-                let cutoff = chrono::Utc::now() - chrono::Duration::days(days as i64);
-                if entry.timestamp < cutoff {
-                    return false;
-                }
+            if let Some(dir) = &args.directory
+                && !entry.directory.contains(dir)
+            {
+                return false;
             }
 
-            true
+            window.is_none_or(|window| window.contains(entry.timestamp))
         })
         .collect();
 
@@ -70,7 +86,7 @@ pub fn handle_export(app: &mut CliApp, args: &ExportArgs) -> Result<()> {
             for entry in &filtered_entries {
                 output.push_str(&format!(
                     "{},{},{}\n",
-                    entry.timestamp.to_rfc3339(),
+                    app.timezone.to_rfc3339(entry.timestamp, entry.utc_offset),
                     entry.directory,
                     entry.command.replace(",", "\\,")
                 ));
@@ -82,7 +98,7 @@ pub fn handle_export(app: &mut CliApp, args: &ExportArgs) -> Result<()> {
             for entry in &filtered_entries {
                 output.push_str(&format!(
                     "{}\t{}\t{}\n",
-                    entry.timestamp.to_rfc3339(),
+                    app.timezone.to_rfc3339(entry.timestamp, entry.utc_offset),
                     entry.directory,
                     entry.command
                 ));
@@ -3,6 +3,7 @@
 use crate::cli::CliApp;
 use crate::error::Result;
 use crate::manage_tui;
+use crate::query::QueryContext;
 
 pub fn handle_manage(app: &mut CliApp) -> Result<()> {
     // Get all entries
@@ -14,7 +15,9 @@ pub fn handle_manage(app: &mut CliApp) -> Result<()> {
     }
 
     // Run the management UI
-    let to_delete = manage_tui::run_management_ui(entries)?;
+    let query_context = QueryContext::new(app.current_session_id()?, app.timezone);
+    let to_delete =
+        manage_tui::run_management_ui(entries, query_context, app.search_engine.clone())?;
 
     if to_delete.is_empty() {
         if !app.quiet {
@@ -27,7 +30,10 @@ pub fn handle_manage(app: &mut CliApp) -> Result<()> {
     let deleted = app.provider_mut().delete_entries(&to_delete)?;
 
     if !app.quiet {
-        println!("Successfully deleted {} entries", deleted);
+        println!(
+            "Moved {} entries to the trash (restore with `mortimer trash restore <id>`)",
+            deleted
+        );
     }
 
     Ok(())
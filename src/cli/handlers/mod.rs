@@ -3,8 +3,10 @@
 //! This module organizes command handlers into logical groups:
 //! - `basic`: Basic commands (log, search, recent, frequent)
 //! - `import_export`: Import and export handlers
-//! - `database`: Database-specific handlers (migrate, merge, tokens, hosts, sessions)
+//! - `database`: Database-specific handlers (migrate, merge, db, tokens, hosts, sessions)
 //! - `config`: Configuration and shell integration handlers
+//! - `redact`: Redaction debugging handlers
+//! - `scrub`: Scanning and scrubbing native shell history files
 //! - `util`: Utility functions for handlers
 
 mod basic;
@@ -12,6 +14,8 @@ mod config;
 mod database;
 mod import_export;
 mod manage;
+mod redact;
+mod scrub;
 mod shell_integration;
 
 pub use basic::*;
@@ -19,4 +23,6 @@ pub use config::*;
 pub use database::*;
 pub use import_export::*;
 pub use manage::*;
+pub use redact::*;
+pub use scrub::*;
 pub use shell_integration::*;
@@ -0,0 +1,266 @@
+//! Redaction debugging handlers for Mortimer CLI
+
+use crate::cli::args::*;
+use crate::cli::{CliApp, HistoryBackend};
+use crate::error::{Error, Result};
+use crate::redaction::{RedactionEngine, generalize_allowlist_pattern};
+use crate::redaction_corpus::{CorpusFailure, RedactionCorpus};
+
+pub fn handle_redact(app: &mut CliApp, args: &RedactArgs) -> Result<()> {
+    match &args.action {
+        Some(RedactAction::Test(test_args)) => handle_redact_test(app, test_args),
+        Some(RedactAction::Allow(allow_args)) => handle_redact_allow(app, allow_args),
+        None => {
+            let command = args.command.as_deref().ok_or_else(|| {
+                Error::invalid_arguments("Must specify a command to redact or a subcommand")
+            })?;
+            handle_redact_command(app, command, args.explain)
+        }
+    }
+}
+
+fn handle_redact_command(app: &CliApp, command: &str, explain: bool) -> Result<()> {
+    let engine = RedactionEngine::from_config(&app.config)?;
+
+    if !explain {
+        println!("{}", engine.redact(command)?);
+        return Ok(());
+    }
+
+    let explanation = engine.explain(command)?;
+
+    println!("Input:  {}", explanation.input);
+    if !app.config.enable_redaction {
+        println!("Note:   redaction is disabled in the configuration");
+    }
+    println!();
+
+    if explanation.matches.is_empty() {
+        println!("No rules matched");
+    } else {
+        println!("Matches:");
+        for m in &explanation.matches {
+            let status = match &m.suppressed {
+                Some(_) => "suppressed",
+                None => "applied",
+            };
+            println!(
+                "  [{}] {} bytes {}..{} {} {:?}",
+                status, m.rule_id, m.span.0, m.span.1, m.replacement_type, m.matched
+            );
+            if let Some(reason) = &m.suppressed {
+                println!("      {}", reason);
+            }
+            if app.verbose {
+                println!("      pattern: {}", m.pattern);
+            }
+        }
+    }
+
+    println!();
+    println!("Output: {}", explanation.output);
+
+    Ok(())
+}
+
+fn handle_redact_test(app: &CliApp, args: &RedactTestArgs) -> Result<()> {
+    let engine = RedactionEngine::from_config(&app.config)?;
+    let corpus = RedactionCorpus::load(&args.corpus)?;
+    let report = corpus.run(&engine)?;
+
+    for failure in &report.failures {
+        print_failure(&corpus, failure);
+    }
+
+    if !app.quiet {
+        println!(
+            "{} cases, {} passed, {} failed",
+            report.total,
+            report.total - report.failures.len(),
+            report.failures.len()
+        );
+    }
+
+    if report.passed() {
+        Ok(())
+    } else {
+        Err(Error::custom(format!(
+            "{} of {} redaction cases failed",
+            report.failures.len(),
+            report.total
+        )))
+    }
+}
+
+/// A value the engine redacts, as a candidate for allowlisting
+struct AllowCandidate {
+    rule_id: String,
+    value: String,
+    span: (usize, usize),
+}
+
+fn handle_redact_allow(app: &mut CliApp, args: &RedactAllowArgs) -> Result<()> {
+    let (command, label) = match (&args.command, args.command_id) {
+        (Some(command), _) => (command.clone(), "Command".to_string()),
+        (None, Some(id)) => {
+            let HistoryBackend::Database(mgr) = &mut app.backend else {
+                return Err(Error::custom(
+                    "Allowlisting from history requires database backend. \
+                     Use --use-db flag, or pass the command with --command.",
+                ));
+            };
+            (mgr.expand_command(id)?, format!("Command {}", id))
+        }
+        (None, None) => {
+            return Err(Error::invalid_arguments(
+                "Must specify a command ID or --command",
+            ));
+        }
+    };
+
+    // Work from what the engine redacts now, so values caught by a rule but
+    // never stored as tokens can be allowlisted too
+    let engine = RedactionEngine::from_config(&app.config)?;
+    let explanation = engine.explain(&command)?;
+
+    let mut candidates: Vec<AllowCandidate> = Vec::new();
+    for m in explanation
+        .matches
+        .iter()
+        .filter(|m| m.suppressed.is_none())
+    {
+        if candidates.iter().any(|c| c.value == m.value) {
+            continue;
+        }
+        // Later rules see earlier placeholders; only values from the
+        // command as written can be allowlisted
+        let Some(start) = command.find(&m.value) else {
+            continue;
+        };
+        candidates.push(AllowCandidate {
+            rule_id: m.rule_id.clone(),
+            span: (start, start + m.value.len()),
+            value: m.value.clone(),
+        });
+    }
+    candidates.sort_by_key(|c| c.span);
+
+    let candidate = match &args.span {
+        Some(span) => {
+            let span = parse_span(span)?;
+            candidates.iter().find(|c| c.span == span).ok_or_else(|| {
+                Error::invalid_arguments(format!(
+                    "No redacted value at bytes {}..{} of the command",
+                    span.0, span.1
+                ))
+            })?
+        }
+        None if candidates.len() == 1 => &candidates[0],
+        None if candidates.is_empty() => {
+            return Err(Error::invalid_arguments(format!(
+                "Nothing in the command is redacted: {}",
+                command
+            )));
+        }
+        None => {
+            println!("{}: {}", label, command);
+            println!("Redacted values:");
+            for c in &candidates {
+                println!(
+                    "  bytes {}..{} {} {:?}",
+                    c.span.0, c.span.1, c.rule_id, c.value
+                );
+            }
+            return Err(Error::invalid_arguments(
+                "Command has several redacted values; choose one with --span",
+            ));
+        }
+    };
+
+    println!("{}: {}", label, command);
+    println!("Rule:    {}", candidate.rule_id);
+    println!(
+        "Match:   {:?} (bytes {}..{})",
+        candidate.value, candidate.span.0, candidate.span.1
+    );
+
+    let pattern = if args.generalize {
+        match generalize_allowlist_pattern(&candidate.value) {
+            Some(pattern) => pattern,
+            None => {
+                println!("No safe generalization for this value, allowing it literally");
+                regex::escape(&candidate.value)
+            }
+        }
+    } else {
+        regex::escape(&candidate.value)
+    };
+
+    if app.config.redaction.allowlist.contains(&pattern) {
+        println!("Already allowlisted: {}", pattern);
+    } else if args.dry_run {
+        println!("Would add allowlist entry: {}", pattern);
+    } else {
+        app.config.redaction.allowlist.push(pattern.clone());
+        let path = app.save_config()?;
+        println!("Added allowlist entry: {}", pattern);
+        println!("Saved configuration to {}", path.display());
+    }
+
+    if args.restore
+        && let (Some(id), HistoryBackend::Database(mgr)) = (args.command_id, &mut app.backend)
+    {
+        let token = mgr
+            .get_tokens_for_command(id)?
+            .into_iter()
+            .find(|t| t.original_value == candidate.value);
+        match token {
+            None => println!("No stored token holds this value; nothing to restore"),
+            Some(token) if args.dry_run => {
+                println!("Would restore {} in command {}", token.placeholder, id);
+            }
+            Some(token) => {
+                let command = mgr.restore_token(id, &token.placeholder)?;
+                println!("Restored command {}: {}", id, command);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse a `START..END` byte span
+fn parse_span(span: &str) -> Result<(usize, usize)> {
+    span.split_once("..")
+        .and_then(|(start, end)| Some((start.parse().ok()?, end.parse().ok()?)))
+        .ok_or_else(|| {
+            Error::invalid_arguments(format!("Invalid span '{}', expected START..END", span))
+        })
+}
+
+fn print_failure(corpus: &RedactionCorpus, failure: &CorpusFailure) {
+    println!("FAIL {}:{}", corpus.path.display(), failure.case.line);
+    println!("  input:  {}", failure.case.command);
+
+    match failure.expected() {
+        Some(expected) => {
+            println!("- {}", expected);
+            println!("+ {}", failure.actual);
+            let column = first_difference(expected, &failure.actual);
+            println!("  {}^", " ".repeat(column));
+        }
+        None => {
+            println!("  expected the command to be redacted, but it was left unchanged");
+        }
+    }
+
+    if !failure.rules_applied.is_empty() {
+        println!("  rules:  {}", failure.rules_applied.join(", "));
+    }
+    println!();
+}
+
+/// Column (in chars) of the first position where two strings differ
+fn first_difference(a: &str, b: &str) -> usize {
+    a.chars().zip(b.chars()).take_while(|(x, y)| x == y).count()
+}
@@ -0,0 +1,149 @@
+//! Native shell history scan and scrub handlers for Mortimer CLI
+
+use crate::cli::CliApp;
+use crate::cli::args::*;
+use crate::error::{Error, Result};
+use crate::redaction::RedactionEngine;
+use crate::shell_history::{NativeHistory, Shell};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+pub fn handle_scan(app: &mut CliApp, args: &ScanArgs) -> Result<()> {
+    let (shell, path) = resolve_history_file(app, &args.file, &args.shell)?;
+    let engine = RedactionEngine::from_config(&app.config)?;
+    let history = NativeHistory::load(shell, &path)?;
+    let findings = history.scan(&engine)?;
+
+    for finding in &findings {
+        println!(
+            "{}:{}: {}: {}",
+            path.display(),
+            finding.line,
+            finding.rule_id,
+            finding.redacted.replace('\n', "\\n")
+        );
+    }
+
+    if !app.quiet {
+        let mut lines: Vec<usize> = findings.iter().map(|f| f.line).collect();
+        lines.dedup();
+        println!(
+            "{} findings in {} of {} {} commands",
+            findings.len(),
+            lines.len(),
+            history.command_count(),
+            shell.name()
+        );
+        print_unreadable(app, &history);
+    }
+
+    Ok(())
+}
+
+pub fn handle_scrub(app: &mut CliApp, args: &ScrubArgs) -> Result<()> {
+    let (shell, path) = resolve_history_file(app, &args.file, &args.shell)?;
+    let engine = RedactionEngine::from_config(&app.config)?;
+    let mut history = NativeHistory::load(shell, &path)?;
+    let changed = history.redact(&engine)?;
+
+    if changed == 0 {
+        if !app.quiet {
+            println!("No secrets found in {}", path.display());
+            print_unreadable(app, &history);
+        }
+        return Ok(());
+    }
+
+    if args.dry_run {
+        println!(
+            "DRY RUN: Would redact {} of {} commands in {}",
+            changed,
+            history.command_count(),
+            path.display()
+        );
+        print_unreadable(app, &history);
+        return Ok(());
+    }
+
+    if !args.force {
+        print!(
+            "Redact {} commands in {}? A backup of the original is kept (y/N): ",
+            changed,
+            path.display()
+        );
+        io::stdout().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        if !input.trim().eq_ignore_ascii_case("y") {
+            println!("Aborted");
+            return Ok(());
+        }
+    }
+
+    let backup = history.save(&path)?;
+
+    if !app.quiet {
+        println!("Redacted {} commands in {}", changed, path.display());
+        println!(
+            "The original is in {}; delete it once you have checked the result",
+            backup.display()
+        );
+        println!(
+            "Note: running {} sessions keep their own history and may write it back on exit",
+            shell.name()
+        );
+        print_unreadable(app, &history);
+    }
+
+    Ok(())
+}
+
+/// Work out which shell's history file to use
+pub(super) fn resolve_history_file(
+    app: &CliApp,
+    file: &Option<PathBuf>,
+    shell: &Option<ShellType>,
+) -> Result<(Shell, PathBuf)> {
+    let shell = match shell {
+        Some(ShellType::Zsh) => Shell::Zsh,
+        Some(ShellType::Bash) => Shell::Bash,
+        Some(ShellType::Fish) => Shell::Fish,
+        None => file.as_deref().and_then(Shell::detect).ok_or_else(|| {
+            Error::invalid_arguments("Cannot tell which shell wrote this file, use --shell")
+        })?,
+    };
+
+    let path = match file {
+        Some(path) => path.clone(),
+        None => app
+            .config
+            .import
+            .shell_history_paths
+            .get(shell.name())
+            .cloned()
+            .ok_or_else(|| {
+                Error::invalid_arguments(format!(
+                    "No history file configured for {}, pass the file to use",
+                    shell.name()
+                ))
+            })?,
+    };
+
+    Ok((shell, path))
+}
+
+fn print_unreadable(app: &CliApp, history: &NativeHistory) {
+    let lines = history.unreadable_lines();
+    if lines.is_empty() {
+        return;
+    }
+
+    eprintln!(
+        "Warning: skipped {} lines that are not valid UTF-8",
+        lines.len()
+    );
+    if app.verbose {
+        let lines: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+        eprintln!("  lines: {}", lines.join(", "));
+    }
+}
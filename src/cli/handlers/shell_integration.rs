@@ -2,7 +2,8 @@
 
 use crate::cli::args::*;
 use crate::cli::CliApp;
-use crate::error::Result;
+use crate::error::{Error, Result};
+use crate::incognito::{Incognito, SESSION_ENV};
 
 pub fn handle_shell(app: &CliApp, args: &ShellArgs) -> Result<()> {
     let shell_script = match args.shell {
@@ -10,6 +11,13 @@ pub fn handle_shell(app: &CliApp, args: &ShellArgs) -> Result<()> {
         ShellType::Bash => generate_bash_integration(),
         ShellType::Fish => generate_fish_integration(),
     };
+    // The prompt indicator checks the marker itself rather than running
+    // mortimer for every prompt
+    let incognito_dir = Incognito::from_config(&app.config);
+    let shell_script = shell_script.replace(
+        "@INCOGNITO_DIR@",
+        &shell_quote(&incognito_dir.dir().to_string_lossy()),
+    );
 
     if let Some(output_file) = &args.output {
         std::fs::write(output_file, shell_script)?;
@@ -23,29 +31,141 @@ pub fn handle_shell(app: &CliApp, args: &ShellArgs) -> Result<()> {
     Ok(())
 }
 
+pub fn handle_incognito(app: &mut CliApp, args: &IncognitoArgs) -> Result<()> {
+    let incognito = Incognito::from_config(&app.config);
+    let Some(session) = Incognito::current_session() else {
+        if matches!(args.action, IncognitoAction::Status) {
+            println!("Incognito: off (no shell session)");
+            return Ok(());
+        }
+        return Err(Error::custom(format!(
+            "{} is not set; load the shell integration (mortimer shell <shell>) first",
+            SESSION_ENV
+        )));
+    };
+
+    let active = incognito.is_active(&session);
+    let active = match args.action {
+        IncognitoAction::On => true,
+        IncognitoAction::Off => false,
+        IncognitoAction::Toggle => !active,
+        IncognitoAction::Status => {
+            println!("Incognito: {}", if active { "on" } else { "off" });
+            return Ok(());
+        }
+    };
+
+    incognito.set(&session, active)?;
+    if !app.quiet {
+        if active {
+            println!("Incognito on: commands from this shell are not logged");
+        } else {
+            println!("Incognito off: logging resumed");
+        }
+    }
+
+    Ok(())
+}
+
+/// Quote a value for use in a shell script
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
 fn generate_zsh_integration() -> String {
     r#"# Mortimer Zsh Integration
 # Add this to your ~/.zshrc
 
-# Custom history manager function
+# Identify this shell so incognito mode applies to it alone; nested
+# shells inherit the id, and with it incognito mode
+export MORTIMER_SESSION=${MORTIMER_SESSION:-$$-$RANDOM$RANDOM}
+MORTIMER_INCOGNITO_DIR=@INCOGNITO_DIR@
+
+# Remember each command and when it started; it is logged with its exit
+# code once it has finished, before the next prompt
+zmodload zsh/datetime
+mortimer_remember_command() {
+    MORTIMER_COMMAND=$1
+    MORTIMER_STARTED=$EPOCHSECONDS
+}
 log_command() {
-    mortimer log "$1"
+    local exit_code=$?
+    [[ -n "$MORTIMER_COMMAND" ]] || return
+    mortimer log -E "$exit_code" -T "$MORTIMER_STARTED" "$MORTIMER_COMMAND"
+    unset MORTIMER_COMMAND
 }
 
-# Hook to log commands before execution
+# Hooks to log commands after execution; log_command goes first so it sees
+# the command's exit status
 autoload -Uz add-zsh-hook
-add-zsh-hook preexec log_command
+add-zsh-hook preexec mortimer_remember_command
+precmd_functions=(log_command ${precmd_functions:#log_command})
 
 # Interactive history search with fzf (Ctrl+R)
+# Whatever is typed on the line is used as a search query first,
+# e.g. `docker dir:~/src after:yesterday` then Ctrl+R.
 mortimer-fzf-widget() {
-    BUFFER=$(mortimer fzf | fzf --height 50% --reverse --tac 2>/dev/tty)
-    CURSOR=$#BUFFER
+    local result
+    result=$(mortimer fzf -- "$BUFFER" | fzf --height 50% --reverse --tac 2>/dev/tty)
+    if [[ -n "$result" ]]; then
+        BUFFER=$result
+        CURSOR=$#BUFFER
+    fi
     zle reset-prompt
 }
 zle -N mortimer-fzf-widget
 
 # Replace default Ctrl-R with fzf search
 bindkey '^R' mortimer-fzf-widget
+
+# Rehydrate a redacted command: type its ID, then press Ctrl+X Ctrl+T.
+# With redaction.confirm_token_reveal set, it asks on the terminal first.
+# The expanded command starts with a space, so mortimer does not log it,
+# and when it runs it is kept out of this shell's history.
+mortimer-expand-widget() {
+    local expanded
+    zle -I
+    if expanded=$(mortimer expand "$BUFFER" </dev/tty 2>/dev/tty); then
+        BUFFER=" $expanded"
+        CURSOR=$#BUFFER
+        MORTIMER_EXPANDED=1
+    else
+        zle -M "mortimer: cannot expand '$BUFFER'"
+    fi
+}
+zle -N mortimer-expand-widget
+bindkey '^X^T' mortimer-expand-widget
+mortimer_skip_expanded() {
+    [[ -n $MORTIMER_EXPANDED ]] || return 0
+    unset MORTIMER_EXPANDED
+    [[ $1 != ' '* ]]
+}
+add-zsh-hook zshaddhistory mortimer_skip_expanded
+
+# Incognito mode: Ctrl+X Ctrl+P pauses or resumes logging for this shell.
+# To show it in the prompt:
+#   setopt PROMPT_SUBST; PROMPT='$(mortimer_incognito_prompt)'$PROMPT
+mortimer_incognito_prompt() {
+    [[ -e "$MORTIMER_INCOGNITO_DIR/$MORTIMER_SESSION" ]] && print -n "[incognito] "
+}
+mortimer-incognito-widget() {
+    zle -M "$(mortimer incognito toggle 2>&1)"
+    zle reset-prompt
+}
+zle -N mortimer-incognito-widget
+bindkey '^X^P' mortimer-incognito-widget
+
+# Forget the last command, e.g. a pasted password: Ctrl+X Ctrl+F removes
+# it from mortimer, from $HISTFILE and from this shell's history list
+mortimer-forget-widget() {
+    # Save this shell's new commands so they can be removed from the file
+    fc -AI
+    zle -M "$(mortimer forget --force --shred --native zsh --native-file "${HISTFILE:-$HOME/.zsh_history}" 2>&1)"
+    # Switch to a history list read back from the cleaned file
+    fc -p "${HISTFILE:-$HOME/.zsh_history}" "$HISTSIZE" "$SAVEHIST"
+}
+zle -N mortimer-forget-widget
+bindkey '^X^F' mortimer-forget-widget
 "#
     .to_string()
 }
@@ -54,16 +174,85 @@ fn generate_bash_integration() -> String {
     r#"# Mortimer Bash Integration
 # Add this to your ~/.bashrc
 
-# Function to log commands
+# Identify this shell so incognito mode applies to it alone; nested
+# shells inherit the id, and with it incognito mode
+export MORTIMER_SESSION=${MORTIMER_SESSION:-$$-$RANDOM$RANDOM}
+MORTIMER_INCOGNITO_DIR=@INCOGNITO_DIR@
+
+# Log the command that just finished, with its exit code. It is read back
+# from the history list; an empty line leaves the list as it was, so the
+# last entry is remembered to avoid logging it twice. The first prompt only
+# remembers the entry, which comes from the history file.
 log_command() {
-    mortimer log "$1"
+    local exit_code=$? entry
+    entry=$(HISTTIMEFORMAT= history 1)
+    if [[ -z ${MORTIMER_LAST_ENTRY+set} ]]; then
+        MORTIMER_LAST_ENTRY=$entry
+        return
+    fi
+    [[ -n "$entry" && "$entry" != "$MORTIMER_LAST_ENTRY" ]] || return
+    # A command expanded by mortimer_expand, still starting with its space,
+    # holds secrets: drop it from the history list instead of logging it
+    if [[ -n $MORTIMER_EXPANDED ]]; then
+        unset MORTIMER_EXPANDED
+        if [[ $(sed -e 's/^ *[0-9]*[* ] //' <<< "$entry") == ' '* ]]; then
+            history -d -1
+            MORTIMER_LAST_ENTRY=$(HISTTIMEFORMAT= history 1)
+            return
+        fi
+    fi
+    MORTIMER_LAST_ENTRY=$entry
+    mortimer log -E "$exit_code" "$(sed -e 's/^ *[0-9]*[* ] *//' <<< "$entry")"
 }
 
-# Hook to log commands after execution
-PROMPT_COMMAND="log_command \"\$BASH_COMMAND\"; $PROMPT_COMMAND"
+# Hook to log commands after execution; it runs first so it sees the
+# command's exit status
+PROMPT_COMMAND="log_command${PROMPT_COMMAND:+; $PROMPT_COMMAND}"
 
 # Interactive history search with fzf (Ctrl+R)
-bind -x '"\C-r": "READLINE_LINE=$(mortimer fzf | fzf --height 50% --reverse --tac 2>/dev/tty); READLINE_POINT=${#READLINE_LINE}"'
+# Whatever is typed on the line is used as a search query first,
+# e.g. `docker dir:~/src after:yesterday` then Ctrl+R.
+mortimer_fzf_search() {
+    local result
+    result=$(mortimer fzf -- "$READLINE_LINE" | fzf --height 50% --reverse --tac 2>/dev/tty)
+    if [[ -n "$result" ]]; then
+        READLINE_LINE=$result
+        READLINE_POINT=${#READLINE_LINE}
+    fi
+}
+bind -x '"\C-r": mortimer_fzf_search'
+
+# Rehydrate a redacted command: type its ID, then press Ctrl+X Ctrl+T.
+# With redaction.confirm_token_reveal set, it asks on the terminal first.
+# The expanded command starts with a space; when it runs, log_command drops
+# it from the history list instead of logging it.
+mortimer_expand() {
+    local expanded
+    if expanded=$(mortimer expand "$READLINE_LINE" </dev/tty 2>/dev/tty); then
+        READLINE_LINE=" $expanded"
+        READLINE_POINT=${#READLINE_LINE}
+        MORTIMER_EXPANDED=1
+    else
+        echo "mortimer: cannot expand '$READLINE_LINE'" >&2
+    fi
+}
+bind -x '"\C-x\C-t": mortimer_expand'
+
+# Incognito mode: Ctrl+X Ctrl+P pauses or resumes logging for this shell.
+# To show it in the prompt:
+#   PS1='$(mortimer_incognito_prompt)'$PS1
+mortimer_incognito_prompt() {
+    [[ -e "$MORTIMER_INCOGNITO_DIR/$MORTIMER_SESSION" ]] && printf '[incognito] '
+}
+bind -x '"\C-x\C-p": mortimer incognito toggle'
+
+# Forget the last command, e.g. a pasted password: Ctrl+X Ctrl+F removes
+# it from mortimer, from $HISTFILE and from this shell's history list
+mortimer_forget() {
+    mortimer forget --force --shred --native bash --native-file "${HISTFILE:-$HOME/.bash_history}" &&
+        history -d -1
+}
+bind -x '"\C-x\C-f": mortimer_forget'
 "#
     .to_string()
 }
@@ -72,14 +261,23 @@ fn generate_fish_integration() -> String {
     r#"# Mortimer Fish Integration
 # Add this to your ~/.config/fish/config.fish
 
-# Function to log commands
-function mortimer_log_command --on-event fish_preexec
-    mortimer log "$argv[1]" &
+# Identify this shell so incognito mode applies to it alone; nested
+# shells inherit the id, and with it incognito mode
+set -q MORTIMER_SESSION; or set -gx MORTIMER_SESSION $fish_pid-(random)(random)
+set -g MORTIMER_INCOGNITO_DIR @INCOGNITO_DIR@
+
+# Log commands after execution, with their exit code
+function mortimer_log_command --on-event fish_postexec
+    set -l exit_code $status
+    test -n "$argv[1]"; or return
+    mortimer log -E $exit_code "$argv[1]" &
 end
 
 # Interactive history search with fzf (Ctrl+R)
+# Whatever is typed on the line is used as a search query first,
+# e.g. `docker dir:~/src after:yesterday` then Ctrl+R.
 function mortimer_fzf_search
-    set -l result (mortimer fzf | fzf --height 50% --reverse --tac 2>/dev/tty)
+    set -l result (mortimer fzf -- (commandline) | fzf --height 50% --reverse --tac 2>/dev/tty)
     if test -n "$result"
         commandline -r "$result"
     end
@@ -88,6 +286,40 @@ end
 
 # Replace default Ctrl-R with fzf search
 bind \cr mortimer_fzf_search
+
+# Rehydrate a redacted command: type its ID, then press Ctrl+X Ctrl+T.
+# Fish and mortimer both skip space-prefixed commands, so the secret
+# stays out of history. With redaction.confirm_token_reveal set, it asks on
+# the terminal first.
+function mortimer_expand
+    set -l expanded (mortimer expand (commandline) </dev/tty 2>/dev/tty | string collect)
+    and commandline -r " $expanded"
+    or echo "mortimer: cannot expand '"(commandline)"'" >&2
+    commandline -f repaint
+end
+bind \cx\ct mortimer_expand
+
+# Incognito mode: Ctrl+X Ctrl+P pauses or resumes logging for this shell.
+# To show it in the prompt, call mortimer_incognito_prompt from
+# fish_prompt or fish_right_prompt.
+function mortimer_incognito_prompt
+    test -e "$MORTIMER_INCOGNITO_DIR/$MORTIMER_SESSION"; and echo -n "[incognito] "
+end
+function mortimer_incognito_toggle
+    mortimer incognito toggle
+    commandline -f repaint
+end
+bind \cx\cp mortimer_incognito_toggle
+
+# Forget the last command, e.g. a pasted password: Ctrl+X Ctrl+F removes
+# it from mortimer and from fish's own history
+function mortimer_forget
+    set -l last $history[1]
+    mortimer forget --force --shred
+    and builtin history delete --exact --case-sensitive -- "$last"
+    commandline -f repaint
+end
+bind \cx\cf mortimer_forget
 "#
     .to_string()
 }
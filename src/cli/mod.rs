@@ -15,10 +15,12 @@ use crate::config::Config;
 use crate::error::Result;
 use crate::history::HistoryManager;
 use crate::history_db::HistoryManagerDb;
+use crate::retention::RetentionSchedule;
 use crate::search::SearchEngine;
+use crate::timezone::DisplayTimezone;
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
-use tracing::debug;
+use tracing::{debug, warn};
 
 /// Mortimer - Enhanced shell history manager with sensitive data redaction
 #[derive(Parser)]
@@ -64,16 +66,34 @@ pub enum Commands {
     Import(ImportArgs),
     /// Export history to various formats
     Export(ExportArgs),
+    /// Scan a shell's own history file for secrets
+    Scan(ScanArgs),
+    /// Redact secrets in a shell's own history file in place
+    Scrub(ScrubArgs),
     /// Show history statistics
     Stats(StatsArgs),
     /// Clear history
     Clear(ClearArgs),
+    /// Remove commands so they cannot be recovered from disk
+    Shred(ShredArgs),
+    /// List, restore or empty deleted commands
+    Trash(TrashArgs),
+    /// Apply retention policies to expire old commands
+    Gc(GcArgs),
+    /// List or restore archived history (file backend)
+    Archive(ArchiveArgs),
+    /// Move the last commands of a shell session to the trash, or shred them
+    Forget(ForgetArgs),
     /// Show configuration
     Config(ConfigArgs),
+    /// List exclusion rules or test a command against them
+    Exclude(ExcludeArgs),
     /// Output commands for fuzzy finder (fzf)
     Fzf(FzfArgs),
     /// Generate shell integration scripts
     Shell(ShellArgs),
+    /// Pause logging for the current shell session
+    Incognito(IncognitoArgs),
     /// Show recent commands
     Recent(RecentArgs),
     /// Show frequent commands
@@ -82,14 +102,20 @@ pub enum Commands {
     Manage,
     /// Validate redaction patterns
     Validate(ValidateArgs),
+    /// Redact a command with the configured rules
+    Redact(RedactArgs),
     /// Show backend status and configuration
     Status,
     /// Migrate from legacy .mhist file to database
     Migrate(MigrateArgs),
     /// Merge databases from different machines
     Merge(MergeArgs),
+    /// Encrypt, decrypt or rekey the history database
+    Db(DbArgs),
     /// Manage and retrieve stored tokens
     Tokens(TokensArgs),
+    /// Rebuild a redacted command from its stored tokens
+    Expand(ExpandArgs),
     /// List and manage hosts
     Hosts(HostsArgs),
     /// List and manage sessions
@@ -105,8 +131,12 @@ pub(crate) enum HistoryBackend {
 /// Main CLI application
 pub struct CliApp {
     pub config: Config,
+    /// Configuration file given with `--config`, if any
+    pub config_path: Option<PathBuf>,
     pub(crate) backend: HistoryBackend,
     pub search_engine: SearchEngine,
+    /// Zone that times are shown and read in
+    pub timezone: DisplayTimezone,
     pub verbose: bool,
     pub quiet: bool,
     #[allow(dead_code)]
@@ -124,7 +154,11 @@ impl CliApp {
         };
 
         // Determine which backend to use
-        let backend = if cli.use_file {
+        let backend = if matches!(cli.command, Commands::Db(_)) {
+            // Converting the database works on the file, which may not open
+            // with the configured key until it has been converted
+            HistoryBackend::File(HistoryManager::new(config.clone())?)
+        } else if cli.use_file {
             // Explicitly use file backend
             HistoryBackend::File(HistoryManager::new(config.clone())?)
         } else if cli.use_db || std::env::var("MORTIMER_USE_DB").is_ok() {
@@ -141,25 +175,43 @@ impl CliApp {
         };
 
         // Initialize search engine
-        let search_engine = SearchEngine::with_config(
-            config.search.fuzzy_search,
-            config.search.case_sensitive,
-            config.search.include_directory,
-            config.search.include_timestamps,
-            config.search.max_results,
-            config.search.highlight_matches,
-        );
+        let search_engine = SearchEngine {
+            normalize_unicode: config.search.normalize_unicode,
+            ignore_accents: config.search.ignore_accents,
+            ..SearchEngine::with_config(
+                config.search.fuzzy_search,
+                config.search.case_sensitive,
+                config.search.include_directory,
+                config.search.include_timestamps,
+                config.search.max_results,
+                config.search.highlight_matches,
+            )
+        };
+
+        let timezone = DisplayTimezone::from_config(&config)?;
 
         Ok(Self {
             config,
+            config_path: cli.config.clone(),
             backend,
             search_engine,
+            timezone,
             verbose: cli.verbose,
             quiet: cli.quiet,
             no_color: cli.no_color,
         })
     }
 
+    /// Save the configuration to the file it was loaded from
+    pub fn save_config(&self) -> Result<PathBuf> {
+        let path = match &self.config_path {
+            Some(path) => path.clone(),
+            None => Config::default_config_path()?,
+        };
+        self.config.save_to_path(&path)?;
+        Ok(path)
+    }
+
     /// Run the CLI application
     pub fn run(&mut self, command: &Commands) -> Result<()> {
         // Log backend info
@@ -172,35 +224,92 @@ impl CliApp {
             }
         }
 
+        // `gc` applies the policies itself, and may only be asked to show
+        // what they would expire
+        if !matches!(command, Commands::Gc(_) | Commands::Db(_)) {
+            self.apply_retention_if_due();
+        }
+
         match command {
             Commands::Log(args) => handle_log(self, args),
             Commands::Search(args) => handle_search(self, args),
             Commands::Import(args) => handle_import(self, args),
             Commands::Export(args) => handle_export(self, args),
+            Commands::Scan(args) => handle_scan(self, args),
+            Commands::Scrub(args) => handle_scrub(self, args),
             Commands::Stats(args) => handle_stats(self, args),
             Commands::Clear(args) => handle_clear(self, args),
+            Commands::Shred(args) => handle_shred(self, args),
+            Commands::Trash(args) => handle_trash(self, args),
+            Commands::Gc(args) => handle_gc(self, args),
+            Commands::Archive(args) => handle_archive(self, args),
+            Commands::Forget(args) => handle_forget(self, args),
             Commands::Config(args) => handle_config(self, args),
+            Commands::Exclude(args) => handle_exclude(self, args),
             Commands::Fzf(args) => handle_fzf(self, args),
             Commands::Shell(args) => handle_shell(self, args),
+            Commands::Incognito(args) => handle_incognito(self, args),
             Commands::Recent(args) => handle_recent(self, args),
             Commands::Frequent(args) => handle_frequent(self, args),
             Commands::Manage => handle_manage(self),
             Commands::Validate(args) => handle_validate(self, args),
+            Commands::Redact(args) => handle_redact(self, args),
             Commands::Status => handle_status(self),
             Commands::Migrate(args) => handle_migrate(self, args),
             Commands::Merge(args) => handle_merge(self, args),
+            Commands::Db(args) => handle_db(self, args),
             Commands::Tokens(args) => handle_tokens(self, args),
+            Commands::Expand(args) => handle_expand(self, args),
             Commands::Hosts(args) => handle_hosts(self, args),
             Commands::Sessions(args) => handle_sessions(self, args),
         }
     }
 
+    /// Apply the retention policies if they have not been applied in the
+    /// last day; failures are logged rather than failing the command
+    fn apply_retention_if_due(&mut self) {
+        if !self.config.retention.apply_on_open || self.config.retention.policies.is_empty() {
+            return;
+        }
+
+        let schedule = RetentionSchedule::from_config(&self.config);
+        let now = chrono::Utc::now();
+        if !schedule.is_due(now) {
+            return;
+        }
+
+        match self.provider_mut().apply_retention(false) {
+            Ok(expired) => {
+                if !expired.is_empty() {
+                    debug!(
+                        "Retention policies moved {} commands to the trash",
+                        expired.len()
+                    );
+                }
+                if let Err(e) = schedule.mark(now) {
+                    warn!("Could not record retention run: {}", e);
+                }
+            }
+            Err(e) => warn!("Could not apply retention policies: {}", e),
+        }
+    }
+
     pub fn verbose_println(&self, message: &str) {
         if self.verbose && !self.quiet {
             eprintln!("[verbose] {}", message);
         }
     }
 
+    /// Session of the calling shell as the backend records it, if known
+    ///
+    /// Only the database backend records sessions.
+    pub fn current_session_id(&self) -> Result<Option<String>> {
+        match &self.backend {
+            HistoryBackend::File(_) => Ok(None),
+            HistoryBackend::Database(mgr) => mgr.current_session_id(),
+        }
+    }
+
     /// Get a reference to the backend as a HistoryProvider trait object
     pub fn provider(&self) -> &dyn crate::backend::HistoryProvider {
         match &self.backend {
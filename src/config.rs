@@ -19,6 +19,15 @@ pub const DEFAULT_MAX_ENTRIES: usize = 100_000;
 /// Default redaction replacement text
 pub const DEFAULT_REDACTION_PLACEHOLDER: &str = "<redacted>";
 
+/// Default time to wait for an external detector, in milliseconds
+pub const DEFAULT_DETECTOR_TIMEOUT_MS: u64 = 1000;
+
+/// Default number of days deleted commands stay in the trash
+pub const DEFAULT_TRASH_RETENTION_DAYS: u32 = 30;
+
+/// Environment variable holding the passphrase of an encrypted database
+pub const DB_KEY_ENV: &str = "MORTIMER_DB_KEY";
+
 /// Main configuration structure for Mortimer
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -46,10 +55,158 @@ pub struct Config {
     /// Shell integration settings
     pub shell_integration: ShellIntegrationConfig,
 
+    /// Database backend settings
+    #[serde(default)]
+    pub database: DatabaseConfig,
+
+    /// Trash settings for deleted commands
+    #[serde(default)]
+    pub trash: TrashConfig,
+
+    /// Retention policies that expire old commands
+    #[serde(default)]
+    pub retention: RetentionConfig,
+
+    /// Archive settings for the file backend
+    #[serde(default)]
+    pub archive: ArchiveConfig,
+
+    /// How times are shown
+    #[serde(default)]
+    pub display: DisplayConfig,
+
     /// Custom environment variables to redact
     pub custom_env_vars: Vec<String>,
 }
 
+/// Configuration for the trash that deleted commands are moved to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashConfig {
+    /// Days a deleted command stays restorable; unset keeps it until the
+    /// trash is emptied
+    #[serde(default)]
+    pub retention_days: Option<u32>,
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        Self {
+            retention_days: Some(DEFAULT_TRASH_RETENTION_DAYS),
+        }
+    }
+}
+
+/// Configuration for archiving old entries of the file backend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveConfig {
+    /// Move entries beyond `max_entries` into compressed archive segments
+    /// instead of deleting them
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// Configuration for showing times
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DisplayConfig {
+    /// Timezone that times are shown in and dates are read in: `local`,
+    /// `logged`, `UTC`, an offset such as `+02:00` or a name such as
+    /// `Europe/Berlin`; unset uses the system timezone
+    #[serde(default)]
+    pub timezone: Option<String>,
+}
+
+/// Configuration for expiring old commands
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionConfig {
+    /// Policies tried in order; the first whose conditions match a command
+    /// decides how long it is kept
+    #[serde(default)]
+    pub policies: Vec<RetentionPolicyConfig>,
+
+    /// Apply the policies when the history is opened, at most once a day
+    #[serde(default = "default_true")]
+    pub apply_on_open: bool,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            policies: Vec::new(),
+            apply_on_open: true,
+        }
+    }
+}
+
+/// How long to keep the commands a policy matches
+///
+/// Every condition that is set must match for the policy to apply.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPolicyConfig {
+    /// Name shown by `mortimer gc`
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// Regex matched against the stored command, e.g. `^(ls|cd|git status)`
+    #[serde(default)]
+    pub regex: Option<String>,
+
+    /// Only commands with stored tokens (database backend)
+    #[serde(default)]
+    pub has_tokens: bool,
+
+    /// Only commands that exited with a non-zero code (database backend)
+    #[serde(default)]
+    pub failed: bool,
+
+    /// Days to keep matching commands
+    pub max_age_days: u32,
+}
+
+/// Configuration for the database backend
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DatabaseConfig {
+    /// File holding the passphrase of an encrypted database
+    ///
+    /// [`DB_KEY_ENV`] takes precedence when it is set. Encryption needs a
+    /// build with the `sqlcipher` feature.
+    #[serde(default)]
+    pub key_file: Option<PathBuf>,
+}
+
+impl DatabaseConfig {
+    /// The database passphrase from the environment or the key file, if any
+    pub fn key(&self) -> Result<Option<String>> {
+        if let Ok(key) = std::env::var(DB_KEY_ENV) {
+            if key.is_empty() {
+                return Err(Error::config_validation(
+                    DB_KEY_ENV.to_string(),
+                    "must not be empty".to_string(),
+                ));
+            }
+            return Ok(Some(key));
+        }
+
+        let Some(path) = &self.key_file else {
+            return Ok(None);
+        };
+        let key = fs::read_to_string(path)?;
+        let key = key.trim_end_matches(['\r', '\n']);
+        if key.is_empty() {
+            return Err(Error::config_validation(
+                "database.key_file".to_string(),
+                format!("{} is empty", path.display()),
+            ));
+        }
+        Ok(Some(key.to_string()))
+    }
+}
+
 /// Configuration for redaction behavior
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RedactionConfig {
@@ -65,11 +222,110 @@ pub struct RedactionConfig {
     /// Patterns to exclude from redaction
     pub exclude_patterns: Vec<String>,
 
+    /// Values never redacted, as regexes each matched against a whole
+    /// redacted value rather than the command
+    #[serde(default)]
+    pub allowlist: Vec<String>,
+
     /// Whether to redact environment variables
     pub redact_env_vars: bool,
 
     /// Minimum length for values to be considered for redaction
     pub min_redaction_length: usize,
+
+    /// How detected secrets are handled, keyed by rule id or token type
+    ///
+    /// Keys are redaction rule ids (`pem_block`, `custom:0`, `env:TOKEN`) or
+    /// extracted token types (`password`, `api_key`). Anything not listed
+    /// uses the `redact` policy.
+    #[serde(default)]
+    pub policies: HashMap<String, SecretPolicy>,
+
+    /// Template for correlatable placeholders, e.g. `<{type}:{hash8}>`
+    ///
+    /// When set, redacted values are replaced by a keyed hash of the value
+    /// instead of `placeholder`, so the same secret always gets the same
+    /// placeholder. See [`crate::placeholder`].
+    #[serde(default)]
+    pub placeholder_template: Option<String>,
+
+    /// Delete stored tokens older than this many days when the database is
+    /// opened; tokens are kept forever when unset
+    #[serde(default)]
+    pub token_retention_days: Option<u32>,
+
+    /// Require typing a confirmation before stored token values are shown
+    #[serde(default)]
+    pub confirm_token_reveal: bool,
+
+    /// External executables that find secrets the patterns cannot
+    ///
+    /// See [`crate::detector`] for the protocol.
+    #[serde(default)]
+    pub detectors: Vec<DetectorConfig>,
+}
+
+/// An external secret detector
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DetectorConfig {
+    /// Name of the detector; its matches use the rule id `detector:<name>`
+    pub name: String,
+
+    /// Executable to run
+    pub command: PathBuf,
+
+    /// Arguments passed to the executable
+    #[serde(default)]
+    pub args: Vec<String>,
+
+    /// How long to wait for each reply, in milliseconds
+    #[serde(default = "default_detector_timeout_ms")]
+    pub timeout_ms: u64,
+
+    /// What to do when the detector fails, times out or replies nonsense
+    #[serde(default)]
+    pub on_failure: DetectorFailure,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_detector_timeout_ms() -> u64 {
+    DEFAULT_DETECTOR_TIMEOUT_MS
+}
+
+/// How a failing detector affects the command being redacted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DetectorFailure {
+    /// Warn and carry on with the other rules
+    #[default]
+    Open,
+    /// Fail the redaction, so the command is not logged
+    Closed,
+}
+
+/// How a detected secret is handled when a command is logged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretPolicy {
+    /// Store the redacted command and keep the original value as a token
+    #[default]
+    Redact,
+    /// Store the redacted command but never keep the original value
+    RedactNoToken,
+    /// Do not log the command at all
+    Drop,
+    /// Store the redacted command and warn the user
+    Warn,
+}
+
+/// Combined effect of every secret policy that applies to a command
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PolicyDecision {
+    /// Rules or token types with a non-default policy, and that policy
+    pub matched: Vec<(String, SecretPolicy)>,
 }
 
 /// Configuration for importing history from other shells
@@ -111,6 +367,14 @@ pub struct SearchConfig {
 
     /// Whether to highlight matches in search results
     pub highlight_matches: bool,
+
+    /// Whether to match compatibility forms (NFKC), such as `ﬁ` for `fi`
+    #[serde(default)]
+    pub normalize_unicode: bool,
+
+    /// Whether accented letters match their plain forms, such as `e` for `é`
+    #[serde(default)]
+    pub ignore_accents: bool,
 }
 
 /// Configuration for logging
@@ -138,9 +402,16 @@ pub struct ShellIntegrationConfig {
     /// Whether to automatically log all commands
     pub auto_log: bool,
 
-    /// Commands to exclude from logging
+    /// Programs to exclude from logging
+    ///
+    /// A single word matches the program name, so `ls` does not exclude
+    /// `lsof`; several words match the start of the command.
     pub exclude_commands: Vec<String>,
 
+    /// Rules deciding which commands are logged, see [`crate::exclusion`]
+    #[serde(default)]
+    pub exclusion_rules: Vec<ExclusionRuleConfig>,
+
     /// Whether to log commands that start with a space
     pub log_space_prefixed: bool,
 
@@ -151,6 +422,51 @@ pub struct ShellIntegrationConfig {
     pub min_command_length: usize,
 }
 
+/// A rule that excludes commands from logging, or allows them regardless
+///
+/// Every condition that is set must match for the rule to apply.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExclusionRuleConfig {
+    /// Name shown by `mortimer exclude --test`
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// Whether a match excludes the command or allows it
+    #[serde(default)]
+    pub action: ExclusionAction,
+
+    /// Program names, e.g. `ls`; wrappers such as `sudo` are looked through
+    #[serde(default)]
+    pub programs: Vec<String>,
+
+    /// Regex matched against the whole command
+    #[serde(default)]
+    pub regex: Option<String>,
+
+    /// Glob matched against the working directory, e.g. `~/secrets/**`
+    #[serde(default)]
+    pub cwd: Option<String>,
+
+    /// Environment marker, `NAME` to match when it is set or `NAME=value`
+    #[serde(default)]
+    pub env: Option<String>,
+
+    /// Glob matched against the hostname
+    #[serde(default)]
+    pub host: Option<String>,
+}
+
+/// What a matching exclusion rule does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExclusionAction {
+    /// Do not log the command
+    #[default]
+    Exclude,
+    /// Log the command even if exclude rules match
+    Allow,
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -163,6 +479,11 @@ impl Default for Config {
             search: SearchConfig::default(),
             logging: LoggingConfig::default(),
             shell_integration: ShellIntegrationConfig::default(),
+            database: DatabaseConfig::default(),
+            trash: TrashConfig::default(),
+            retention: RetentionConfig::default(),
+            archive: ArchiveConfig::default(),
+            display: DisplayConfig::default(),
             custom_env_vars: vec![
                 "PASSWORD".to_string(),
                 "SECRET".to_string(),
@@ -181,12 +502,89 @@ impl Default for RedactionConfig {
             use_builtin_patterns: true,
             custom_patterns: Vec::new(),
             exclude_patterns: Vec::new(),
+            allowlist: Vec::new(),
             redact_env_vars: true,
             min_redaction_length: 3,
+            policies: HashMap::new(),
+            placeholder_template: None,
+            token_retention_days: None,
+            confirm_token_reveal: false,
+            detectors: Vec::new(),
         }
     }
 }
 
+impl RedactionConfig {
+    /// Resolve the policies for the rule ids and token types found in a command
+    pub fn resolve_policy<'a>(&self, keys: impl IntoIterator<Item = &'a str>) -> PolicyDecision {
+        let mut matched: Vec<(String, SecretPolicy)> = Vec::new();
+
+        for key in keys {
+            if let Some(&policy) = self.policies.get(key)
+                && policy != SecretPolicy::Redact
+                && !matched.iter().any(|(k, _)| k == key)
+            {
+                matched.push((key.to_string(), policy));
+            }
+        }
+
+        PolicyDecision { matched }
+    }
+}
+
+impl SecretPolicy {
+    /// Name of the policy as written in the configuration
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SecretPolicy::Redact => "redact",
+            SecretPolicy::RedactNoToken => "redact_no_token",
+            SecretPolicy::Drop => "drop",
+            SecretPolicy::Warn => "warn",
+        }
+    }
+}
+
+impl PolicyDecision {
+    /// Whether the command must not be logged
+    pub fn should_drop(&self) -> bool {
+        self.has(SecretPolicy::Drop)
+    }
+
+    /// Whether original values may be kept in the tokens table
+    pub fn store_tokens(&self) -> bool {
+        !self.has(SecretPolicy::RedactNoToken)
+    }
+
+    /// Whether the user should be warned about the command
+    pub fn should_warn(&self) -> bool {
+        self.has(SecretPolicy::Warn)
+    }
+
+    /// Non-default policies that apply, without duplicates
+    pub fn actions(&self) -> Vec<SecretPolicy> {
+        let mut actions: Vec<SecretPolicy> = Vec::new();
+        for (_, policy) in &self.matched {
+            if !actions.contains(policy) {
+                actions.push(*policy);
+            }
+        }
+        actions
+    }
+
+    /// Rules or token types that asked for the given policy
+    pub fn rules_with(&self, policy: SecretPolicy) -> Vec<String> {
+        self.matched
+            .iter()
+            .filter(|(_, p)| *p == policy)
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    fn has(&self, policy: SecretPolicy) -> bool {
+        self.matched.iter().any(|(_, p)| *p == policy)
+    }
+}
+
 impl Default for ImportConfig {
     fn default() -> Self {
         let mut shell_history_paths = HashMap::new();
@@ -220,6 +618,8 @@ impl Default for SearchConfig {
             include_timestamps: false,
             max_results: 1000,
             highlight_matches: true,
+            normalize_unicode: false,
+            ignore_accents: false,
         }
     }
 }
@@ -247,6 +647,7 @@ impl Default for ShellIntegrationConfig {
                 "clear".to_string(),
                 "history".to_string(),
             ],
+            exclusion_rules: Vec::new(),
             log_space_prefixed: false,
             log_duplicates: false,
             min_command_length: 1,
@@ -267,9 +668,9 @@ impl Config {
             return Ok(Self::default());
         }
 
-        let content = fs::read_to_string(path).map_err(|e| Error::Io(e))?;
+        let content = fs::read_to_string(path).map_err(Error::Io)?;
 
-        let config: Config = serde_json::from_str(&content).map_err(|e| Error::Json(e))?;
+        let config: Config = serde_json::from_str(&content).map_err(Error::Json)?;
 
         config.validate()?;
         Ok(config)
@@ -302,6 +703,11 @@ impl Config {
         Ok(home.join(DEFAULT_CONFIG_FILE))
     }
 
+    /// Path of the secret key used for correlatable placeholders
+    pub fn placeholder_key_path(&self) -> PathBuf {
+        self.history_file.with_extension("key")
+    }
+
     /// Validate the configuration
     pub fn validate(&self) -> Result<()> {
         // Validate redaction patterns
@@ -311,13 +717,56 @@ impl Config {
             })?;
         }
 
-        // Validate exclude patterns
-        for pattern in &self.redaction.exclude_patterns {
+        // Validate exclude patterns and allowlist entries
+        for pattern in self
+            .redaction
+            .exclude_patterns
+            .iter()
+            .chain(&self.redaction.allowlist)
+        {
             regex::Regex::new(pattern).map_err(|_| Error::InvalidRedactionPattern {
                 pattern: pattern.clone(),
             })?;
         }
 
+        if let Some(template) = &self.redaction.placeholder_template {
+            crate::placeholder::PlaceholderTemplate::validate(template)?;
+        }
+
+        for detector in &self.redaction.detectors {
+            if detector.name.is_empty() || detector.name.contains(char::is_whitespace) {
+                return Err(Error::config_validation(
+                    "redaction.detectors".to_string(),
+                    format!("invalid detector name '{}'", detector.name),
+                ));
+            }
+            if detector.timeout_ms == 0 {
+                return Err(Error::config_validation(
+                    "redaction.detectors".to_string(),
+                    format!("timeout_ms for '{}' must be greater than 0", detector.name),
+                ));
+            }
+        }
+
+        crate::exclusion::ExclusionRules::from_config(self)?;
+
+        if self.redaction.token_retention_days == Some(0) {
+            return Err(Error::config_validation(
+                "redaction.token_retention_days",
+                "must be greater than 0, or unset to keep tokens",
+            ));
+        }
+
+        if self.trash.retention_days == Some(0) {
+            return Err(Error::config_validation(
+                "trash.retention_days",
+                "must be greater than 0, or unset to keep deleted commands",
+            ));
+        }
+
+        crate::retention::RetentionPolicies::from_config(self)?;
+        crate::timezone::DisplayTimezone::from_config(self)?;
+
         // Validate max entries
         if self.max_entries == 0 {
             return Err(Error::config_validation(
@@ -358,6 +807,11 @@ impl Config {
         self.search = other.search.clone();
         self.logging = other.logging.clone();
         self.shell_integration = other.shell_integration.clone();
+        self.database = other.database.clone();
+        self.trash = other.trash.clone();
+        self.retention = other.retention.clone();
+        self.archive = other.archive.clone();
+        self.display = other.display.clone();
         self.custom_env_vars = other.custom_env_vars.clone();
     }
 
@@ -380,28 +834,6 @@ impl Config {
         patterns.extend(self.redaction.custom_patterns.clone());
         patterns
     }
-
-    /// Check if a command should be excluded from logging
-    pub fn should_exclude_command(&self, command: &str) -> bool {
-        // Check if command is in exclude list
-        for excluded in &self.shell_integration.exclude_commands {
-            if command.starts_with(excluded) {
-                return true;
-            }
-        }
-
-        // Check command length
-        if command.len() < self.shell_integration.min_command_length {
-            return true;
-        }
-
-        // Check if command starts with space and we're configured to exclude those
-        if !self.shell_integration.log_space_prefixed && command.starts_with(' ') {
-            return true;
-        }
-
-        false
-    }
 }
 
 #[cfg(test)]
@@ -445,8 +877,10 @@ mod tests {
         let temp_file = NamedTempFile::new().unwrap();
         let config_path = temp_file.path().to_path_buf();
 
-        let mut config = Config::default();
-        config.max_entries = 50000;
+        let mut config = Config {
+            max_entries: 50000,
+            ..Default::default()
+        };
         config.redaction.placeholder = "<HIDDEN>".to_string();
 
         // Save configuration
@@ -460,16 +894,47 @@ mod tests {
     }
 
     #[test]
-    fn test_should_exclude_command() {
-        let config = Config::default();
-
-        // Should exclude commands in the exclude list
-        assert!(config.should_exclude_command("ls -la"));
-        assert!(config.should_exclude_command("cd /tmp"));
+    fn test_resolve_secret_policy() {
+        let mut config = Config::default();
+        config
+            .redaction
+            .policies
+            .insert("pem_block".to_string(), SecretPolicy::Drop);
+        config
+            .redaction
+            .policies
+            .insert("password".to_string(), SecretPolicy::RedactNoToken);
+        config
+            .redaction
+            .policies
+            .insert("token".to_string(), SecretPolicy::Redact);
+
+        let decision = config.redaction.resolve_policy(["token", "echo"]);
+        assert_eq!(decision, PolicyDecision::default());
+        assert!(!decision.should_drop());
+        assert!(decision.store_tokens());
+
+        let decision = config.redaction.resolve_policy(["password", "password"]);
+        assert_eq!(decision.matched.len(), 1);
+        assert!(!decision.store_tokens());
+        assert!(!decision.should_warn());
+
+        let decision = config.redaction.resolve_policy(["password", "pem_block"]);
+        assert!(decision.should_drop());
+        assert_eq!(decision.rules_with(SecretPolicy::Drop), vec!["pem_block"]);
+        assert_eq!(
+            decision.actions(),
+            vec![SecretPolicy::RedactNoToken, SecretPolicy::Drop]
+        );
+    }
 
-        // Should not exclude other commands
-        assert!(!config.should_exclude_command("echo hello"));
-        assert!(!config.should_exclude_command("grep pattern file"));
+    #[test]
+    fn test_secret_policy_serialization() {
+        let json = r#"{"pem_block": "drop", "password": "redact_no_token", "api_key": "warn"}"#;
+        let policies: HashMap<String, SecretPolicy> = serde_json::from_str(json).unwrap();
+        assert_eq!(policies["pem_block"], SecretPolicy::Drop);
+        assert_eq!(policies["password"], SecretPolicy::RedactNoToken);
+        assert_eq!(policies["api_key"], SecretPolicy::Warn);
     }
 
     #[test]
@@ -487,12 +952,16 @@ mod tests {
 
     #[test]
     fn test_config_merge() {
-        let mut config1 = Config::default();
-        config1.max_entries = 1000;
-
-        let mut config2 = Config::default();
-        config2.max_entries = 2000;
-        config2.enable_redaction = false;
+        let mut config1 = Config {
+            max_entries: 1000,
+            ..Default::default()
+        };
+
+        let config2 = Config {
+            max_entries: 2000,
+            enable_redaction: false,
+            ..Default::default()
+        };
 
         config1.merge(&config2);
 
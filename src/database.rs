@@ -5,14 +5,163 @@
 //! - Session management
 //! - Token/password storage for retrieval
 //! - Migration from legacy .mhist files
-
-use crate::error::Result;
+//! - Whole-file encryption with SQLCipher (`sqlcipher` feature)
+//! - Secure deletion of commands and their tokens
+
+use crate::backend::ShredReport;
+use crate::config::SecretPolicy;
+use crate::dates::TimeSpan;
+use crate::error::{Error, Result};
+use crate::incognito::Incognito;
+use crate::search::{ExitCodeFilter, SearchQuery};
+use crate::timezone::{local_offset, parse_logged};
 use crate::types::{CommandId, HostId, SessionId};
 use chrono::{DateTime, Utc};
 use rusqlite::{params, Connection, OptionalExtension};
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
+/// Shredded values shorter than this are not looked for in the file, as
+/// they turn up in unrelated bytes
+const MIN_REMNANT_LEN: usize = 4;
+
+/// Header at the start of every unencrypted SQLite database
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+/// A command read from a history file for import: time, logged offset if
+/// known, directory and command
+pub type ImportedCommand = (DateTime<Utc>, Option<i32>, String, String);
+
+/// Whether the file at `path` is a database encrypted with SQLCipher
+///
+/// A missing or empty file is not; anything that lacks the plain SQLite
+/// header is assumed to be.
+pub fn is_encrypted(path: &Path) -> bool {
+    use std::io::Read;
+
+    let mut header = [0u8; 16];
+    match std::fs::File::open(path).and_then(|mut file| file.read_exact(&mut header)) {
+        Ok(()) => header != SQLITE_HEADER,
+        Err(_) => false,
+    }
+}
+
+fn require_sqlcipher(path: &Path) -> Result<()> {
+    if cfg!(feature = "sqlcipher") {
+        Ok(())
+    } else {
+        Err(Error::database_key(
+            path,
+            "encryption needs a mortimer built with the sqlcipher feature",
+        ))
+    }
+}
+
+/// Open a connection, unlocking it with `key` when one is given
+fn open_connection(path: &Path, key: Option<&str>) -> Result<Connection> {
+    let encrypted = is_encrypted(path);
+    if encrypted && key.is_none() {
+        return Err(Error::database_key(
+            path,
+            format!(
+                "the database is encrypted; set {} or database.key_file",
+                crate::config::DB_KEY_ENV
+            ),
+        ));
+    }
+
+    let conn = Connection::open(path)?;
+    if let Some(key) = key {
+        require_sqlcipher(path)?;
+        conn.pragma_update(None, "key", key)?;
+    }
+
+    // SQLCipher only checks the key on first access
+    if conn
+        .query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(()))
+        .is_err()
+    {
+        let reason = if encrypted {
+            "wrong key"
+        } else {
+            "the database is not encrypted; run `mortimer db encrypt` first"
+        };
+        return Err(Error::database_key(path, reason));
+    }
+
+    Ok(conn)
+}
+
+/// Encrypt an unencrypted database in place
+pub fn encrypt_database(path: &Path, key: &str) -> Result<()> {
+    require_sqlcipher(path)?;
+    if is_encrypted(path) {
+        return Err(Error::database_key(
+            path,
+            "the database is already encrypted",
+        ));
+    }
+    export_database(path, None, key)
+}
+
+/// Decrypt an encrypted database in place
+pub fn decrypt_database(path: &Path, key: &str) -> Result<()> {
+    require_sqlcipher(path)?;
+    if !is_encrypted(path) {
+        return Err(Error::database_key(path, "the database is not encrypted"));
+    }
+    export_database(path, Some(key), "")
+}
+
+/// Change the key of an encrypted database
+pub fn rekey_database(path: &Path, key: &str, new_key: &str) -> Result<()> {
+    let conn = open_connection(path, Some(key))?;
+    conn.pragma_update(None, "rekey", new_key)?;
+    Ok(())
+}
+
+/// Whether `table` has a column called `column`
+fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT 1 FROM pragma_table_info('{}') WHERE name = ?1",
+        table
+    ))?;
+    Ok(stmt.exists([column])?)
+}
+
+/// Copy a database into a new file with another key, then swap the files
+///
+/// An empty `new_key` writes an unencrypted database.
+fn export_database(path: &Path, key: Option<&str>, new_key: &str) -> Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| Error::database_key(path, "not a file"))?
+        .to_string_lossy();
+    let temp_path: PathBuf = path.with_file_name(format!(".{}.mortimer-tmp", name));
+    if temp_path.exists() {
+        std::fs::remove_file(&temp_path)?;
+    }
+
+    let conn = open_connection(path, key)?;
+    let exported = conn
+        .execute(
+            "ATTACH DATABASE ?1 AS converted KEY ?2",
+            params![temp_path.to_string_lossy(), new_key],
+        )
+        .and_then(|_| conn.query_row("SELECT sqlcipher_export('converted')", [], |_| Ok(())))
+        .and_then(|_| conn.execute("DETACH DATABASE converted", []));
+    drop(conn);
+    if let Err(e) = exported {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(e.into());
+    }
+
+    std::fs::set_permissions(&temp_path, std::fs::metadata(path)?.permissions())?;
+    std::fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
 /// Represents a host in the database
 #[derive(Debug, Clone)]
 pub struct Host {
@@ -40,6 +189,8 @@ pub struct CommandEntry {
     pub directory: String,
     pub redacted: bool,
     pub exit_code: Option<i32>,
+    /// Offset from UTC, in seconds, of the zone the command was logged in
+    pub utc_offset: Option<i32>,
 }
 
 /// Represents a redacted token that can be retrieved
@@ -53,6 +204,15 @@ pub struct Token {
     pub created_at: DateTime<Utc>,
 }
 
+/// A deleted command in the trash
+#[derive(Debug, Clone)]
+pub struct TrashedCommand {
+    pub entry: CommandEntry,
+    pub deleted_at: DateTime<Utc>,
+    /// Stored tokens kept with the command
+    pub tokens: usize,
+}
+
 /// Statistics about the database
 #[derive(Debug, Clone, Default)]
 pub struct DatabaseStats {
@@ -61,10 +221,73 @@ pub struct DatabaseStats {
     pub total_hosts: usize,
     pub redacted_commands: usize,
     pub stored_tokens: usize,
+    /// Number of commands affected by each non-default secret policy
+    pub policy_events: Vec<(String, usize)>,
     pub oldest_entry: Option<DateTime<Utc>>,
     pub newest_entry: Option<DateTime<Utc>>,
 }
 
+/// A redaction rule that fired on a logged command
+#[derive(Debug, Clone)]
+pub struct RedactionEvent {
+    pub command_id: CommandId,
+    pub rule_id: String,
+    /// Type of the token extracted by the rule, if one was
+    pub token_type: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// How a stored token was accessed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenAccessKind {
+    /// The value was printed, e.g. by `tokens --show-values`
+    Reveal,
+    /// The value was put back into a command by `expand`
+    Expand,
+    /// The value was written back into the stored command for good
+    Restore,
+    /// The value was written to an encrypted token bundle
+    Export,
+}
+
+impl TokenAccessKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenAccessKind::Reveal => "reveal",
+            TokenAccessKind::Expand => "expand",
+            TokenAccessKind::Restore => "restore",
+            TokenAccessKind::Export => "export",
+        }
+    }
+}
+
+/// An entry of the token access log
+#[derive(Debug, Clone)]
+pub struct TokenAccess {
+    pub accessed_at: DateTime<Utc>,
+    pub hostname: String,
+    pub session_id: Option<String>,
+    /// One of the [`TokenAccessKind`] names
+    pub action: String,
+    pub command_id: Option<CommandId>,
+    pub token_id: Option<i64>,
+    pub token_type: String,
+    pub placeholder: String,
+}
+
+/// Age summary of the stored tokens of one type
+#[derive(Debug, Clone)]
+pub struct TokenTypeAge {
+    pub token_type: String,
+    pub count: usize,
+    /// Tokens created before the staleness cutoff passed to the report
+    pub stale: usize,
+    pub oldest: DateTime<Utc>,
+    pub newest: DateTime<Utc>,
+    /// Tokens of commands in the trash, included in `count`
+    pub trashed: usize,
+}
+
 /// Main database manager
 pub struct Database {
     conn: Connection,
@@ -76,12 +299,20 @@ impl Database {
     /// Create a new database connection and initialize schema
     #[must_use = "Database connection must be used"]
     pub fn new(db_path: &Path) -> Result<Self> {
+        Self::open(db_path, None)
+    }
+
+    /// Open a database, encrypted with `key` if one is given
+    ///
+    /// A new database is created encrypted when a key is given.
+    #[must_use = "Database connection must be used"]
+    pub fn open(db_path: &Path, key: Option<&str>) -> Result<Self> {
         // Create parent directory if it doesn't exist
         if let Some(parent) = db_path.parent() {
             std::fs::create_dir_all(parent)?;
         }
 
-        let conn = Connection::open(db_path)?;
+        let conn = open_connection(db_path, key)?;
 
         // Enable foreign keys
         conn.execute("PRAGMA foreign_keys = ON", [])?;
@@ -132,6 +363,7 @@ impl Database {
                 directory TEXT NOT NULL,
                 redacted INTEGER NOT NULL DEFAULT 0,
                 exit_code INTEGER,
+                utc_offset INTEGER,
                 FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
             )",
             [],
@@ -151,6 +383,96 @@ impl Database {
             [],
         )?;
 
+        // Policy events table - records when a secret policy changed how a
+        // command was logged, without keeping the command itself
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS policy_events (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                action TEXT NOT NULL,
+                rules TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // Redaction events table - which rule redacted which command
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS redaction_events (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                command_id INTEGER NOT NULL,
+                rule_id TEXT NOT NULL,
+                token_type TEXT,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (command_id) REFERENCES commands(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        // Trash - deleted commands with their tokens and redaction events,
+        // under their original IDs so they can be put back
+        self.conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS trash_commands (
+                id INTEGER PRIMARY KEY,
+                session_id TEXT NOT NULL,
+                command TEXT NOT NULL,
+                timestamp TEXT NOT NULL,
+                directory TEXT NOT NULL,
+                redacted INTEGER NOT NULL DEFAULT 0,
+                exit_code INTEGER,
+                deleted_at TEXT NOT NULL,
+                utc_offset INTEGER
+             );
+             CREATE TABLE IF NOT EXISTS trash_tokens (
+                id INTEGER PRIMARY KEY,
+                command_id INTEGER NOT NULL,
+                token_type TEXT NOT NULL,
+                placeholder TEXT NOT NULL,
+                original_value TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (command_id) REFERENCES trash_commands(id) ON DELETE CASCADE
+             );
+             CREATE TABLE IF NOT EXISTS trash_redaction_events (
+                id INTEGER PRIMARY KEY,
+                command_id INTEGER NOT NULL,
+                rule_id TEXT NOT NULL,
+                token_type TEXT,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (command_id) REFERENCES trash_commands(id) ON DELETE CASCADE
+             );
+             CREATE INDEX IF NOT EXISTS idx_trash_tokens_command ON trash_tokens(command_id);
+             CREATE INDEX IF NOT EXISTS idx_trash_redaction_events_command
+                ON trash_redaction_events(command_id);",
+        )?;
+
+        // Token access log - who was shown which stored token, and how.
+        // Rows are only ever added, and outlive the tokens they describe.
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS token_access_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                accessed_at TEXT NOT NULL,
+                hostname TEXT NOT NULL,
+                session_id TEXT,
+                action TEXT NOT NULL,
+                command_id INTEGER,
+                token_id INTEGER,
+                token_type TEXT NOT NULL,
+                placeholder TEXT NOT NULL
+            )",
+            [],
+        )?;
+        self.conn.execute_batch(
+            "CREATE TRIGGER IF NOT EXISTS token_access_log_no_update
+                BEFORE UPDATE ON token_access_log
+                BEGIN SELECT RAISE(ABORT, 'token_access_log is append-only'); END;
+             CREATE TRIGGER IF NOT EXISTS token_access_log_no_delete
+                BEFORE DELETE ON token_access_log
+                BEGIN SELECT RAISE(ABORT, 'token_access_log is append-only'); END;",
+        )?;
+
+        // Columns added after the tables were first created
+        self.ensure_column("commands", "utc_offset", "INTEGER")?;
+        self.ensure_column("trash_commands", "utc_offset", "INTEGER")?;
+
         // Create indices for common queries
         self.conn.execute(
             "CREATE INDEX IF NOT EXISTS idx_commands_timestamp ON commands(timestamp DESC)",
@@ -177,6 +499,22 @@ impl Database {
             [],
         )?;
 
+        self.conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_redaction_events_command ON redaction_events(command_id)",
+            [],
+        )?;
+
+        Ok(())
+    }
+
+    /// Add a column that databases created by older versions lack
+    fn ensure_column(&self, table: &str, column: &str, definition: &str) -> Result<()> {
+        if !has_column(&self.conn, table, column)? {
+            self.conn.execute(
+                &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+                [],
+            )?;
+        }
         Ok(())
     }
 
@@ -241,15 +579,46 @@ impl Database {
     }
 
     /// Get or create a session for the current shell
+    ///
+    /// Shells with the integration loaded identify themselves in
+    /// [`SESSION_ENV`](crate::incognito::SESSION_ENV), and all their commands share one session. Otherwise
+    /// every process starts a session of its own.
     pub fn ensure_session(&mut self) -> Result<String> {
         if let Some(ref session_id) = self.current_session_id {
-            Ok(session_id.as_str().to_string())
-        } else {
-            self.start_session()
+            return Ok(session_id.as_str().to_string());
         }
+
+        let Some(session_id) = self.shell_session_id()? else {
+            return self.start_session();
+        };
+        self.conn.execute(
+            "INSERT OR IGNORE INTO sessions (id, host_id, started_at) VALUES (?1, ?2, ?3)",
+            params![
+                session_id,
+                self.current_host_id.as_i64(),
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+
+        self.current_session_id = Some(SessionId::new(session_id.clone()));
+        Ok(session_id)
+    }
+
+    /// Session id of the calling shell, qualified by hostname so merged
+    /// databases keep sessions apart
+    pub fn shell_session_id(&self) -> Result<Option<String>> {
+        let Some(shell_session) = Incognito::current_session() else {
+            return Ok(None);
+        };
+        let hostname: String = self.conn.query_row(
+            "SELECT hostname FROM hosts WHERE id = ?1",
+            [self.current_host_id.as_i64()],
+            |row| row.get(0),
+        )?;
+        Ok(Some(format!("{}:{}", hostname, shell_session)))
     }
 
-    /// Add a command to the database
+    /// Add a command to the database, logged in this machine's timezone
     pub fn add_command(
         &mut self,
         command: &str,
@@ -257,20 +626,38 @@ impl Database {
         timestamp: DateTime<Utc>,
         redacted: bool,
         exit_code: Option<i32>,
+    ) -> Result<i64> {
+        let utc_offset = Some(local_offset(timestamp));
+        self.add_command_with_offset(
+            command, directory, timestamp, redacted, exit_code, utc_offset,
+        )
+    }
+
+    /// Add a command logged at the given offset from UTC, if known
+    pub fn add_command_with_offset(
+        &mut self,
+        command: &str,
+        directory: &str,
+        timestamp: DateTime<Utc>,
+        redacted: bool,
+        exit_code: Option<i32>,
+        utc_offset: Option<i32>,
     ) -> Result<i64> {
         let session_id = self.ensure_session()?;
         let timestamp_str = timestamp.to_rfc3339();
 
         self.conn.execute(
-            "INSERT INTO commands (session_id, command, timestamp, directory, redacted, exit_code)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            "INSERT INTO commands
+                (session_id, command, timestamp, directory, redacted, exit_code, utc_offset)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
             params![
                 session_id,
                 command,
                 timestamp_str,
                 directory,
                 redacted as i32,
-                exit_code
+                exit_code,
+                utc_offset
             ],
         )?;
 
@@ -285,17 +672,370 @@ impl Database {
         placeholder: &str,
         original_value: &str,
     ) -> Result<i64> {
-        let now = Utc::now().to_rfc3339();
+        self.store_token_at(
+            command_id,
+            token_type,
+            placeholder,
+            original_value,
+            Utc::now(),
+        )
+    }
 
+    /// Store a redacted token, dated when the secret was used
+    pub fn store_token_at(
+        &self,
+        command_id: i64,
+        token_type: &str,
+        placeholder: &str,
+        original_value: &str,
+        created_at: DateTime<Utc>,
+    ) -> Result<i64> {
         self.conn.execute(
             "INSERT INTO tokens (command_id, token_type, placeholder, original_value, created_at)
              VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![command_id, token_type, placeholder, original_value, now],
+            params![
+                command_id,
+                token_type,
+                placeholder,
+                original_value,
+                created_at.to_rfc3339()
+            ],
         )?;
 
         Ok(self.conn.last_insert_rowid())
     }
 
+    /// Get every stored token together with the command it belongs to
+    pub fn get_tokens_with_commands(&self) -> Result<Vec<(CommandEntry, Token)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT c.id, c.session_id, c.command, c.timestamp, c.directory, c.redacted, c.exit_code,
+                    t.id, t.command_id, t.token_type, t.placeholder, t.original_value, t.created_at,
+                    c.utc_offset
+             FROM tokens t
+             JOIN commands c ON t.command_id = c.id
+             ORDER BY t.id",
+        )?;
+
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    CommandEntry {
+                        id: row.get(0)?,
+                        session_id: row.get(1)?,
+                        command: row.get(2)?,
+                        timestamp: row
+                            .get::<_, String>(3)?
+                            .parse()
+                            .unwrap_or_else(|_| Utc::now()),
+                        directory: row.get(4)?,
+                        redacted: row.get::<_, i32>(5)? != 0,
+                        exit_code: row.get(6)?,
+                        utc_offset: row.get(13)?,
+                    },
+                    Token {
+                        id: row.get(7)?,
+                        command_id: row.get(8)?,
+                        token_type: row.get(9)?,
+                        placeholder: row.get(10)?,
+                        original_value: row.get(11)?,
+                        created_at: row
+                            .get::<_, String>(12)?
+                            .parse()
+                            .unwrap_or_else(|_| Utc::now()),
+                    },
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(rows)
+    }
+
+    /// Find a command by its content rather than its row ID
+    ///
+    /// Row IDs differ between databases, so this is how tokens and commands
+    /// are matched up across machines.
+    pub fn find_command(
+        &self,
+        timestamp: DateTime<Utc>,
+        directory: &str,
+        command: &str,
+    ) -> Result<Option<CommandId>> {
+        let id = self
+            .conn
+            .query_row(
+                "SELECT id FROM commands
+                 WHERE timestamp = ?1 AND directory = ?2 AND command = ?3
+                 ORDER BY id LIMIT 1",
+                params![timestamp.to_rfc3339(), directory, command],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        Ok(id)
+    }
+
+    /// Check whether a command already has a token with this value
+    pub fn has_token(
+        &self,
+        command_id: CommandId,
+        placeholder: &str,
+        original_value: &str,
+    ) -> Result<bool> {
+        let count: usize = self.conn.query_row(
+            "SELECT COUNT(*) FROM tokens
+             WHERE command_id = ?1 AND placeholder = ?2 AND original_value = ?3",
+            params![command_id, placeholder, original_value],
+            |row| row.get(0),
+        )?;
+
+        Ok(count > 0)
+    }
+
+    /// Delete a command's tokens for one placeholder
+    pub fn delete_tokens_for_placeholder(
+        &self,
+        command_id: CommandId,
+        placeholder: &str,
+    ) -> Result<usize> {
+        let deleted = self.conn.execute(
+            "DELETE FROM tokens WHERE command_id = ?1 AND placeholder = ?2",
+            params![command_id, placeholder],
+        )?;
+
+        Ok(deleted)
+    }
+
+    /// Delete stored tokens, optionally only older ones or those of one type
+    ///
+    /// Tokens of commands in the trash are deleted too, so a restored command
+    /// cannot bring an expired value back. Commands keep their placeholders,
+    /// so they can no longer be expanded. Freed pages are zeroed.
+    pub fn purge_tokens(
+        &self,
+        created_before: Option<DateTime<Utc>>,
+        token_type: Option<&str>,
+    ) -> Result<usize> {
+        self.conn.pragma_update(None, "secure_delete", true)?;
+
+        let mut filter = String::from("WHERE 1=1");
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+        if let Some(before) = created_before {
+            filter.push_str(" AND created_at < ?");
+            params.push(Box::new(before.to_rfc3339()));
+        }
+
+        if let Some(token_type) = token_type {
+            filter.push_str(" AND token_type = ?");
+            params.push(Box::new(token_type.to_string()));
+        }
+
+        let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|b| b.as_ref()).collect();
+        let mut purged = 0;
+        for table in ["tokens", "trash_tokens"] {
+            purged += self.conn.execute(
+                &format!("DELETE FROM {} {}", table, filter),
+                param_refs.as_slice(),
+            )?;
+        }
+
+        Ok(purged)
+    }
+
+    /// Summarize stored tokens by type, including those of commands in the
+    /// trash, oldest first
+    pub fn get_token_age_report(&self, stale_before: DateTime<Utc>) -> Result<Vec<TokenTypeAge>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT token_type, COUNT(*),
+                    SUM(CASE WHEN created_at < ?1 THEN 1 ELSE 0 END),
+                    MIN(created_at), MAX(created_at), SUM(trashed)
+             FROM (SELECT token_type, created_at, 0 AS trashed FROM tokens
+                   UNION ALL
+                   SELECT token_type, created_at, 1 AS trashed FROM trash_tokens)
+             GROUP BY token_type
+             ORDER BY MIN(created_at)",
+        )?;
+
+        let report = stmt
+            .query_map(params![stale_before.to_rfc3339()], |row| {
+                Ok(TokenTypeAge {
+                    token_type: row.get(0)?,
+                    count: row.get(1)?,
+                    stale: row.get(2)?,
+                    oldest: row
+                        .get::<_, String>(3)?
+                        .parse()
+                        .unwrap_or_else(|_| Utc::now()),
+                    newest: row
+                        .get::<_, String>(4)?
+                        .parse()
+                        .unwrap_or_else(|_| Utc::now()),
+                    trashed: row.get(5)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(report)
+    }
+
+    /// Record that a secret policy changed how a command was logged
+    pub fn record_policy_event(&self, action: SecretPolicy, rules: &str) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+
+        self.conn.execute(
+            "INSERT INTO policy_events (action, rules, created_at) VALUES (?1, ?2, ?3)",
+            params![action.as_str(), rules, now],
+        )?;
+
+        Ok(())
+    }
+
+    /// Record that a redaction rule fired on a command
+    pub fn record_redaction_event(
+        &self,
+        command_id: i64,
+        rule_id: &str,
+        token_type: Option<&str>,
+        created_at: DateTime<Utc>,
+    ) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO redaction_events (command_id, rule_id, token_type, created_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![command_id, rule_id, token_type, created_at.to_rfc3339()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Get the redaction events recorded for a command
+    pub fn get_redaction_events_for_command(
+        &self,
+        command_id: CommandId,
+    ) -> Result<Vec<RedactionEvent>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT command_id, rule_id, token_type, created_at
+             FROM redaction_events WHERE command_id = ?1
+             ORDER BY id",
+        )?;
+
+        let events = stmt
+            .query_map(params![command_id], |row| {
+                Ok(RedactionEvent {
+                    command_id: row.get(0)?,
+                    rule_id: row.get(1)?,
+                    token_type: row.get(2)?,
+                    created_at: row
+                        .get::<_, String>(3)?
+                        .parse()
+                        .unwrap_or_else(|_| Utc::now()),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(events)
+    }
+
+    /// Record that stored tokens were accessed, from this host and session
+    pub fn record_token_access(&mut self, kind: TokenAccessKind, tokens: &[Token]) -> Result<()> {
+        if tokens.is_empty() {
+            return Ok(());
+        }
+
+        let session_id = self.ensure_session()?;
+        let hostname: String = self.conn.query_row(
+            "SELECT hostname FROM hosts WHERE id = ?1",
+            [self.current_host_id.as_i64()],
+            |row| row.get(0),
+        )?;
+        let now = Utc::now().to_rfc3339();
+
+        let tx = self.conn.transaction()?;
+        for token in tokens {
+            tx.execute(
+                "INSERT INTO token_access_log
+                    (accessed_at, hostname, session_id, action, command_id, token_id,
+                     token_type, placeholder)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    now,
+                    hostname,
+                    session_id,
+                    kind.as_str(),
+                    token.command_id,
+                    token.id,
+                    token.token_type,
+                    token.placeholder
+                ],
+            )?;
+        }
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    /// Get the token access log, oldest first
+    pub fn get_token_access_log(&self) -> Result<Vec<TokenAccess>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT accessed_at, hostname, session_id, action, command_id, token_id,
+                    token_type, placeholder
+             FROM token_access_log
+             ORDER BY id",
+        )?;
+
+        let entries = stmt
+            .query_map([], |row| {
+                Ok(TokenAccess {
+                    accessed_at: row
+                        .get::<_, String>(0)?
+                        .parse()
+                        .unwrap_or_else(|_| Utc::now()),
+                    hostname: row.get(1)?,
+                    session_id: row.get(2)?,
+                    action: row.get(3)?,
+                    command_id: row.get(4)?,
+                    token_id: row.get(5)?,
+                    token_type: row.get(6)?,
+                    placeholder: row.get(7)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(entries)
+    }
+
+    /// Get the rule id and time of every recorded redaction event
+    pub fn get_redaction_event_times(&self) -> Result<Vec<(String, DateTime<Utc>)>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT rule_id, created_at FROM redaction_events ORDER BY id")?;
+
+        let events = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get::<_, String>(1)?
+                        .parse()
+                        .unwrap_or_else(|_| Utc::now()),
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(events)
+    }
+
+    /// Count recorded policy events by action
+    pub fn get_policy_event_counts(&self) -> Result<Vec<(String, usize)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT action, COUNT(*) FROM policy_events GROUP BY action ORDER BY action",
+        )?;
+
+        let counts = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(counts)
+    }
+
     /// Get tokens for a specific command
     #[must_use = "Token query results should be used"]
     pub fn get_tokens_for_command(&self, command_id: CommandId) -> Result<Vec<Token>> {
@@ -381,6 +1121,36 @@ impl Database {
         Ok(tokens)
     }
 
+    /// Get tokens stored under a specific placeholder
+    ///
+    /// With correlatable placeholders this finds every use of one secret.
+    pub fn get_tokens_by_placeholder(&self, placeholder: &str) -> Result<Vec<Token>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, command_id, token_type, placeholder, original_value, created_at
+             FROM tokens
+             WHERE placeholder = ?1
+             ORDER BY created_at DESC",
+        )?;
+
+        let tokens = stmt
+            .query_map(params![placeholder], |row| {
+                Ok(Token {
+                    id: row.get(0)?,
+                    command_id: row.get(1)?,
+                    token_type: row.get(2)?,
+                    placeholder: row.get(3)?,
+                    original_value: row.get(4)?,
+                    created_at: row
+                        .get::<_, String>(5)?
+                        .parse()
+                        .unwrap_or_else(|_| Utc::now()),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(tokens)
+    }
+
     /// Search commands
     #[must_use = "Search results should be used"]
     pub fn search_commands(
@@ -391,7 +1161,8 @@ impl Database {
         limit: Option<usize>,
     ) -> Result<Vec<CommandEntry>> {
         let mut sql = String::from(
-            "SELECT c.id, c.session_id, c.command, c.timestamp, c.directory, c.redacted, c.exit_code
+            "SELECT c.id, c.session_id, c.command, c.timestamp, c.directory, c.redacted, c.exit_code,
+                    c.utc_offset
              FROM commands c
              JOIN sessions s ON c.session_id = s.id
              JOIN hosts h ON s.host_id = h.id
@@ -433,6 +1204,7 @@ impl Database {
                     directory: row.get(4)?,
                     redacted: row.get::<_, i32>(5)? != 0,
                     exit_code: row.get(6)?,
+                    utc_offset: row.get(7)?,
                 })
             })?
             .collect::<rusqlite::Result<Vec<_>>>()?;
@@ -440,11 +1212,103 @@ impl Database {
         Ok(commands)
     }
 
+    /// Commands that could match a search, oldest first, each with the host
+    /// it ran on
+    ///
+    /// Only the query's filters on fields other than the command text are
+    /// applied here, loosely enough never to leave out a match; the search
+    /// engine matches the text and checks every filter exactly. Rows are read
+    /// as `visit` takes them.
+    pub fn with_search_candidates<T>(
+        &self,
+        query: &SearchQuery,
+        visit: impl FnOnce(
+            &mut dyn Iterator<Item = Result<(CommandEntry, Option<String>)>>,
+        ) -> Result<T>,
+    ) -> Result<T> {
+        let mut sql = String::from(
+            "SELECT c.id, c.session_id, c.command, c.timestamp, c.directory, c.redacted, c.exit_code,
+                    c.utc_offset, h.hostname
+             FROM commands c
+             LEFT JOIN sessions s ON c.session_id = s.id
+             LEFT JOIN hosts h ON s.host_id = h.id
+             WHERE 1 = 1",
+        );
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+        // Stored times are RFC 3339 in UTC, so they sort as text; compare
+        // whole seconds to stay clear of fractional digits
+        if let Some((start, end)) = query.time_range {
+            sql.push_str(" AND c.timestamp >= ? AND c.timestamp < ?");
+            params.push(Box::new(start.format("%Y-%m-%dT%H:%M:%S").to_string()));
+            params.push(Box::new(
+                (end + chrono::Duration::seconds(1))
+                    .format("%Y-%m-%dT%H:%M:%S")
+                    .to_string(),
+            ));
+        }
+        if let Some(dir) = &query.directory {
+            sql.push_str(" AND instr(c.directory, ?) > 0");
+            params.push(Box::new(dir.clone()));
+        }
+        if let Some(host) = &query.host {
+            sql.push_str(" AND h.hostname = ? COLLATE NOCASE");
+            params.push(Box::new(host.clone()));
+        }
+        if let Some(session) = &query.session {
+            sql.push_str(" AND c.session_id = ?");
+            params.push(Box::new(session.clone()));
+        }
+        match query.exit_code {
+            Some(ExitCodeFilter::Is(code)) => {
+                sql.push_str(" AND c.exit_code = ?");
+                params.push(Box::new(code));
+            }
+            Some(ExitCodeFilter::IsNot(code)) => {
+                sql.push_str(" AND c.exit_code IS NOT NULL AND c.exit_code != ?");
+                params.push(Box::new(code));
+            }
+            None => {}
+        }
+        if query.redacted_only {
+            sql.push_str(" AND c.redacted != 0");
+        }
+        if query.exclude_redacted {
+            sql.push_str(" AND c.redacted = 0");
+        }
+        sql.push_str(" ORDER BY c.timestamp ASC");
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|b| b.as_ref()).collect();
+        let mut rows = stmt
+            .query_map(param_refs.as_slice(), |row| {
+                Ok((
+                    CommandEntry {
+                        id: row.get(0)?,
+                        session_id: row.get(1)?,
+                        command: row.get(2)?,
+                        timestamp: row
+                            .get::<_, String>(3)?
+                            .parse()
+                            .unwrap_or_else(|_| Utc::now()),
+                        directory: row.get(4)?,
+                        redacted: row.get::<_, i32>(5)? != 0,
+                        exit_code: row.get(6)?,
+                        utc_offset: row.get(7)?,
+                    },
+                    row.get(8)?,
+                ))
+            })?
+            .map(|row| row.map_err(Error::from));
+
+        visit(&mut rows)
+    }
+
     /// Get recent commands
     #[must_use = "Query results should be used"]
     pub fn get_recent_commands(&self, limit: usize) -> Result<Vec<CommandEntry>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, session_id, command, timestamp, directory, redacted, exit_code
+            "SELECT id, session_id, command, timestamp, directory, redacted, exit_code, utc_offset
              FROM commands
              ORDER BY timestamp DESC
              LIMIT ?1",
@@ -463,6 +1327,7 @@ impl Database {
                     directory: row.get(4)?,
                     redacted: row.get::<_, i32>(5)? != 0,
                     exit_code: row.get(6)?,
+                    utc_offset: row.get(7)?,
                 })
             })?
             .collect::<rusqlite::Result<Vec<_>>>()?;
@@ -470,11 +1335,76 @@ impl Database {
         Ok(commands)
     }
 
+    /// Most recent commands of a session, newest first
+    pub fn get_session_commands(
+        &self,
+        session_id: &str,
+        limit: usize,
+    ) -> Result<Vec<CommandEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, session_id, command, timestamp, directory, redacted, exit_code, utc_offset
+             FROM commands
+             WHERE session_id = ?1
+             ORDER BY timestamp DESC, id DESC
+             LIMIT ?2",
+        )?;
+
+        let commands = stmt
+            .query_map(params![session_id, limit as i64], |row| {
+                Ok(CommandEntry {
+                    id: row.get(0)?,
+                    session_id: row.get(1)?,
+                    command: row.get(2)?,
+                    timestamp: row
+                        .get::<_, String>(3)?
+                        .parse()
+                        .unwrap_or_else(|_| Utc::now()),
+                    directory: row.get(4)?,
+                    redacted: row.get::<_, i32>(5)? != 0,
+                    exit_code: row.get(6)?,
+                    utc_offset: row.get(7)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(commands)
+    }
+
+    /// Get a single command by ID
+    pub fn get_command(&self, id: CommandId) -> Result<Option<CommandEntry>> {
+        let command = self
+            .conn
+            .query_row(
+                "SELECT id, session_id, command, timestamp, directory, redacted, exit_code, utc_offset
+                 FROM commands
+                 WHERE id = ?1",
+                params![id],
+                |row| {
+                    Ok(CommandEntry {
+                        id: row.get(0)?,
+                        session_id: row.get(1)?,
+                        command: row.get(2)?,
+                        timestamp: row
+                            .get::<_, String>(3)?
+                            .parse()
+                            .unwrap_or_else(|_| Utc::now()),
+                        directory: row.get(4)?,
+                        redacted: row.get::<_, i32>(5)? != 0,
+                        exit_code: row.get(6)?,
+                        utc_offset: row.get(7)?,
+                    })
+                },
+            )
+            .optional()?;
+
+        Ok(command)
+    }
+
     /// Get all commands (for export/migration)
     #[must_use = "Query results should be used"]
     pub fn get_all_commands(&self) -> Result<Vec<CommandEntry>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, session_id, command, timestamp, directory, redacted, exit_code
+            "SELECT id, session_id, command, timestamp, directory, redacted, exit_code, utc_offset
              FROM commands
              ORDER BY timestamp ASC",
         )?;
@@ -492,6 +1422,7 @@ impl Database {
                     directory: row.get(4)?,
                     redacted: row.get::<_, i32>(5)? != 0,
                     exit_code: row.get(6)?,
+                    utc_offset: row.get(7)?,
                 })
             })?
             .collect::<rusqlite::Result<Vec<_>>>()?;
@@ -547,6 +1478,7 @@ impl Database {
             total_hosts,
             redacted_commands,
             stored_tokens,
+            policy_events: self.get_policy_event_counts()?,
             oldest_entry: oldest_entry.and_then(|s| s.parse().ok()),
             newest_entry: newest_entry.and_then(|s| s.parse().ok()),
         })
@@ -602,25 +1534,22 @@ impl Database {
         Ok(sessions)
     }
 
-    /// Import from legacy .mhist file format
+    /// Read the commands of a legacy .mhist file, for import
     /// Handles multiline commands properly
-    pub fn import_from_mhist(&mut self, mhist_path: &Path) -> Result<usize> {
+    pub fn read_mhist(mhist_path: &Path) -> Result<Vec<ImportedCommand>> {
         let content = std::fs::read_to_string(mhist_path)?;
-        let mut imported_count = 0;
-        let mut current_entry: Option<(DateTime<Utc>, String, String)> = None;
+        let mut commands = Vec::new();
+        let mut current_entry: Option<ImportedCommand> = None;
 
         for line in content.lines() {
             // Check if this is a new entry (starts with timestamp pattern)
             if let Some(entry_parts) = Self::parse_mhist_line(line) {
                 // Save previous entry if exists
-                if let Some((timestamp, directory, command)) = current_entry.take() {
-                    self.add_command(&command, &directory, timestamp, false, None)?;
-                    imported_count += 1;
-                }
+                commands.extend(current_entry.take());
 
                 // Start new entry
                 current_entry = Some(entry_parts);
-            } else if let Some((_timestamp, _directory, command)) = current_entry.as_mut() {
+            } else if let Some((_, _, _, command)) = current_entry.as_mut() {
                 // This is a continuation line (multiline command)
                 command.push('\n');
                 command.push_str(line.trim());
@@ -628,17 +1557,15 @@ impl Database {
         }
 
         // Don't forget the last entry
-        if let Some((timestamp, directory, command)) = current_entry {
-            self.add_command(&command, &directory, timestamp, false, None)?;
-            imported_count += 1;
-        }
+        commands.extend(current_entry);
 
-        Ok(imported_count)
+        Ok(commands)
     }
 
     /// Parse a single .mhist line
-    /// Format: "2025-10-27 19:39:35 | /Users/fm/tmp | command"
-    fn parse_mhist_line(line: &str) -> Option<(DateTime<Utc>, String, String)> {
+    /// Format: "2025-10-27 19:39:35 +02:00 | /Users/fm/tmp | command", where
+    /// older files leave out the offset
+    fn parse_mhist_line(line: &str) -> Option<ImportedCommand> {
         let parts: Vec<&str> = line.splitn(3, " | ").collect();
         if parts.len() != 3 {
             return None;
@@ -648,37 +1575,41 @@ impl Database {
         let directory = parts[1].trim().to_string();
         let command = parts[2].to_string();
 
-        // Parse timestamp
-        let timestamp = chrono::NaiveDateTime::parse_from_str(timestamp_str, "%Y-%m-%d %H:%M:%S")
-            .ok()?
-            .and_utc();
+        let (timestamp, utc_offset) = parse_logged(timestamp_str)?;
 
-        Some((timestamp, directory, command))
+        Some((timestamp, utc_offset, directory, command))
     }
 
-    /// Import from bash history
-    pub fn import_from_bash_history(&mut self, bash_history_path: &Path) -> Result<usize> {
+    /// Read the commands of a bash history file, for import
+    ///
+    /// Bash history has no times, so entries are stamped with the time of
+    /// import and `window` either takes all of them or none.
+    pub fn read_bash_history(
+        bash_history_path: &Path,
+        window: Option<TimeSpan>,
+    ) -> Result<Vec<ImportedCommand>> {
         let content = std::fs::read_to_string(bash_history_path)?;
-        let mut imported_count = 0;
         let now = Utc::now();
-
-        for line in content.lines() {
-            let line = line.trim();
-            if line.is_empty() || line.starts_with('#') {
-                continue;
-            }
-
-            self.add_command(line, "<imported>", now, false, None)?;
-            imported_count += 1;
+        if window.is_some_and(|window| !window.contains(now)) {
+            return Ok(Vec::new());
         }
 
-        Ok(imported_count)
+        Ok(content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| (now, None, "<imported>".to_string(), line.to_string()))
+            .collect())
     }
 
-    /// Import from zsh history
-    pub fn import_from_zsh_history(&mut self, zsh_history_path: &Path) -> Result<usize> {
+    /// Read the commands of a zsh history file, for import, only entries
+    /// within `window` if given
+    pub fn read_zsh_history(
+        zsh_history_path: &Path,
+        window: Option<TimeSpan>,
+    ) -> Result<Vec<ImportedCommand>> {
         let content = std::fs::read_to_string(zsh_history_path)?;
-        let mut imported_count = 0;
+        let mut commands = Vec::new();
 
         // Zsh format: ": 1609786800:0;command"
         let re = regex::Regex::new(r"^: (\d+):\d+;(.*)").unwrap();
@@ -688,32 +1619,51 @@ impl Database {
                 let timestamp_str = caps.get(1).unwrap().as_str();
                 let command = caps.get(2).unwrap().as_str();
 
-                if let Ok(timestamp_secs) = timestamp_str.parse::<i64>() {
-                    if let Some(datetime) = DateTime::from_timestamp(timestamp_secs, 0) {
-                        self.add_command(command, "<imported>", datetime, false, None)?;
-                        imported_count += 1;
-                    }
+                if let Ok(timestamp_secs) = timestamp_str.parse::<i64>()
+                    && let Some(datetime) = DateTime::from_timestamp(timestamp_secs, 0)
+                    && window.is_none_or(|window| window.contains(datetime))
+                {
+                    commands.push((
+                        datetime,
+                        None,
+                        "<imported>".to_string(),
+                        command.to_string(),
+                    ));
                 }
             }
         }
 
-        Ok(imported_count)
+        Ok(commands)
     }
 
     /// Merge another database into this one
-    pub fn merge_from_database(&mut self, other_db_path: &Path) -> Result<usize> {
-        let other_conn = Connection::open(other_db_path)?;
+    ///
+    /// `other_key` unlocks the other database if it is encrypted.
+    pub fn merge_from_database(
+        &mut self,
+        other_db_path: &Path,
+        other_key: Option<&str>,
+    ) -> Result<usize> {
+        let other_conn = open_connection(other_db_path, other_key)?;
         let mut imported_count = 0;
 
+        // Databases from older versions did not record offsets
+        let utc_offset = if has_column(&other_conn, "commands", "utc_offset")? {
+            "c.utc_offset"
+        } else {
+            "NULL"
+        };
+
         // Get all commands from the other database
-        let mut stmt = other_conn.prepare(
-            "SELECT c.command, c.timestamp, c.directory, c.redacted, c.exit_code,
+        let mut stmt = other_conn.prepare(&format!(
+            "SELECT c.command, c.timestamp, c.directory, c.redacted, c.exit_code, {},
                     s.started_at, h.hostname
              FROM commands c
              JOIN sessions s ON c.session_id = s.id
              JOIN hosts h ON s.host_id = h.id
              ORDER BY c.timestamp ASC",
-        )?;
+            utc_offset
+        ))?;
 
         let commands: Vec<_> = stmt
             .query_map([], |row| {
@@ -723,13 +1673,16 @@ impl Database {
                     row.get::<_, String>(2)?,
                     row.get::<_, i32>(3)? != 0,
                     row.get::<_, Option<i32>>(4)?,
+                    row.get::<_, Option<i32>>(5)?,
                 ))
             })?
             .collect::<rusqlite::Result<Vec<_>>>()?;
 
-        for (command, timestamp_str, directory, redacted, exit_code) in commands {
+        for (command, timestamp_str, directory, redacted, exit_code, utc_offset) in commands {
             if let Ok(timestamp) = timestamp_str.parse() {
-                self.add_command(&command, &directory, timestamp, redacted, exit_code)?;
+                self.add_command_with_offset(
+                    &command, &directory, timestamp, redacted, exit_code, utc_offset,
+                )?;
                 imported_count += 1;
             }
         }
@@ -739,23 +1692,308 @@ impl Database {
 
     /// Clear all data (for testing)
     pub fn clear(&self) -> Result<()> {
+        self.conn.execute("DELETE FROM trash_commands", [])?;
         self.conn.execute("DELETE FROM tokens", [])?;
+        self.conn.execute("DELETE FROM redaction_events", [])?;
+        self.conn.execute("DELETE FROM policy_events", [])?;
         self.conn.execute("DELETE FROM commands", [])?;
         self.conn.execute("DELETE FROM sessions", [])?;
         self.conn.execute("DELETE FROM hosts", [])?;
         Ok(())
     }
 
+    /// Replace the stored text of a command
+    pub fn update_command(&self, id: CommandId, command: &str, redacted: bool) -> Result<()> {
+        self.conn.execute(
+            "UPDATE commands SET command = ?1, redacted = ?2 WHERE id = ?3",
+            params![command, redacted, id],
+        )?;
+        Ok(())
+    }
+
+    /// Hostname of every session, by session id
+    pub fn session_hostnames(&self) -> Result<HashMap<String, String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT s.id, h.hostname FROM sessions s JOIN hosts h ON h.id = s.host_id")?;
+        let hostnames = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<HashMap<String, String>>>()?;
+        Ok(hostnames)
+    }
+
+    /// Ids of the commands that have stored tokens
+    pub fn command_ids_with_tokens(&self) -> Result<HashSet<CommandId>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT DISTINCT command_id FROM tokens")?;
+        let ids = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<HashSet<CommandId>>>()?;
+        Ok(ids)
+    }
+
     /// Delete a specific command by ID
     pub fn delete_command(&self, id: CommandId) -> Result<()> {
         self.conn.execute("DELETE FROM commands WHERE id = ?1", [id.0])?;
         Ok(())
     }
+
+    /// Move commands to the trash, with their tokens and redaction events
+    pub fn trash_commands(&mut self, ids: &[CommandId]) -> Result<usize> {
+        let now = Utc::now().to_rfc3339();
+        let tx = self.conn.transaction()?;
+        let mut moved = 0;
+        for id in ids {
+            let copied = tx.execute(
+                "INSERT INTO trash_commands
+                    (id, session_id, command, timestamp, directory, redacted, exit_code, utc_offset,
+                     deleted_at)
+                 SELECT id, session_id, command, timestamp, directory, redacted, exit_code,
+                        utc_offset, ?2
+                 FROM commands WHERE id = ?1",
+                params![id.0, now],
+            )?;
+            if copied == 0 {
+                continue;
+            }
+            tx.execute(
+                "INSERT INTO trash_tokens
+                    (id, command_id, token_type, placeholder, original_value, created_at)
+                 SELECT id, command_id, token_type, placeholder, original_value, created_at
+                 FROM tokens WHERE command_id = ?1",
+                [id.0],
+            )?;
+            tx.execute(
+                "INSERT INTO trash_redaction_events (id, command_id, rule_id, token_type, created_at)
+                 SELECT id, command_id, rule_id, token_type, created_at
+                 FROM redaction_events WHERE command_id = ?1",
+                [id.0],
+            )?;
+            // Tokens and redaction events go with the command
+            tx.execute("DELETE FROM commands WHERE id = ?1", [id.0])?;
+            moved += 1;
+        }
+        tx.commit()?;
+
+        Ok(moved)
+    }
+
+    /// Move every command to the trash
+    pub fn trash_all_commands(&mut self) -> Result<usize> {
+        let ids = {
+            let mut stmt = self.conn.prepare("SELECT id FROM commands")?;
+            stmt.query_map([], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<CommandId>>>()?
+        };
+        self.trash_commands(&ids)
+    }
+
+    /// Put a command from the trash back, with its tokens and redaction
+    /// events; false if it is not in the trash
+    pub fn restore_command(&mut self, id: CommandId) -> Result<bool> {
+        let host_id = self.current_host_id.as_i64();
+        let tx = self.conn.transaction()?;
+
+        // The session may have been cleared since
+        tx.execute(
+            "INSERT OR IGNORE INTO sessions (id, host_id, started_at)
+             SELECT session_id, ?2, timestamp FROM trash_commands WHERE id = ?1",
+            params![id.0, host_id],
+        )?;
+        let restored = tx.execute(
+            "INSERT INTO commands
+                (id, session_id, command, timestamp, directory, redacted, exit_code, utc_offset)
+             SELECT id, session_id, command, timestamp, directory, redacted, exit_code, utc_offset
+             FROM trash_commands WHERE id = ?1",
+            [id.0],
+        )?;
+        if restored == 0 {
+            return Ok(false);
+        }
+        tx.execute(
+            "INSERT INTO tokens (id, command_id, token_type, placeholder, original_value, created_at)
+             SELECT id, command_id, token_type, placeholder, original_value, created_at
+             FROM trash_tokens WHERE command_id = ?1",
+            [id.0],
+        )?;
+        tx.execute(
+            "INSERT INTO redaction_events (id, command_id, rule_id, token_type, created_at)
+             SELECT id, command_id, rule_id, token_type, created_at
+             FROM trash_redaction_events WHERE command_id = ?1",
+            [id.0],
+        )?;
+        tx.execute("DELETE FROM trash_commands WHERE id = ?1", [id.0])?;
+        tx.commit()?;
+
+        Ok(true)
+    }
+
+    /// Commands in the trash, most recently deleted first
+    pub fn get_trash(&self) -> Result<Vec<TrashedCommand>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT t.id, t.session_id, t.command, t.timestamp, t.directory, t.redacted,
+                    t.exit_code, t.deleted_at,
+                    (SELECT COUNT(*) FROM trash_tokens WHERE command_id = t.id), t.utc_offset
+             FROM trash_commands t
+             ORDER BY t.deleted_at DESC, t.id DESC",
+        )?;
+
+        let trash = stmt
+            .query_map([], |row| {
+                Ok(TrashedCommand {
+                    entry: CommandEntry {
+                        id: row.get(0)?,
+                        session_id: row.get(1)?,
+                        command: row.get(2)?,
+                        timestamp: row
+                            .get::<_, String>(3)?
+                            .parse()
+                            .unwrap_or_else(|_| Utc::now()),
+                        directory: row.get(4)?,
+                        redacted: row.get::<_, i32>(5)? != 0,
+                        exit_code: row.get(6)?,
+                        utc_offset: row.get(9)?,
+                    },
+                    deleted_at: row
+                        .get::<_, String>(7)?
+                        .parse()
+                        .unwrap_or_else(|_| Utc::now()),
+                    tokens: row.get(8)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(trash)
+    }
+
+    /// Delete commands from the trash for good, optionally only those
+    /// deleted before `cutoff`
+    ///
+    /// Freed pages are zeroed, as the trash holds token values.
+    pub fn empty_trash(&self, cutoff: Option<DateTime<Utc>>) -> Result<usize> {
+        self.conn.pragma_update(None, "secure_delete", true)?;
+        let removed = match cutoff {
+            Some(cutoff) => self.conn.execute(
+                "DELETE FROM trash_commands WHERE deleted_at < ?1",
+                [cutoff.to_rfc3339()],
+            )?,
+            None => self.conn.execute("DELETE FROM trash_commands", [])?,
+        };
+        Ok(removed)
+    }
+
+    /// Delete commands and their tokens so they cannot be recovered
+    ///
+    /// A plain `DELETE` leaves the rows in free pages. Here freed pages are
+    /// zeroed, the WAL is checkpointed and the file is rebuilt, and then the
+    /// file is searched for the deleted text and token values. Values that
+    /// other commands still store are not counted as remnants.
+    pub fn shred_commands(&mut self, ids: &[CommandId]) -> Result<ShredReport> {
+        self.conn.pragma_update(None, "secure_delete", true)?;
+
+        let mut report = ShredReport::default();
+        let mut values: Vec<String> = Vec::new();
+
+        let tx = self.conn.transaction()?;
+        for id in ids {
+            // The command may be in the history or in the trash
+            for (commands, tokens, events) in [
+                ("commands", "tokens", "redaction_events"),
+                ("trash_commands", "trash_tokens", "trash_redaction_events"),
+            ] {
+                let command: Option<String> = tx
+                    .query_row(
+                        &format!("SELECT command FROM {} WHERE id = ?1", commands),
+                        [id.0],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+                let Some(command) = command else {
+                    continue;
+                };
+                values.push(command);
+
+                let mut stmt = tx.prepare(&format!(
+                    "SELECT original_value FROM {} WHERE command_id = ?1",
+                    tokens
+                ))?;
+                let token_values = stmt
+                    .query_map([id.0], |row| row.get::<_, String>(0))?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                drop(stmt);
+                values.extend(token_values);
+
+                report.tokens += tx.execute(
+                    &format!("DELETE FROM {} WHERE command_id = ?1", tokens),
+                    [id.0],
+                )?;
+                tx.execute(
+                    &format!("DELETE FROM {} WHERE command_id = ?1", events),
+                    [id.0],
+                )?;
+                report.commands +=
+                    tx.execute(&format!("DELETE FROM {} WHERE id = ?1", commands), [id.0])?;
+            }
+        }
+        tx.commit()?;
+
+        self.conn
+            .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+        self.conn.execute("VACUUM", [])?;
+
+        values.sort();
+        values.dedup();
+        values.retain(|value| value.len() >= MIN_REMNANT_LEN);
+        let mut remaining = Vec::with_capacity(values.len());
+        for value in values {
+            let stored: bool = self.conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM commands WHERE command = ?1)
+                     OR EXISTS(SELECT 1 FROM tokens WHERE original_value = ?1)
+                     OR EXISTS(SELECT 1 FROM trash_commands WHERE command = ?1)
+                     OR EXISTS(SELECT 1 FROM trash_tokens WHERE original_value = ?1)",
+                [&value],
+                |row| row.get(0),
+            )?;
+            if !stored {
+                remaining.push(value);
+            }
+        }
+        report.remnants = self.count_remnants(&remaining)?;
+
+        Ok(report)
+    }
+
+    /// Count the values that can still be found in the database files
+    fn count_remnants(&self, values: &[String]) -> Result<usize> {
+        let Some(path) = self.conn.path().filter(|p| !p.is_empty()) else {
+            return Ok(0);
+        };
+
+        let mut contents = Vec::new();
+        for suffix in ["", "-wal", "-journal"] {
+            match std::fs::read(format!("{}{}", path, suffix)) {
+                Ok(bytes) => contents.push(bytes),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Ok(values
+            .iter()
+            .filter(|value| {
+                contents
+                    .iter()
+                    .any(|bytes| bytes.windows(value.len()).any(|w| w == value.as_bytes()))
+            })
+            .count())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::TimeZone;
     use tempfile::NamedTempFile;
 
     #[test]
@@ -797,14 +2035,171 @@ mod tests {
         assert_eq!(tokens[0].original_value, "password123");
     }
 
+    #[test]
+    fn test_token_access_log_is_append_only() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let mut db = Database::new(temp_file.path()).unwrap();
+
+        let cmd_id = db
+            .add_command("echo password123", "/home", Utc::now(), true, None)
+            .unwrap();
+        db.store_token(cmd_id, "password", "<redacted>", "password123")
+            .unwrap();
+        let tokens = db.get_tokens_for_command(CommandId::new(cmd_id)).unwrap();
+        db.record_token_access(TokenAccessKind::Reveal, &tokens)
+            .unwrap();
+
+        assert!(
+            db.conn
+                .execute("UPDATE token_access_log SET action = 'none'", [])
+                .is_err()
+        );
+        assert!(db.conn.execute("DELETE FROM token_access_log", []).is_err());
+        assert_eq!(db.get_token_access_log().unwrap()[0].action, "reveal");
+    }
+
+    #[test]
+    fn test_trash_and_restore() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let mut db = Database::new(temp_file.path()).unwrap();
+
+        let cmd_id = db
+            .add_command("echo <redacted>", "/home", Utc::now(), true, None)
+            .unwrap();
+        db.store_token(cmd_id, "password", "<redacted>", "password123")
+            .unwrap();
+        db.record_redaction_event(cmd_id, "password", Some("password"), Utc::now())
+            .unwrap();
+        let id = CommandId::new(cmd_id);
+
+        assert_eq!(db.trash_commands(&[id]).unwrap(), 1);
+        assert!(db.get_command(id).unwrap().is_none());
+        assert!(db.get_tokens_for_command(id).unwrap().is_empty());
+        let trash = db.get_trash().unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].tokens, 1);
+
+        assert!(db.restore_command(id).unwrap());
+        assert!(!db.restore_command(id).unwrap());
+        assert_eq!(
+            db.get_command(id).unwrap().unwrap().command,
+            "echo <redacted>"
+        );
+        assert_eq!(
+            db.get_tokens_for_command(id).unwrap()[0].original_value,
+            "password123"
+        );
+        assert_eq!(db.get_redaction_events_for_command(id).unwrap().len(), 1);
+        assert!(db.get_trash().unwrap().is_empty());
+
+        db.trash_commands(&[id]).unwrap();
+        assert_eq!(
+            db.empty_trash(Some(Utc::now() - chrono::Duration::days(1)))
+                .unwrap(),
+            0
+        );
+        assert_eq!(db.empty_trash(None).unwrap(), 1);
+        assert!(!db.restore_command(id).unwrap());
+    }
+
+    #[test]
+    fn test_utc_offset_is_recorded_and_migrated() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let time = Utc.with_ymd_and_hms(2025, 7, 1, 12, 0, 0).unwrap();
+        {
+            let mut db = Database::new(temp_file.path()).unwrap();
+            db.add_command("ls", "/tmp", time, false, None).unwrap();
+            let recent = db.get_recent_commands(1).unwrap();
+            assert_eq!(recent[0].utc_offset, Some(local_offset(time)));
+
+            // Databases from before offsets were recorded lack the column
+            db.conn
+                .execute("ALTER TABLE commands DROP COLUMN utc_offset", [])
+                .unwrap();
+        }
+
+        let db = Database::new(temp_file.path()).unwrap();
+        let recent = db.get_recent_commands(1).unwrap();
+        assert_eq!(recent[0].command, "ls");
+        assert_eq!(recent[0].utc_offset, None);
+    }
+
     #[test]
     fn test_mhist_parsing() {
         let line = "2025-10-27 19:39:35 | /Users/fm/tmp | ls -la";
         let result = Database::parse_mhist_line(line);
         assert!(result.is_some());
 
-        let (_, directory, command) = result.unwrap();
+        let (_, utc_offset, directory, command) = result.unwrap();
+        assert_eq!(utc_offset, None);
         assert_eq!(directory, "/Users/fm/tmp");
         assert_eq!(command, "ls -la");
+
+        let line = "2025-10-27 19:39:35 -04:00 | /Users/fm/tmp | ls -la";
+        let (timestamp, utc_offset, _, _) = Database::parse_mhist_line(line).unwrap();
+        assert_eq!(utc_offset, Some(-4 * 3600));
+        assert_eq!(
+            timestamp,
+            Utc.with_ymd_and_hms(2025, 10, 27, 23, 39, 35).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_plain_database_is_not_opened_with_a_key() {
+        let temp_file = NamedTempFile::new().unwrap();
+        assert!(!is_encrypted(temp_file.path()));
+
+        Database::new(temp_file.path()).unwrap();
+        assert!(!is_encrypted(temp_file.path()));
+
+        // Either the build has no SQLCipher or the file is not encrypted
+        assert!(matches!(
+            Database::open(temp_file.path(), Some("key")),
+            Err(Error::DatabaseKey { .. })
+        ));
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[test]
+    fn test_encrypt_rekey_decrypt() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("history.db");
+        let mut db = Database::new(&path).unwrap();
+        db.add_command("deploy --host db01", "/srv", Utc::now(), false, None)
+            .unwrap();
+        drop(db);
+
+        encrypt_database(&path, "first").unwrap();
+        assert!(is_encrypted(&path));
+        assert!(
+            !std::fs::read(&path)
+                .unwrap()
+                .windows(4)
+                .any(|w| w == b"db01")
+        );
+        assert!(Database::new(&path).is_err());
+        assert!(Database::open(&path, Some("wrong")).is_err());
+        let db = Database::open(&path, Some("first")).unwrap();
+        assert_eq!(db.get_stats().unwrap().total_commands, 1);
+        drop(db);
+
+        rekey_database(&path, "first", "second").unwrap();
+        assert!(Database::open(&path, Some("first")).is_err());
+
+        // Merging reads an encrypted source with its own key
+        let mut other = Database::new(&temp_dir.path().join("other.db")).unwrap();
+        assert!(other.merge_from_database(&path, None).is_err());
+        assert_eq!(other.merge_from_database(&path, Some("second")).unwrap(), 1);
+
+        decrypt_database(&path, "second").unwrap();
+        assert!(!is_encrypted(&path));
+        assert_eq!(
+            Database::new(&path)
+                .unwrap()
+                .get_stats()
+                .unwrap()
+                .total_commands,
+            1
+        );
     }
 }
@@ -0,0 +1,436 @@
+//! Dates for time filters
+//!
+//! One parser behind every time filter: the `--since`, `--before` and
+//! `--date` flags of `search`, `export`, `import`, `stats` and `clear`, and
+//! the `after:`, `before:` and `date:` query fields. A date names a span of
+//! time, resolved in the display timezone (see [`crate::timezone`]), which
+//! is the system timezone unless configured otherwise:
+//!
+//! - `now`, `3h ago`, `"2 days ago"`, `90m` — an instant
+//! - `today`, `yesterday`, `monday`, `last monday` — a day
+//! - `this week`, `last month`, `this year` — a calendar week, month or year
+//! - `2025-10-01` — a day, `2025-10` — a month, `2025` — a year,
+//!   `2025-W40` — an ISO week, `2025-W40-3` — a day of an ISO week
+//! - `2025-10-01T14:00`, `2025-10-01 14:00:30` — a minute or a second
+//! - `2025-10-01T14:00:00+02:00` — an instant in the given offset
+//! - `2025-10-01..2025-10-07` — from the start of the first date to the end
+//!   of the second, both days included; either side may be left open
+//!
+//! A weekday on its own is the latest such day, today included; `last`
+//! skips today. Filters starting at a date (`--since`, `after:`) start at
+//! the beginning of its span, and filters ending at one (`--before`,
+//! `before:`) stop at the beginning of its span.
+
+use crate::error::{Error, Result};
+use crate::timezone::DisplayTimezone;
+use chrono::{
+    DateTime, Datelike, Duration, Months, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday,
+};
+
+/// A span of time, from `start` up to but not including `end`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeSpan {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl TimeSpan {
+    /// All of time
+    pub const ALL: TimeSpan = TimeSpan {
+        start: DateTime::<Utc>::MIN_UTC,
+        end: DateTime::<Utc>::MAX_UTC,
+    };
+
+    /// Whether `time` falls within the span
+    pub fn contains(&self, time: DateTime<Utc>) -> bool {
+        self.start <= time && time < self.end
+    }
+
+    /// The part of the span that is also within `other`
+    pub fn intersect(self, other: TimeSpan) -> TimeSpan {
+        TimeSpan {
+            start: self.start.max(other.start),
+            end: self.end.min(other.end),
+        }
+    }
+
+    fn instant(time: DateTime<Utc>) -> Self {
+        Self {
+            start: time,
+            end: time,
+        }
+    }
+}
+
+/// Parse `input` as a date or a range of dates, resolved in the timezone of
+/// `now` and relative to it
+pub fn parse_span<Tz: TimeZone>(input: &str, now: &DateTime<Tz>) -> Result<TimeSpan> {
+    let Some((from, to)) = input.split_once("..") else {
+        return parse_date(input, now);
+    };
+
+    let start = match from.trim() {
+        "" => TimeSpan::ALL.start,
+        from => parse_date(from, now)?.start,
+    };
+    let end = match to.trim() {
+        "" => TimeSpan::ALL.end,
+        to => parse_date(to, now)?.end,
+    };
+    if start >= end {
+        return Err(Error::InvalidTimestamp {
+            timestamp: format!("'{}' ends before it starts", input.trim()),
+        });
+    }
+    Ok(TimeSpan { start, end })
+}
+
+/// The span between the `since` and `before` dates and within the `during`
+/// range, read in `timezone`, or `None` when none is given
+pub fn window(
+    since: Option<&str>,
+    before: Option<&str>,
+    during: Option<&str>,
+    timezone: &DisplayTimezone,
+) -> Result<Option<TimeSpan>> {
+    if since.is_none() && before.is_none() && during.is_none() {
+        return Ok(None);
+    }
+
+    let now = Utc::now();
+    let mut window = match during {
+        Some(during) => timezone.parse_span(during, now)?,
+        None => TimeSpan::ALL,
+    };
+    if let Some(since) = since {
+        window.start = window.start.max(timezone.parse_span(since, now)?.start);
+    }
+    if let Some(before) = before {
+        window.end = window.end.min(timezone.parse_span(before, now)?.start);
+    }
+    if window.start > window.end {
+        return Err(Error::invalid_arguments(
+            "the time filters leave no time to match",
+        ));
+    }
+    Ok(Some(window))
+}
+
+/// Narrow `window` to `other` as well, where `None` is all of time
+pub fn narrow(window: Option<TimeSpan>, other: Option<TimeSpan>) -> Result<Option<TimeSpan>> {
+    let (window, other) = match (window, other) {
+        (Some(window), Some(other)) => (window, other),
+        (window, other) => return Ok(window.or(other)),
+    };
+    let narrowed = window.intersect(other);
+    if narrowed.start > narrowed.end {
+        return Err(Error::invalid_arguments(
+            "the time filters leave no time to match",
+        ));
+    }
+    Ok(Some(narrowed))
+}
+
+fn parse_date<Tz: TimeZone>(input: &str, now: &DateTime<Tz>) -> Result<TimeSpan> {
+    let input = input.trim();
+    let text = input
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_ascii_lowercase();
+    let tz = now.timezone();
+    let today = now.date_naive();
+
+    let span = match text.as_str() {
+        "now" => Some(TimeSpan::instant(now.with_timezone(&Utc))),
+        "today" => days(&tz, today, 1),
+        "yesterday" => days(&tz, today - Duration::days(1), 1),
+        "tomorrow" => days(&tz, today + Duration::days(1), 1),
+        "this week" => days(&tz, week_start(today), 7),
+        "last week" => days(&tz, week_start(today) - Duration::weeks(1), 7),
+        "this month" => month(&tz, today.with_day(1), 1),
+        "last month" => month(
+            &tz,
+            today
+                .with_day(1)
+                .and_then(|first| first.checked_sub_months(Months::new(1))),
+            1,
+        ),
+        "this year" => month(&tz, NaiveDate::from_ymd_opt(today.year(), 1, 1), 12),
+        "last year" => month(&tz, NaiveDate::from_ymd_opt(today.year() - 1, 1, 1), 12),
+        _ => None,
+    };
+    let span = span
+        .or_else(|| weekday(&tz, today, &text))
+        .or_else(|| calendar(&tz, input))
+        .or_else(|| relative(now, &text));
+
+    span.ok_or_else(|| Error::InvalidTimestamp {
+        timestamp: format!(
+            "'{}'; use e.g. 2025-10-01, 2025-10-01T14:00, 2025-W40, yesterday, \"last monday\", \"3h ago\" or 2025-10-01..2025-10-07",
+            input
+        ),
+    })
+}
+
+/// `monday` or `last monday`
+fn weekday<Tz: TimeZone>(tz: &Tz, today: NaiveDate, text: &str) -> Option<TimeSpan> {
+    let (name, skip_today) = match text.strip_prefix("last ") {
+        Some(name) => (name, true),
+        None => (text, false),
+    };
+    let day = name.parse::<Weekday>().ok()?;
+    let mut back = (7 + today.weekday().num_days_from_monday() - day.num_days_from_monday()) % 7;
+    if back == 0 && skip_today {
+        back = 7;
+    }
+    days(tz, today - Duration::days(i64::from(back)), 1)
+}
+
+/// Calendar dates and times: days, months, ISO weeks, minutes and seconds
+fn calendar<Tz: TimeZone>(tz: &Tz, input: &str) -> Option<TimeSpan> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(input) {
+        return Some(TimeSpan::instant(time.with_timezone(&Utc)));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        return days(tz, date, 1);
+    }
+    if let Some((year, week)) = input.split_once("-W").or_else(|| input.split_once("-w")) {
+        let year = year.parse().ok()?;
+        let (week, day) = match week.split_once('-') {
+            Some((week, day)) => (week, Some(day)),
+            None => (week, None),
+        };
+        let week = week.parse().ok()?;
+        return match day {
+            Some(day) => {
+                let day = Weekday::try_from(day.parse::<u8>().ok()?.checked_sub(1)?).ok()?;
+                days(tz, NaiveDate::from_isoywd_opt(year, week, day)?, 1)
+            }
+            None => days(tz, NaiveDate::from_isoywd_opt(year, week, Weekday::Mon)?, 7),
+        };
+    }
+    for (format, length) in [
+        ("%Y-%m-%dT%H:%M:%S", Duration::seconds(1)),
+        ("%Y-%m-%d %H:%M:%S", Duration::seconds(1)),
+        ("%Y-%m-%dT%H:%M", Duration::minutes(1)),
+        ("%Y-%m-%d %H:%M", Duration::minutes(1)),
+    ] {
+        if let Ok(time) = NaiveDateTime::parse_from_str(input, format) {
+            return Some(TimeSpan {
+                start: local(tz, time)?,
+                end: local(tz, time + length)?,
+            });
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(&format!("{}-01", input), "%Y-%m-%d") {
+        return month(tz, Some(date), 1);
+    }
+    if input.len() == 4 && input.bytes().all(|b| b.is_ascii_digit()) {
+        return month(tz, NaiveDate::from_ymd_opt(input.parse().ok()?, 1, 1), 12);
+    }
+    None
+}
+
+/// `3h ago`, `2 days ago` or just `90m`
+fn relative<Tz: TimeZone>(now: &DateTime<Tz>, text: &str) -> Option<TimeSpan> {
+    let text = text.strip_suffix("ago").unwrap_or(text).trim_end();
+    let split = text.find(|c: char| !c.is_ascii_digit())?;
+    let (count, unit) = text.split_at(split);
+    let count: u32 = count.parse().ok()?;
+
+    let unit = unit.trim();
+    let unit = unit
+        .strip_suffix('s')
+        .filter(|unit| unit.len() > 1)
+        .unwrap_or(unit);
+    let length = match unit {
+        "s" | "sec" | "second" => Duration::seconds(1),
+        "m" | "min" | "minute" => Duration::minutes(1),
+        "h" | "hr" | "hour" => Duration::hours(1),
+        "d" | "day" => Duration::days(1),
+        "w" | "week" => Duration::weeks(1),
+        "mo" | "month" => {
+            return Some(TimeSpan::instant(
+                now.clone()
+                    .checked_sub_months(Months::new(count))?
+                    .with_timezone(&Utc),
+            ));
+        }
+        "y" | "year" => {
+            return Some(TimeSpan::instant(
+                now.clone()
+                    .checked_sub_months(Months::new(count.checked_mul(12)?))?
+                    .with_timezone(&Utc),
+            ));
+        }
+        _ => return None,
+    };
+    let time = now
+        .clone()
+        .checked_sub_signed(length.checked_mul(count as i32)?)?;
+    Some(TimeSpan::instant(time.with_timezone(&Utc)))
+}
+
+fn week_start(date: NaiveDate) -> NaiveDate {
+    date - Duration::days(i64::from(date.weekday().num_days_from_monday()))
+}
+
+/// `count` whole days from the start of `first`
+fn days<Tz: TimeZone>(tz: &Tz, first: NaiveDate, count: i64) -> Option<TimeSpan> {
+    Some(TimeSpan {
+        start: local(tz, first.and_hms_opt(0, 0, 0)?)?,
+        end: local(tz, (first + Duration::days(count)).and_hms_opt(0, 0, 0)?)?,
+    })
+}
+
+/// `count` whole months from the start of `first`
+fn month<Tz: TimeZone>(tz: &Tz, first: Option<NaiveDate>, count: u32) -> Option<TimeSpan> {
+    let first = first?;
+    let next = first.checked_add_months(Months::new(count))?;
+    Some(TimeSpan {
+        start: local(tz, first.and_hms_opt(0, 0, 0)?)?,
+        end: local(tz, next.and_hms_opt(0, 0, 0)?)?,
+    })
+}
+
+/// The instant a local wall-clock time names; a time skipped by a DST change
+/// is read as the hour after it
+fn local<Tz: TimeZone>(tz: &Tz, time: NaiveDateTime) -> Option<DateTime<Utc>> {
+    tz.from_local_datetime(&time)
+        .earliest()
+        .or_else(|| {
+            tz.from_local_datetime(&(time + Duration::hours(1)))
+                .earliest()
+        })
+        .map(|time| time.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    /// Friday 2025-10-10 12:00 at UTC+2
+    fn now() -> DateTime<FixedOffset> {
+        FixedOffset::east_opt(2 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2025, 10, 10, 12, 0, 0)
+            .unwrap()
+    }
+
+    fn span(input: &str) -> (String, String) {
+        let span = parse_span(input, &now()).unwrap();
+        let offset = now().timezone();
+        (
+            span.start.with_timezone(&offset).to_rfc3339(),
+            span.end.with_timezone(&offset).to_rfc3339(),
+        )
+    }
+
+    fn day(date: &str) -> (String, String) {
+        let start = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+        let time = |date: NaiveDate| format!("{}T00:00:00+02:00", date.format("%Y-%m-%d"));
+        (time(start), time(start + Duration::days(1)))
+    }
+
+    #[test]
+    fn test_named_days_resolve_in_local_time() {
+        assert_eq!(span("today"), day("2025-10-10"));
+        assert_eq!(span("Yesterday"), day("2025-10-09"));
+        assert_eq!(span("friday"), day("2025-10-10"));
+        assert_eq!(span("last friday"), day("2025-10-03"));
+        assert_eq!(span("last  Monday"), day("2025-10-06"));
+        assert_eq!(span("sun"), day("2025-10-05"));
+        assert_eq!(span("2025-10-01"), day("2025-10-01"));
+        assert_eq!(span("2025-W40-3"), day("2025-10-01"));
+
+        assert_eq!(
+            span("this week"),
+            (day("2025-10-06").0, day("2025-10-12").1)
+        );
+        assert_eq!(span("2025-W40"), (day("2025-09-29").0, day("2025-10-05").1));
+        assert_eq!(
+            span("last month"),
+            (day("2025-09-01").0, day("2025-09-30").1)
+        );
+        assert_eq!(span("2024-02"), (day("2024-02-01").0, day("2024-02-29").1));
+        assert_eq!(span("2024"), (day("2024-01-01").0, day("2024-12-31").1));
+    }
+
+    #[test]
+    fn test_times_and_relative_dates() {
+        assert_eq!(
+            span("2025-10-01T14:00"),
+            (
+                "2025-10-01T14:00:00+02:00".to_string(),
+                "2025-10-01T14:01:00+02:00".to_string()
+            )
+        );
+        assert_eq!(span("2025-10-01 14:00:30").0, "2025-10-01T14:00:30+02:00");
+        assert_eq!(span("2025-10-01T14:00:00Z").0, "2025-10-01T16:00:00+02:00");
+
+        for (input, expected) in [
+            ("3h ago", "2025-10-10T09:00:00+02:00"),
+            ("3 hours ago", "2025-10-10T09:00:00+02:00"),
+            ("90m", "2025-10-10T10:30:00+02:00"),
+            ("2 days ago", "2025-10-08T12:00:00+02:00"),
+            ("1 month ago", "2025-09-10T12:00:00+02:00"),
+            ("now", "2025-10-10T12:00:00+02:00"),
+        ] {
+            assert_eq!(span(input), (expected.to_string(), expected.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_narrow() {
+        let at = |hour| now().with_timezone(&Utc) + Duration::hours(hour);
+        let span = |start, end| TimeSpan {
+            start: at(start),
+            end: at(end),
+        };
+
+        assert_eq!(narrow(None, None).unwrap(), None);
+        assert_eq!(narrow(Some(span(0, 2)), None).unwrap(), Some(span(0, 2)));
+        assert_eq!(narrow(None, Some(span(0, 2))).unwrap(), Some(span(0, 2)));
+        assert_eq!(
+            narrow(Some(span(0, 4)), Some(span(2, 6))).unwrap(),
+            Some(span(2, 4))
+        );
+        assert!(narrow(Some(span(0, 1)), Some(span(2, 3))).is_err());
+    }
+
+    #[test]
+    fn test_ranges() {
+        assert_eq!(
+            span("2025-10-01..2025-10-07"),
+            (day("2025-10-01").0, day("2025-10-07").1)
+        );
+        assert_eq!(
+            span("last monday..yesterday"),
+            (day("2025-10-06").0, day("2025-10-09").1)
+        );
+
+        let open = parse_span("2025-10-01..", &now()).unwrap();
+        assert_eq!(open.end, TimeSpan::ALL.end);
+        assert!(open.contains(now().with_timezone(&Utc)));
+        assert!(!open.contains(open.start - Duration::seconds(1)));
+
+        for input in [
+            "someday",
+            "2025-13-01",
+            "2025-W54",
+            "3 parsecs ago",
+            "today..yesterday",
+        ] {
+            assert!(
+                matches!(
+                    parse_span(input, &now()),
+                    Err(Error::InvalidTimestamp { .. })
+                ),
+                "{}",
+                input
+            );
+        }
+    }
+}
@@ -0,0 +1,327 @@
+//! External secret detectors for Mortimer
+//!
+//! Some secrets can only be recognised by logic that does not fit in a regex,
+//! such as keys with a tenant prefix and a checksum. A detector is an
+//! executable that reads commands and answers with the spans it considers
+//! secret; the engine merges those spans with its own matches.
+//!
+//! # Protocol
+//!
+//! The detector is started once and kept running for as long as the engine
+//! lives, so a batch import does not start a process per command. For each
+//! command Mortimer writes one JSON line to its stdin:
+//!
+//! ```text
+//! {"command":"deploy --key tnt_4f2a9c1e"}
+//! ```
+//!
+//! and expects one JSON line back on stdout, with byte offsets into the
+//! command:
+//!
+//! ```text
+//! {"spans":[{"start":13,"end":25,"type":"tenant_key"}]}
+//! ```
+//!
+//! A detector that exits, replies late or replies with something else is
+//! restarted on the next command. Whether the command in hand is redacted
+//! without it or refused depends on its `on_failure` setting.
+
+use crate::config::{DetectorConfig, DetectorFailure};
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+use tracing::warn;
+
+/// A secret found by a detector
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct DetectedSpan {
+    /// Byte offset where the secret starts
+    pub start: usize,
+    /// Byte offset just past the secret
+    pub end: usize,
+    /// Kind of secret, used as the placeholder type
+    #[serde(rename = "type")]
+    pub token_type: String,
+}
+
+#[derive(Serialize)]
+struct Request<'a> {
+    command: &'a str,
+}
+
+#[derive(Deserialize)]
+struct Reply {
+    spans: Vec<DetectedSpan>,
+}
+
+/// A running detector process
+struct DetectorProcess {
+    child: Child,
+    stdin: ChildStdin,
+    replies: Receiver<String>,
+}
+
+/// An external detector, started on first use
+pub struct ExternalDetector {
+    config: DetectorConfig,
+    process: Mutex<Option<DetectorProcess>>,
+}
+
+impl std::fmt::Debug for ExternalDetector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ExternalDetector")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ExternalDetector {
+    pub fn new(config: DetectorConfig) -> Self {
+        Self {
+            config,
+            process: Mutex::new(None),
+        }
+    }
+
+    /// Rule id for matches from this detector
+    pub fn rule_id(&self) -> String {
+        format!("detector:{}", self.config.name)
+    }
+
+    /// The executable, for display
+    pub fn command(&self) -> String {
+        self.config.command.display().to_string()
+    }
+
+    /// Ask the detector for the secret spans in a command
+    ///
+    /// Spans are sorted and never overlap. When the detector fails, a
+    /// fail-open detector reports no spans and a fail-closed one returns an
+    /// error.
+    pub fn detect(&self, command: &str) -> Result<Vec<DetectedSpan>> {
+        match self.query(command) {
+            Ok(spans) => Ok(spans),
+            Err(reason) => {
+                // Whatever state the process is in, start afresh next time
+                self.stop();
+                match self.config.on_failure {
+                    DetectorFailure::Open => {
+                        warn!(
+                            "Detector {} failed, redacting without it: {}",
+                            self.config.name, reason
+                        );
+                        Ok(Vec::new())
+                    }
+                    DetectorFailure::Closed => {
+                        Err(Error::detector_failed(self.config.name.clone(), reason))
+                    }
+                }
+            }
+        }
+    }
+
+    fn query(&self, command: &str) -> std::result::Result<Vec<DetectedSpan>, String> {
+        let mut guard = self
+            .process
+            .lock()
+            .map_err(|_| "detector lock poisoned".to_string())?;
+        if guard.is_none() {
+            *guard = Some(self.spawn()?);
+        }
+        let process = guard.as_mut().expect("process was just started");
+
+        let request = serde_json::to_string(&Request { command }).map_err(|e| e.to_string())?;
+        writeln!(process.stdin, "{}", request)
+            .and_then(|_| process.stdin.flush())
+            .map_err(|e| format!("cannot write request: {}", e))?;
+
+        let line = match process
+            .replies
+            .recv_timeout(Duration::from_millis(self.config.timeout_ms))
+        {
+            Ok(line) => line,
+            Err(RecvTimeoutError::Timeout) => {
+                return Err(format!("no reply within {} ms", self.config.timeout_ms));
+            }
+            Err(RecvTimeoutError::Disconnected) => return Err("detector exited".to_string()),
+        };
+
+        let reply: Reply =
+            serde_json::from_str(&line).map_err(|e| format!("invalid reply: {}", e))?;
+        let mut spans = reply.spans;
+        for span in &spans {
+            let valid = span.start < span.end
+                && span.end <= command.len()
+                && command.is_char_boundary(span.start)
+                && command.is_char_boundary(span.end);
+            if !valid {
+                return Err(format!("invalid span {}..{}", span.start, span.end));
+            }
+        }
+
+        // Keep the first of any overlapping spans
+        spans.sort_by_key(|span| (span.start, std::cmp::Reverse(span.end)));
+        let mut merged: Vec<DetectedSpan> = Vec::with_capacity(spans.len());
+        for span in spans {
+            if merged.last().is_none_or(|last| span.start >= last.end) {
+                merged.push(span);
+            }
+        }
+
+        Ok(merged)
+    }
+
+    fn spawn(&self) -> std::result::Result<DetectorProcess, String> {
+        let mut child = Command::new(&self.config.command)
+            .args(&self.config.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| format!("cannot start {}: {}", self.command(), e))?;
+
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+
+        // Read replies on a thread so a silent detector can be timed out
+        let (sender, replies) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(DetectorProcess {
+            child,
+            stdin,
+            replies,
+        })
+    }
+
+    /// Stop the detector process, if it is running
+    fn stop(&self) {
+        let Ok(mut guard) = self.process.lock() else {
+            return;
+        };
+        if let Some(mut process) = guard.take() {
+            let _ = process.child.kill();
+            let _ = process.child.wait();
+        }
+    }
+}
+
+impl Drop for ExternalDetector {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::{Path, PathBuf};
+    use tempfile::TempDir;
+
+    /// Reports `tnt_` followed by 8 characters, and counts its starts
+    const TENANT_DETECTOR: &str = r#"#!/bin/sh
+echo started >> "$(dirname "$0")/starts"
+while IFS= read -r line; do
+  printf '%s\n' "$line" | awk '{
+    i = index($0, "tnt_")
+    if (i == 0) print "{\"spans\":[]}"
+    else printf "{\"spans\":[{\"start\":%d,\"end\":%d,\"type\":\"tenant_key\"}]}\n", i - 13, i - 1
+  }'
+done
+"#;
+
+    fn write_detector(dir: &Path, name: &str, script: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, script).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    fn detector_config(command: PathBuf, on_failure: DetectorFailure) -> DetectorConfig {
+        DetectorConfig {
+            name: "tenant".to_string(),
+            command,
+            args: Vec::new(),
+            timeout_ms: 2000,
+            on_failure,
+        }
+    }
+
+    #[test]
+    fn test_detector_finds_spans_and_stays_running() {
+        let temp_dir = TempDir::new().unwrap();
+        let command = write_detector(temp_dir.path(), "tenant.sh", TENANT_DETECTOR);
+        let detector = ExternalDetector::new(detector_config(command, DetectorFailure::Closed));
+
+        let spans = detector.detect("deploy --key tnt_4f2a9c1e now").unwrap();
+        assert_eq!(
+            spans,
+            vec![DetectedSpan {
+                start: 13,
+                end: 25,
+                token_type: "tenant_key".to_string(),
+            }]
+        );
+        assert!(detector.detect("ls -la").unwrap().is_empty());
+        assert_eq!(detector.detect("echo tnt_00000000").unwrap().len(), 1);
+
+        let starts = fs::read_to_string(temp_dir.path().join("starts")).unwrap();
+        assert_eq!(starts.lines().count(), 1);
+    }
+
+    #[test]
+    fn test_detector_failure_modes() {
+        let temp_dir = TempDir::new().unwrap();
+        let missing = temp_dir.path().join("missing");
+
+        let open = ExternalDetector::new(detector_config(missing.clone(), DetectorFailure::Open));
+        assert!(open.detect("deploy").unwrap().is_empty());
+
+        let closed = ExternalDetector::new(detector_config(missing, DetectorFailure::Closed));
+        assert!(matches!(
+            closed.detect("deploy"),
+            Err(Error::DetectorFailed { .. })
+        ));
+
+        // Replies that do not fit the command are failures too
+        let bad = write_detector(
+            temp_dir.path(),
+            "bad.sh",
+            "#!/bin/sh\nwhile read -r line; do echo '{\"spans\":[{\"start\":0,\"end\":999,\"type\":\"x\"}]}'; done\n",
+        );
+        let detector = ExternalDetector::new(detector_config(bad, DetectorFailure::Closed));
+        assert!(detector.detect("deploy").is_err());
+    }
+
+    #[test]
+    fn test_detector_timeout_restarts_process() {
+        let temp_dir = TempDir::new().unwrap();
+        let slow = write_detector(
+            temp_dir.path(),
+            "slow.sh",
+            "#!/bin/sh\necho started >> \"$(dirname \"$0\")/starts\"\nwhile read -r line; do sleep 5; done\n",
+        );
+        let mut config = detector_config(slow, DetectorFailure::Closed);
+        config.timeout_ms = 100;
+        let detector = ExternalDetector::new(config);
+
+        assert!(detector.detect("deploy").is_err());
+        assert!(detector.detect("deploy").is_err());
+
+        let starts = fs::read_to_string(temp_dir.path().join("starts")).unwrap();
+        assert_eq!(starts.lines().count(), 2);
+    }
+}
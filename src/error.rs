@@ -4,7 +4,7 @@
 //! providing clear error messages and proper error propagation.
 
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 /// Result type alias for Mortimer operations
@@ -57,6 +57,10 @@ pub enum Error {
     #[error("Invalid timestamp format: {timestamp}")]
     InvalidTimestamp { timestamp: String },
 
+    /// An encrypted database could not be opened
+    #[error("Cannot open database {path}: {reason}")]
+    DatabaseKey { path: PathBuf, reason: String },
+
     /// Permission denied
     #[error("Permission denied: {path}")]
     PermissionDenied { path: PathBuf },
@@ -69,6 +73,10 @@ pub enum Error {
     #[error("Invalid redaction pattern: {pattern}")]
     InvalidRedactionPattern { pattern: String },
 
+    /// An external secret detector failed and is configured to fail closed
+    #[error("Detector {name} failed: {reason}")]
+    DetectorFailed { name: String, reason: String },
+
     /// Shell integration error
     #[error("Shell integration error: {shell} - {reason}")]
     ShellIntegration { shell: String, reason: String },
@@ -81,6 +89,10 @@ pub enum Error {
     #[error("Search failed: {reason}")]
     SearchFailed { reason: String },
 
+    /// A redacted command could not be rebuilt from its tokens
+    #[error("Cannot expand command {id}: {reason}")]
+    ExpandFailed { id: i64, reason: String },
+
     /// Configuration validation failed
     #[error("Configuration validation failed: {field} - {reason}")]
     ConfigValidation { field: String, reason: String },
@@ -128,6 +140,30 @@ impl Error {
         }
     }
 
+    /// Create an expand failed error
+    pub fn expand_failed<S: Into<String>>(id: i64, reason: S) -> Self {
+        Error::ExpandFailed {
+            id,
+            reason: reason.into(),
+        }
+    }
+
+    /// Create a detector failed error
+    pub fn detector_failed<S: Into<String>>(name: S, reason: S) -> Self {
+        Error::DetectorFailed {
+            name: name.into(),
+            reason: reason.into(),
+        }
+    }
+
+    /// Create a database key error
+    pub fn database_key<S: Into<String>>(path: &Path, reason: S) -> Self {
+        Error::DatabaseKey {
+            path: path.to_path_buf(),
+            reason: reason.into(),
+        }
+    }
+
     /// Check if this error is recoverable
     pub fn is_recoverable(&self) -> bool {
         match self {
@@ -148,7 +184,7 @@ impl Error {
             Error::Io(_) => "io",
             Error::Regex(_) => "regex",
             Error::Json(_) => "json",
-            Error::Database(_) => "database",
+            Error::Database(_) | Error::DatabaseKey { .. } => "database",
             Error::ConfigNotFound { .. } | Error::ConfigValidation { .. } => "config",
             Error::HistoryFileNotFound { .. } | Error::InvalidHistoryFormat { .. } => "history",
             Error::HomeDirectoryNotFound => "system",
@@ -157,10 +193,11 @@ impl Error {
             Error::InvalidTimestamp { .. } => "timestamp",
             Error::PermissionDenied { .. } => "permission",
             Error::FileExists { .. } => "file",
-            Error::InvalidRedactionPattern { .. } => "redaction",
+            Error::InvalidRedactionPattern { .. } | Error::DetectorFailed { .. } => "redaction",
             Error::ShellIntegration { .. } => "shell",
             Error::ImportFailed { .. } => "import",
             Error::SearchFailed { .. } => "search",
+            Error::ExpandFailed { .. } => "tokens",
             Error::Custom { .. } => "custom",
         }
     }
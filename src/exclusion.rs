@@ -0,0 +1,496 @@
+//! Exclusion rules for Mortimer
+//!
+//! Decides whether a command is logged at all, before any redaction. Rules
+//! come from `shell_integration.exclude_commands` and
+//! `shell_integration.exclusion_rules`, and can match on:
+//! - The program name, looking through environment assignments and
+//!   wrappers such as `sudo`
+//! - A regex over the whole command
+//! - A glob over the working directory, e.g. `~/secrets/**`
+//! - An environment marker, e.g. `AWS_PROFILE=prod`
+//! - A glob over the hostname
+//!
+//! An `allow` rule that matches wins over every `exclude` rule, so broad
+//! exclusions can have explicit exceptions.
+
+use crate::config::{Config, ExclusionAction, ExclusionRuleConfig};
+use crate::error::{Error, Result};
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+/// Commands that run the program given after them
+const WRAPPERS: &[&str] = &[
+    "sudo", "doas", "env", "command", "builtin", "exec", "nohup", "time",
+];
+
+/// Where a command is being logged
+#[derive(Debug, Clone, Default)]
+pub struct ExclusionContext {
+    pub cwd: PathBuf,
+    pub hostname: String,
+    /// Environment of the logging process
+    pub env: Vec<(String, String)>,
+}
+
+impl ExclusionContext {
+    /// The context of the current process
+    pub fn current() -> Self {
+        Self {
+            cwd: std::env::current_dir().unwrap_or_default(),
+            hostname: hostname::get()
+                .map(|h| h.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            env: std::env::vars().collect(),
+        }
+    }
+
+    fn var(&self, name: &str) -> Option<&str> {
+        self.env
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Whether a command is logged, and which rule decided
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExclusionDecision {
+    pub excluded: bool,
+    /// Name and conditions of the deciding rule; `None` when no rule matched
+    pub rule: Option<String>,
+    /// Exclude rules that matched but were overridden by an allow rule
+    pub overridden: Vec<String>,
+}
+
+/// A compiled exclusion rule
+#[derive(Debug, Clone)]
+struct Rule {
+    name: String,
+    action: ExclusionAction,
+    programs: Vec<String>,
+    /// Words the command must start with, from multi-word `exclude_commands`
+    prefix: Option<String>,
+    regex: Option<Regex>,
+    cwd: Option<Regex>,
+    env: Option<(String, Option<String>)>,
+    host: Option<Regex>,
+    description: String,
+}
+
+impl Rule {
+    fn matches(&self, command: &str, program: Option<&str>, ctx: &ExclusionContext) -> bool {
+        if !self.programs.is_empty()
+            && !program.is_some_and(|p| self.programs.iter().any(|name| name == p))
+        {
+            return false;
+        }
+        if let Some(prefix) = &self.prefix {
+            let command = command.trim_start();
+            let at_boundary = command
+                .strip_prefix(prefix.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace));
+            if !at_boundary {
+                return false;
+            }
+        }
+        if self.regex.as_ref().is_some_and(|re| !re.is_match(command)) {
+            return false;
+        }
+        if self
+            .cwd
+            .as_ref()
+            .is_some_and(|re| !re.is_match(&ctx.cwd.to_string_lossy()))
+        {
+            return false;
+        }
+        if let Some((name, value)) = &self.env {
+            let matched = match (ctx.var(name), value) {
+                (Some(actual), Some(value)) => actual == value,
+                (Some(actual), None) => !actual.is_empty(),
+                (None, _) => false,
+            };
+            if !matched {
+                return false;
+            }
+        }
+        if self
+            .host
+            .as_ref()
+            .is_some_and(|re| !re.is_match(&ctx.hostname))
+        {
+            return false;
+        }
+        true
+    }
+
+    fn label(&self) -> String {
+        format!("{} ({})", self.name, self.description)
+    }
+}
+
+/// Compiled exclusion rules
+#[derive(Debug, Clone)]
+pub struct ExclusionRules {
+    rules: Vec<Rule>,
+    min_length: usize,
+    log_space_prefixed: bool,
+}
+
+impl ExclusionRules {
+    /// Compile the exclusion rules of a configuration
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let shell = &config.shell_integration;
+        let mut rules = Vec::new();
+
+        for entry in &shell.exclude_commands {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+            let (programs, prefix) = if entry.contains(char::is_whitespace) {
+                (Vec::new(), Some(entry.to_string()))
+            } else {
+                (vec![entry.to_string()], None)
+            };
+            rules.push(Rule {
+                name: format!("exclude_commands: {}", entry),
+                action: ExclusionAction::Exclude,
+                description: match &prefix {
+                    Some(prefix) => format!("command starts with '{}'", prefix),
+                    None => format!("program {}", entry),
+                },
+                programs,
+                prefix,
+                regex: None,
+                cwd: None,
+                env: None,
+                host: None,
+            });
+        }
+
+        for (index, rule) in shell.exclusion_rules.iter().enumerate() {
+            rules.push(compile_rule(index, rule)?);
+        }
+
+        Ok(Self {
+            rules,
+            min_length: shell.min_command_length,
+            log_space_prefixed: shell.log_space_prefixed,
+        })
+    }
+
+    /// Decide whether a command is logged in the given context
+    pub fn decide(&self, command: &str, ctx: &ExclusionContext) -> ExclusionDecision {
+        if command.len() < self.min_length {
+            return ExclusionDecision {
+                excluded: true,
+                rule: Some(format!("min_command_length ({})", self.min_length)),
+                overridden: Vec::new(),
+            };
+        }
+        if !self.log_space_prefixed && command.starts_with(' ') {
+            return ExclusionDecision {
+                excluded: true,
+                rule: Some("log_space_prefixed (command starts with a space)".to_string()),
+                overridden: Vec::new(),
+            };
+        }
+
+        let program = program_name(command);
+        let matched: Vec<&Rule> = self
+            .rules
+            .iter()
+            .filter(|rule| rule.matches(command, program, ctx))
+            .collect();
+
+        if let Some(allow) = matched
+            .iter()
+            .find(|rule| rule.action == ExclusionAction::Allow)
+        {
+            return ExclusionDecision {
+                excluded: false,
+                rule: Some(allow.label()),
+                overridden: matched
+                    .iter()
+                    .filter(|rule| rule.action == ExclusionAction::Exclude)
+                    .map(|rule| rule.label())
+                    .collect(),
+            };
+        }
+
+        ExclusionDecision {
+            excluded: !matched.is_empty(),
+            rule: matched.first().map(|rule| rule.label()),
+            overridden: Vec::new(),
+        }
+    }
+
+    /// Name and conditions of every rule, in order
+    pub fn describe(&self) -> Vec<(ExclusionAction, String)> {
+        self.rules
+            .iter()
+            .map(|rule| (rule.action, rule.label()))
+            .collect()
+    }
+}
+
+fn compile_rule(index: usize, config: &ExclusionRuleConfig) -> Result<Rule> {
+    let name = config
+        .name
+        .clone()
+        .unwrap_or_else(|| format!("exclusion_rules[{}]", index));
+    let invalid =
+        |reason: String| Error::config_validation(format!("shell_integration.{}", name), reason);
+
+    let mut conditions = Vec::new();
+    if !config.programs.is_empty() {
+        conditions.push(format!("program {}", config.programs.join("|")));
+    }
+    let regex = match &config.regex {
+        Some(pattern) => {
+            conditions.push(format!("regex {}", pattern));
+            Some(Regex::new(pattern).map_err(|e| invalid(e.to_string()))?)
+        }
+        None => None,
+    };
+    let cwd = match &config.cwd {
+        Some(glob) => {
+            conditions.push(format!("cwd {}", glob));
+            Some(glob_to_regex(glob, home::home_dir().as_deref()))
+        }
+        None => None,
+    };
+    let env = match &config.env {
+        Some(marker) => {
+            conditions.push(format!("env {}", marker));
+            let (name, value) = match marker.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (marker.as_str(), None),
+            };
+            if name.is_empty() {
+                return Err(invalid(format!("invalid env marker '{}'", marker)));
+            }
+            Some((name.to_string(), value))
+        }
+        None => None,
+    };
+    let host = match &config.host {
+        Some(glob) => {
+            conditions.push(format!("host {}", glob));
+            Some(glob_to_regex(glob, None))
+        }
+        None => None,
+    };
+
+    if conditions.is_empty() {
+        return Err(invalid("rule has no conditions".to_string()));
+    }
+
+    Ok(Rule {
+        name,
+        action: config.action,
+        programs: config.programs.clone(),
+        prefix: None,
+        regex,
+        cwd,
+        env,
+        host,
+        description: conditions.join(", "),
+    })
+}
+
+/// The program a command runs, looking through `NAME=value` assignments and
+/// wrappers such as `sudo`
+///
+/// A wrapper followed by an option is taken as the program, since its
+/// options may take arguments.
+pub fn program_name(command: &str) -> Option<&str> {
+    let mut words = command.split_whitespace().peekable();
+
+    while let Some(word) = words.next() {
+        if is_assignment(word) {
+            continue;
+        }
+        let word = word.trim_matches(|c| c == '\'' || c == '"');
+        let name = word.rsplit('/').next().unwrap_or(word);
+        if WRAPPERS.contains(&name) && words.peek().is_some_and(|next| !next.starts_with('-')) {
+            continue;
+        }
+        return Some(name);
+    }
+
+    None
+}
+
+fn is_assignment(word: &str) -> bool {
+    match word.split_once('=') {
+        Some((name, _)) => {
+            let mut chars = name.chars();
+            chars
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        None => false,
+    }
+}
+
+/// Compile a glob to an anchored regex
+///
+/// `*` and `?` stop at `/`, `**` crosses directories and a trailing `/**`
+/// also matches the directory itself. A leading `~` is the home directory.
+fn glob_to_regex(glob: &str, home: Option<&Path>) -> Regex {
+    let mut glob = glob.to_string();
+    if let Some(home) = home
+        && (glob == "~" || glob.starts_with("~/"))
+    {
+        glob = format!("{}{}", home.display(), &glob[1..]);
+    }
+
+    let (body, any_below) = match glob.strip_suffix("/**") {
+        Some(body) => (body, true),
+        None => (glob.as_str(), false),
+    };
+
+    let mut pattern = String::from("^");
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    pattern.push_str("(?:.*/)?");
+                } else {
+                    pattern.push_str(".*");
+                }
+            }
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    if any_below {
+        pattern.push_str("(?:/.*)?");
+    }
+    pattern.push('$');
+
+    Regex::new(&pattern).expect("escaped glob is a valid regex")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(cwd: &str) -> ExclusionContext {
+        ExclusionContext {
+            cwd: PathBuf::from(cwd),
+            hostname: "build-01".to_string(),
+            env: vec![("AWS_PROFILE".to_string(), "prod".to_string())],
+        }
+    }
+
+    #[test]
+    fn test_program_name() {
+        assert_eq!(program_name("ls -la"), Some("ls"));
+        assert_eq!(program_name("  /usr/bin/lsof -i"), Some("lsof"));
+        assert_eq!(program_name("FOO=1 BAR=2 sudo cdk deploy"), Some("cdk"));
+        assert_eq!(program_name("sudo -u root ls"), Some("sudo"));
+        assert_eq!(program_name("FOO=1"), None);
+    }
+
+    #[test]
+    fn test_glob_to_regex() {
+        let home = Path::new("/home/me");
+        let secrets = glob_to_regex("~/secrets/**", Some(home));
+        assert!(secrets.is_match("/home/me/secrets"));
+        assert!(secrets.is_match("/home/me/secrets/a/b"));
+        assert!(!secrets.is_match("/home/me/secrets-old"));
+
+        let one_level = glob_to_regex("/srv/*/keys", None);
+        assert!(one_level.is_match("/srv/app/keys"));
+        assert!(!one_level.is_match("/srv/app/x/keys"));
+        assert!(glob_to_regex("/srv/**/keys", None).is_match("/srv/app/x/keys"));
+    }
+
+    #[test]
+    fn test_exclude_commands_match_program_names() {
+        let config = Config::default();
+        let rules = ExclusionRules::from_config(&config).unwrap();
+        let ctx = context("/tmp");
+
+        assert!(rules.decide("ls -la", &ctx).excluded);
+        assert!(rules.decide("sudo ls /root", &ctx).excluded);
+        assert!(rules.decide("cd /tmp", &ctx).excluded);
+        assert!(!rules.decide("echo hello", &ctx).excluded);
+        assert!(!rules.decide("grep pattern file", &ctx).excluded);
+        assert!(!rules.decide("lsof -i :8080", &ctx).excluded);
+        assert!(!rules.decide("cdk deploy", &ctx).excluded);
+        assert!(rules.decide(" echo hidden", &ctx).excluded);
+    }
+
+    #[test]
+    fn test_rules_and_allow_overrides() {
+        let mut config = Config::default();
+        config.shell_integration.exclusion_rules = vec![
+            ExclusionRuleConfig {
+                name: Some("secrets-dir".to_string()),
+                cwd: Some("/home/me/secrets/**".to_string()),
+                ..Default::default()
+            },
+            ExclusionRuleConfig {
+                name: Some("prod".to_string()),
+                env: Some("AWS_PROFILE=prod".to_string()),
+                regex: Some("^aws ".to_string()),
+                ..Default::default()
+            },
+            ExclusionRuleConfig {
+                name: Some("allow-git-on-build-hosts".to_string()),
+                action: ExclusionAction::Allow,
+                programs: vec!["git".to_string()],
+                host: Some("build-*".to_string()),
+                ..Default::default()
+            },
+        ];
+        let rules = ExclusionRules::from_config(&config).unwrap();
+
+        let secrets = context("/home/me/secrets/db");
+        let decision = rules.decide("cat password.txt", &secrets);
+        assert!(decision.excluded);
+        assert!(decision.rule.unwrap().starts_with("secrets-dir"));
+
+        let decision = rules.decide("git status", &secrets);
+        assert!(!decision.excluded);
+        assert!(
+            decision
+                .rule
+                .unwrap()
+                .starts_with("allow-git-on-build-hosts")
+        );
+        assert_eq!(decision.overridden.len(), 1);
+
+        assert!(rules.decide("aws s3 ls", &context("/tmp")).excluded);
+        let decision = rules.decide("echo aws", &context("/tmp"));
+        assert_eq!(
+            decision,
+            ExclusionDecision {
+                excluded: false,
+                rule: None,
+                overridden: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        let mut config = Config::default();
+        config.shell_integration.exclusion_rules = vec![ExclusionRuleConfig::default()];
+        assert!(ExclusionRules::from_config(&config).is_err());
+
+        config.shell_integration.exclusion_rules = vec![ExclusionRuleConfig {
+            regex: Some("(".to_string()),
+            ..Default::default()
+        }];
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,254 @@
+//! Unicode folding for search
+//!
+//! Commands and search terms are folded before they are compared: Unicode
+//! case folding unless the search is case-sensitive, canonical composition
+//! so precomposed and decomposed accents match, and optionally compatibility
+//! normalization (NFKC, so `ﬁ` matches `fi` and full-width letters match
+//! ASCII) and accent stripping (so `cafe` matches `café`).
+//!
+//! Folding can change the length of text — `İ` folds to two characters and
+//! `ß` to `ss` — so a folded command keeps, for each of its characters, the
+//! bytes of the original command it came from. Matches found in the folded
+//! text map back to whole characters of the original.
+
+use caseless::Caseless;
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+/// How text is folded before matching
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Folding {
+    /// Keep case as written
+    pub case_sensitive: bool,
+    /// Apply compatibility normalization (NFKC)
+    pub normalize: bool,
+    /// Drop accents and other combining marks
+    pub ignore_accents: bool,
+}
+
+/// A character of folded text and the original bytes it came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Origin {
+    /// Byte offset of the character in the folded text
+    folded: usize,
+    /// Byte span in the original text
+    start: usize,
+    end: usize,
+}
+
+/// Text folded for matching, with the way back to the original
+#[derive(Debug, Clone)]
+pub struct Folded<'a> {
+    original: &'a str,
+    text: String,
+    /// Origin of every folded character, or `None` when folding kept every
+    /// byte in place
+    origins: Option<Vec<Origin>>,
+}
+
+impl Folding {
+    /// Fold `text` for matching
+    pub fn fold<'a>(&self, text: &'a str) -> Folded<'a> {
+        // ASCII folds byte for byte
+        if text.is_ascii() {
+            let text_folded = if self.case_sensitive {
+                text.to_string()
+            } else {
+                text.to_ascii_lowercase()
+            };
+            return Folded {
+                original: text,
+                text: text_folded,
+                origins: None,
+            };
+        }
+
+        let mut folded = String::with_capacity(text.len());
+        let mut origins = Vec::with_capacity(text.len());
+        for (start, cluster) in clusters(text) {
+            let end = start + cluster.len();
+            for c in self.fold_cluster(cluster).chars() {
+                origins.push(Origin {
+                    folded: folded.len(),
+                    start,
+                    end,
+                });
+                folded.push(c);
+            }
+        }
+
+        Folded {
+            original: text,
+            text: folded,
+            origins: Some(origins),
+        }
+    }
+
+    /// Fold `text` without keeping the way back, for search terms
+    pub fn fold_term(&self, text: &str) -> String {
+        self.fold(text).text
+    }
+
+    fn fold_cluster(&self, cluster: &str) -> String {
+        let cased: String = if self.case_sensitive {
+            cluster.to_string()
+        } else {
+            cluster.chars().default_case_fold().collect()
+        };
+        let decomposed: Vec<char> = if self.normalize {
+            cased.nfkd().collect()
+        } else {
+            cased.nfd().collect()
+        };
+        decomposed
+            .into_iter()
+            .filter(|&c| !(self.ignore_accents && is_combining_mark(c)))
+            .nfc()
+            .collect()
+    }
+}
+
+impl<'a> Folded<'a> {
+    /// The folded text
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// The text before folding
+    pub fn original(&self) -> &'a str {
+        self.original
+    }
+
+    /// Map a byte span of the folded text to the span of the original it
+    /// came from, widened to whole characters
+    pub fn original_span(&self, start: usize, end: usize) -> (usize, usize) {
+        let Some(origins) = &self.origins else {
+            return (start, end);
+        };
+        let containing = |offset: usize| {
+            origins
+                .partition_point(|origin| origin.folded <= offset)
+                .saturating_sub(1)
+        };
+        let first = &origins[containing(start)];
+        let last = &origins[containing(end.max(start + 1) - 1)];
+        (first.start, last.end)
+    }
+
+    /// Each folded character with the byte span of the original it came from
+    pub fn chars(&self) -> Vec<(char, usize, usize)> {
+        match &self.origins {
+            Some(origins) => self
+                .text
+                .chars()
+                .zip(origins)
+                .map(|(c, origin)| (c, origin.start, origin.end))
+                .collect(),
+            None => self
+                .text
+                .char_indices()
+                .map(|(offset, c)| (c, offset, offset + c.len_utf8()))
+                .collect(),
+        }
+    }
+}
+
+/// Split text into characters with the combining marks that follow them,
+/// each with its byte offset
+fn clusters(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut starts = text
+        .char_indices()
+        .filter(|&(offset, c)| offset == 0 || !is_combining_mark(c))
+        .map(|(offset, _)| offset)
+        .peekable();
+    std::iter::from_fn(move || {
+        let start = starts.next()?;
+        let end = starts.peek().copied().unwrap_or(text.len());
+        Some((start, &text[start..end]))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The original text each match of `term` in `text` covers
+    fn find<'a>(folding: Folding, text: &'a str, term: &str) -> Vec<&'a str> {
+        let folded = folding.fold(text);
+        let term = folding.fold_term(term);
+        folded
+            .as_str()
+            .match_indices(&term)
+            .map(|(start, found)| {
+                let (start, end) = folded.original_span(start, start + found.len());
+                &text[start..end]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_case_folding_maps_back_to_the_original() {
+        let folding = Folding::default();
+
+        // "İ" folds to two characters, "i" and a combining dot, and "ß" to "ss"
+        assert_eq!(find(folding, "echo İstanbul", "İSTANBUL"), vec!["İstanbul"]);
+        assert_eq!(find(folding, "echo İstanbul", "stan"), vec!["stan"]);
+        assert_eq!(find(folding, "cd /tmp/Straße", "STRASSE"), vec!["Straße"]);
+        assert_eq!(find(folding, "grep ΣΟΦΙΑ notes", "σοφια"), vec!["ΣΟΦΙΑ"]);
+        assert_eq!(find(folding, "echo Ünïcödé", "ünïcödé"), vec!["Ünïcödé"]);
+
+        // Precomposed and decomposed accents are the same text
+        assert_eq!(
+            find(folding, "vim cafe\u{301}.txt", "café"),
+            vec!["cafe\u{301}"]
+        );
+        assert_eq!(find(folding, "vim café.txt", "cafe\u{301}"), vec!["café"]);
+
+        let sensitive = Folding {
+            case_sensitive: true,
+            ..Folding::default()
+        };
+        assert!(find(sensitive, "echo İstanbul", "istanbul").is_empty());
+        assert_eq!(
+            find(sensitive, "echo İstanbul", "İstanbul"),
+            vec!["İstanbul"]
+        );
+    }
+
+    #[test]
+    fn test_normalization_and_accents() {
+        let plain = Folding::default();
+        let normalized = Folding {
+            normalize: true,
+            ..Folding::default()
+        };
+        let accentless = Folding {
+            ignore_accents: true,
+            ..Folding::default()
+        };
+
+        assert!(find(plain, "ｌｓ －ｌａ", "ls").is_empty());
+        assert_eq!(find(normalized, "ｌｓ －ｌａ", "ls"), vec!["ｌｓ"]);
+        assert_eq!(find(normalized, "echo ﬁle", "file"), vec!["ﬁle"]);
+
+        assert!(find(plain, "open résumé.pdf", "resume").is_empty());
+        assert_eq!(
+            find(accentless, "open résumé.pdf", "resume"),
+            vec!["résumé"]
+        );
+        assert_eq!(
+            find(accentless, "open résumé.pdf", "RÉSUMÉ"),
+            vec!["résumé"]
+        );
+        assert_eq!(find(accentless, "cd Zürich", "zurich"), vec!["Zürich"]);
+        assert_eq!(
+            find(accentless, "echo İstanbul", "istanbul"),
+            vec!["İstanbul"]
+        );
+
+        // Every folded character knows the whole original character
+        let folded = accentless.fold("é!");
+        assert_eq!(folded.as_str(), "e!");
+        assert_eq!(folded.chars(), vec![('e', 0, 2), ('!', 2, 3)]);
+    }
+}
@@ -0,0 +1,337 @@
+//! Fuzzy matching for search
+//!
+//! Scores how well a needle matches a command as a subsequence, in the
+//! manner of fzf: a Smith-Waterman style alignment where every matched
+//! character scores, gaps between matches cost, and characters at word
+//! starts, after path separators, at camelCase humps and in consecutive runs
+//! earn bonuses. The best alignment is kept along with the position of each
+//! character it matched, so highlights mark exactly those characters.
+//!
+//! Matching runs on folded text (see [`crate::folding`]); bonuses come from
+//! the command as written, so camelCase humps survive case folding.
+
+use crate::folding::Folded;
+
+/// Score for each matched character
+const SCORE_MATCH: i32 = 16;
+/// Cost of opening a gap between two matched characters
+const SCORE_GAP_START: i32 = -3;
+/// Cost of each further character in a gap
+const SCORE_GAP_EXTENSION: i32 = -1;
+
+/// Bonus for a character at the start of a word
+const BONUS_BOUNDARY: i32 = SCORE_MATCH / 2;
+/// Bonus for a character at the start of a word after whitespace
+const BONUS_BOUNDARY_WHITE: i32 = BONUS_BOUNDARY + 2;
+/// Bonus for a character at the start of a word after a delimiter, such as
+/// a path separator
+const BONUS_BOUNDARY_DELIMITER: i32 = BONUS_BOUNDARY + 1;
+/// Bonus for a non-word character; they are rare enough in needles that
+/// matching one means something
+const BONUS_NON_WORD: i32 = SCORE_MATCH / 2;
+/// Bonus for an uppercase letter after a lowercase one, or a digit after a
+/// non-digit
+const BONUS_CAMEL_123: i32 = BONUS_BOUNDARY + SCORE_GAP_EXTENSION;
+/// Least bonus for a character right after the previous match, enough to
+/// outweigh opening a gap
+const BONUS_CONSECUTIVE: i32 = -(SCORE_GAP_START + SCORE_GAP_EXTENSION);
+/// The first character of the needle counts its bonus this many times
+const BONUS_FIRST_CHAR_MULTIPLIER: i32 = 2;
+
+/// Score of a cell no alignment reaches
+const UNREACHED: i32 = i32::MIN / 2;
+
+/// The best alignment of a needle in a command
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzyMatch {
+    /// Between 0 and 1, reaching 1 when the needle is a word the command
+    /// starts with
+    pub score: f64,
+    /// Byte offset in the command of each matched character, in order
+    pub positions: Vec<usize>,
+    /// Byte spans of the matched characters in the command, adjacent
+    /// characters joined into one span
+    pub spans: Vec<(usize, usize)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    White,
+    NonWord,
+    Delimiter,
+    Lower,
+    Upper,
+    Letter,
+    Number,
+}
+
+impl CharClass {
+    fn of(c: char) -> Self {
+        if c.is_whitespace() {
+            Self::White
+        } else if c.is_lowercase() {
+            Self::Lower
+        } else if c.is_uppercase() {
+            Self::Upper
+        } else if c.is_numeric() {
+            Self::Number
+        } else if c.is_alphabetic() {
+            Self::Letter
+        } else if matches!(c, '/' | ',' | ':' | ';' | '|') {
+            Self::Delimiter
+        } else {
+            Self::NonWord
+        }
+    }
+
+    fn is_word(self) -> bool {
+        matches!(
+            self,
+            Self::Lower | Self::Upper | Self::Letter | Self::Number
+        )
+    }
+}
+
+/// Bonus for matching a character of class `class` that follows one of
+/// class `prev`
+fn bonus_for(prev: CharClass, class: CharClass) -> i32 {
+    if class.is_word() {
+        match prev {
+            CharClass::White => return BONUS_BOUNDARY_WHITE,
+            CharClass::Delimiter => return BONUS_BOUNDARY_DELIMITER,
+            CharClass::NonWord => return BONUS_BOUNDARY,
+            _ => {}
+        }
+    }
+    if (prev == CharClass::Lower && class == CharClass::Upper)
+        || (prev != CharClass::Number && class == CharClass::Number)
+    {
+        return BONUS_CAMEL_123;
+    }
+    match class {
+        CharClass::NonWord | CharClass::Delimiter => BONUS_NON_WORD,
+        CharClass::White => BONUS_BOUNDARY_WHITE,
+        _ => 0,
+    }
+}
+
+/// Find the best alignment of `needle`, folded the same way, in `haystack`,
+/// if every character of the needle appears in it in order
+pub fn best_match(haystack: &Folded, needle: &str) -> Option<FuzzyMatch> {
+    let needle: Vec<char> = needle.chars().collect();
+    if needle.is_empty() {
+        return Some(FuzzyMatch {
+            score: 0.0,
+            positions: Vec::new(),
+            spans: Vec::new(),
+        });
+    }
+
+    let folded = haystack.chars();
+    let chars: Vec<char> = folded.iter().map(|&(c, _, _)| c).collect();
+
+    // Most commands do not match at all; rule them out before scoring
+    let mut remaining = needle.iter().peekable();
+    for c in &chars {
+        if remaining.peek() == Some(&c) {
+            remaining.next();
+        }
+    }
+    if remaining.peek().is_some() {
+        return None;
+    }
+
+    let original = haystack.original();
+    let mut prev = CharClass::White;
+    let bonus: Vec<i32> = folded
+        .iter()
+        .map(|&(_, start, _)| {
+            let class = original[start..]
+                .chars()
+                .next()
+                .map_or(CharClass::White, CharClass::of);
+            let bonus = bonus_for(prev, class);
+            prev = class;
+            bonus
+        })
+        .collect();
+
+    // For each needle character i matched at column j: the best score of
+    // the needle up to i, the bonus at the start of the run of consecutive
+    // matches the cell ends, and the column needle character i - 1 matched
+    let width = chars.len();
+    let mut score = vec![UNREACHED; needle.len() * width];
+    let mut run_bonus = vec![0; needle.len() * width];
+    let mut from = vec![0; needle.len() * width];
+
+    for (j, &c) in chars.iter().enumerate() {
+        if c == needle[0] {
+            score[j] = SCORE_MATCH + bonus[j] * BONUS_FIRST_CHAR_MULTIPLIER;
+            run_bonus[j] = bonus[j];
+        }
+    }
+
+    for (i, &wanted) in needle.iter().enumerate().skip(1) {
+        let row = i * width;
+        let above = row - width;
+        // Best cell of the previous row to reach the current column through
+        // a gap, with the gap already charged
+        let mut gap: Option<(i32, usize)> = None;
+
+        for j in i..width {
+            if j >= 2 {
+                let decayed = gap.map(|(value, k)| (value + SCORE_GAP_EXTENSION, k));
+                let entering = score[above + j - 2];
+                gap = match decayed {
+                    Some((value, _)) if value >= entering + SCORE_GAP_START => decayed,
+                    _ if entering > UNREACHED => Some((entering + SCORE_GAP_START, j - 2)),
+                    _ => decayed,
+                };
+            }
+            if chars[j] != wanted {
+                continue;
+            }
+
+            let mut best = UNREACHED;
+            let diagonal = score[above + j - 1];
+            if diagonal > UNREACHED {
+                let start_bonus = run_bonus[above + j - 1];
+                let (gain, run) = if bonus[j] >= BONUS_BOUNDARY && bonus[j] > start_bonus {
+                    // A new word starts here; it begins a run of its own
+                    (bonus[j], bonus[j])
+                } else {
+                    (
+                        bonus[j].max(BONUS_CONSECUTIVE).max(start_bonus),
+                        start_bonus,
+                    )
+                };
+                best = diagonal + SCORE_MATCH + gain;
+                run_bonus[row + j] = run;
+                from[row + j] = j - 1;
+            }
+            if let Some((value, k)) = gap {
+                let gapped = value + SCORE_MATCH + bonus[j];
+                if gapped > best {
+                    best = gapped;
+                    run_bonus[row + j] = bonus[j];
+                    from[row + j] = k;
+                }
+            }
+            score[row + j] = best;
+        }
+    }
+
+    let last = (needle.len() - 1) * width;
+    let (mut column, best) =
+        (0..width)
+            .map(|j| (j, score[last + j]))
+            .fold(
+                (0, UNREACHED),
+                |best, cell| if cell.1 > best.1 { cell } else { best },
+            );
+    if best <= UNREACHED {
+        return None;
+    }
+
+    let mut columns = vec![0; needle.len()];
+    for i in (0..needle.len()).rev() {
+        columns[i] = column;
+        column = from[i * width + column];
+    }
+
+    // Several folded characters can come from one original character
+    let mut positions: Vec<usize> = Vec::with_capacity(columns.len());
+    let mut spans: Vec<(usize, usize)> = Vec::new();
+    for column in columns {
+        let (_, start, end) = folded[column];
+        if positions.last() == Some(&start) {
+            continue;
+        }
+        positions.push(start);
+        match spans.last_mut() {
+            Some(last) if last.1 == start => last.1 = end,
+            _ => spans.push((start, end)),
+        }
+    }
+
+    let perfect = SCORE_MATCH * needle.len() as i32
+        + BONUS_BOUNDARY_WHITE * (needle.len() as i32 - 1 + BONUS_FIRST_CHAR_MULTIPLIER);
+    Some(FuzzyMatch {
+        score: (best as f64 / perfect as f64).clamp(0.0, 1.0),
+        positions,
+        spans,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::folding::Folding;
+
+    fn find(haystack: &str, needle: &str, folding: Folding) -> Option<FuzzyMatch> {
+        best_match(&folding.fold(haystack), &folding.fold_term(needle))
+    }
+
+    fn positions(haystack: &str, needle: &str) -> Vec<usize> {
+        find(haystack, needle, Folding::default())
+            .unwrap()
+            .positions
+    }
+
+    fn score(haystack: &str, needle: &str) -> f64 {
+        find(haystack, needle, Folding::default()).unwrap().score
+    }
+
+    #[test]
+    fn test_matched_positions() {
+        // Word starts win over the first occurrence of each character
+        assert_eq!(positions("git status", "gst"), vec![0, 4, 5]);
+        assert_eq!(positions("cargo build --release", "cbr"), vec![0, 6, 14]);
+        assert_eq!(positions("cd ~/src/mortimer/target", "mt"), vec![9, 18]);
+        assert_eq!(positions("docker compose up", "up"), vec![15, 16]);
+        assert_eq!(positions("ProcessManager", "pm"), vec![0, 7]);
+
+        let found = find("git status", "gst", Folding::default()).unwrap();
+        assert_eq!(found.spans, vec![(0, 1), (4, 6)]);
+
+        let sensitive = Folding {
+            case_sensitive: true,
+            ..Folding::default()
+        };
+        assert!(find("ls -la", "lz", Folding::default()).is_none());
+        assert!(find("Makefile", "mk", sensitive).is_none());
+        assert!(find("make", "mk", sensitive).is_some());
+    }
+
+    #[test]
+    fn test_non_ascii_positions() {
+        // Positions and spans are whole characters of the command
+        let found = find("cd ~/Dokumente/Übersicht", "üb", Folding::default()).unwrap();
+        assert_eq!(found.positions, vec![15, 17]);
+        assert_eq!(found.spans, vec![(15, 18)]);
+
+        // Both folded characters of "ß" come from one character
+        let command = "mv Straße.txt out";
+        let found = find(command, "strasse", Folding::default()).unwrap();
+        assert_eq!(found.spans, vec![(3, 10)]);
+        assert_eq!(&command[3..10], "Straße");
+
+        let found = find("echo İstanbul", "ist", Folding::default()).unwrap();
+        assert_eq!(found.spans, vec![(5, 9)]);
+    }
+
+    #[test]
+    fn test_scores_rank_alignments() {
+        // A word the command starts with is a perfect match
+        assert_eq!(score("make install", "make"), 1.0);
+        // Consecutive runs beat scattered characters
+        assert!(score("git checkout", "check") > score("cargo hack check", "chck"));
+        // Word starts beat characters inside words
+        assert!(score("npm run test", "nrt") > score("xenortic", "nrt"));
+        // Path segments and camelCase humps count as word starts
+        assert!(score("vim src/main.rs", "main") > score("vim src/domain.rs", "main"));
+        assert!(score("getUserName", "un") > score("sunburn", "un"));
+        // Shorter gaps cost less
+        assert!(score("a_b", "ab") > score("a__________b", "ab"));
+    }
+}
@@ -4,14 +4,21 @@
 //! including logging, importing, searching, and maintaining command history
 //! with automatic redaction and deduplication.
 
-use crate::config::Config;
+use crate::archive::Archive;
+use crate::backend::{LogOutcome, ShredReport, TrashEntry};
+use crate::config::{Config, PolicyDecision, SecretPolicy};
+use crate::dates::TimeSpan;
 use crate::error::{Error, Result};
-use crate::redaction::{RedactionEngine, RedactionStats};
+use crate::exclusion::{ExclusionContext, ExclusionRules};
+use crate::redaction::{RedactionEngine, RedactionStats, RuleHits};
+use crate::retention::{Expired, RetentionPolicies, RetentionSubject};
+use crate::secure_file;
+use crate::timezone::{self, DisplayTimezone, local_offset};
 use chrono::{DateTime, Utc};
 use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs::{File, OpenOptions};
-use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::io::{BufRead, BufReader, Write};
 use std::path::PathBuf;
 
 /// Represents a single command entry in the history
@@ -29,6 +36,19 @@ pub struct HistoryEntry {
     pub original: Option<String>,
     /// Whether this entry is marked as deleted
     pub deleted: bool,
+    /// Host the command ran on, if the backend records it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    /// Shell session the command ran in, if the backend records it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<String>,
+    /// Exit code of the command, if it was recorded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    /// Offset from UTC, in seconds, of the zone the command was logged in,
+    /// if it was recorded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub utc_offset: Option<i32>,
 }
 
 /// Statistics about the history
@@ -52,23 +72,62 @@ pub struct HistoryStats {
 pub struct HistoryManager {
     config: Config,
     redaction_engine: RedactionEngine,
+    exclusion_rules: ExclusionRules,
+    retention: RetentionPolicies,
     history_file: PathBuf,
+    /// Redaction events, one `timestamp | kind | name` line each
+    events_file: PathBuf,
+    /// Deleted entries, each history line prefixed with `deleted_at | `
+    trash_file: PathBuf,
+    /// Compressed segments holding entries rotated out of the history file
+    archive: Archive,
+    /// Hashes of the history lines restored from the archive, one per line;
+    /// they do not count against `max_entries` and are not rotated again
+    restored_file: PathBuf,
     stats: HistoryStats,
 }
 
+/// Summary of one archive segment
+#[derive(Debug, Clone)]
+pub struct ArchiveSegment {
+    /// Date the entries were archived, which names the segment
+    pub name: String,
+    pub entries: usize,
+    pub first: Option<DateTime<Utc>>,
+    pub last: Option<DateTime<Utc>>,
+    /// Compressed size in bytes
+    pub size: u64,
+}
+
+/// Event kind for a redaction rule that fired
+const EVENT_RULE: &str = "rule";
+/// Event kind for a secret policy action
+const EVENT_POLICY: &str = "policy";
+
+/// Once the history is over `max_entries`, this fraction of it is archived
+/// in one go, so that every later command does not add a tiny segment
+const ARCHIVE_BATCH_DIVISOR: usize = 10;
+
+/// Hash identifying a history line restored from the archive, so the
+/// restored file does not hold another copy of the commands
+fn line_hash(line: &str) -> String {
+    use sha2::{Digest, Sha256};
+    format!("{:x}", Sha256::digest(line.as_bytes()))
+}
+
+/// ID of a line in the trash: the first 32 bits of its hash, short enough
+/// to type
+fn trash_id(line: &str) -> i64 {
+    i64::from_str_radix(&line_hash(line)[..8], 16).unwrap_or_default()
+}
+
 impl HistoryManager {
     /// Create a new history manager with the given configuration
     #[must_use = "History manager must be used to log commands"]
     pub fn new(config: Config) -> Result<Self> {
-        let redaction_engine = RedactionEngine::with_config(
-            config.redaction.use_builtin_patterns,
-            config.redaction.custom_patterns.clone(),
-            config.redaction.exclude_patterns.clone(),
-            config.redaction.placeholder.clone(),
-            config.redaction.min_redaction_length,
-            config.custom_env_vars.clone(),
-            config.redaction.redact_env_vars,
-        )?;
+        let redaction_engine = RedactionEngine::from_config(&config)?;
+        let exclusion_rules = ExclusionRules::from_config(&config)?;
+        let retention = RetentionPolicies::from_config(&config)?;
 
         let history_file = config.history_file.clone();
 
@@ -80,12 +139,27 @@ impl HistoryManager {
             File::create(&history_file)?;
         }
 
+        let mut events_file = history_file.clone().into_os_string();
+        events_file.push(".events");
+        let mut trash_file = history_file.clone().into_os_string();
+        trash_file.push(".trash");
+        let mut restored_file = history_file.clone().into_os_string();
+        restored_file.push(".restored");
+
+        let archive = Archive::for_history(&history_file);
         let mut manager = Self {
             config,
             redaction_engine,
+            exclusion_rules,
+            retention,
+            archive,
             history_file,
+            events_file: PathBuf::from(events_file),
+            trash_file: PathBuf::from(trash_file),
+            restored_file: PathBuf::from(restored_file),
             stats: HistoryStats::default(),
         };
+        manager.expire_trash()?;
 
         // Load initial statistics
         manager.update_stats()?;
@@ -94,7 +168,7 @@ impl HistoryManager {
     }
 
     /// Log a command to the history
-    pub fn log_command(&mut self, command: &str) -> Result<()> {
+    pub fn log_command(&mut self, command: &str) -> Result<LogOutcome> {
         self.log_command_with_timestamp(command, None)
     }
 
@@ -103,27 +177,71 @@ impl HistoryManager {
         &mut self,
         command: &str,
         timestamp: Option<DateTime<Utc>>,
-    ) -> Result<()> {
-        // Check if we should exclude this command
-        if self.config.should_exclude_command(command) {
-            return Ok(());
-        }
-
-        let timestamp = timestamp.unwrap_or_else(|| Utc::now());
+    ) -> Result<LogOutcome> {
         let directory = env::current_dir()
             .unwrap_or_else(|_| PathBuf::from("<unknown>"))
             .to_string_lossy()
             .to_string();
 
-        // Redact sensitive information
-        let (redacted_command, was_redacted) = if self.config.enable_redaction {
-            let original = command.to_string();
-            let redacted = self
-                .redaction_engine
-                .redact_with_stats(command, &mut self.stats.redaction_stats)?;
-            (redacted.clone(), redacted != original)
+        let outcome = self.record_command(
+            command,
+            directory,
+            timestamp.unwrap_or_else(Utc::now),
+            &ExclusionContext::current(),
+            !self.config.shell_integration.log_duplicates,
+        )?;
+
+        // Trim history if it exceeds max entries
+        if self.config.max_entries > 0 && self.stats.total_entries > self.config.max_entries {
+            self.trim_history()?;
+        }
+
+        Ok(outcome)
+    }
+
+    /// Write a command run in `directory` the way it is logged: excluded,
+    /// redacted and subject to the secret policies, and skipped if
+    /// `skip_duplicates` and it was among the last commands
+    fn record_command(
+        &mut self,
+        command: &str,
+        directory: String,
+        timestamp: DateTime<Utc>,
+        context: &ExclusionContext,
+        skip_duplicates: bool,
+    ) -> Result<LogOutcome> {
+        // Check if we should exclude this command
+        if self.exclusion_rules.decide(command, context).excluded {
+            return Ok(LogOutcome::Skipped);
+        }
+
+        let explanation = if self.config.enable_redaction {
+            Some(self.redaction_engine.explain(command)?)
         } else {
-            (command.to_string(), false)
+            None
+        };
+        let decision = match &explanation {
+            Some(explanation) => self
+                .config
+                .redaction
+                .resolve_policy(explanation.applied_rules()),
+            None => PolicyDecision::default(),
+        };
+
+        if decision.should_drop() {
+            self.record_events(timestamp, &[], &decision)?;
+            return Ok(LogOutcome::Dropped {
+                rules: decision.rules_with(SecretPolicy::Drop),
+            });
+        }
+
+        // The explanation already holds the redacted command
+        let (redacted_command, was_redacted) = match &explanation {
+            Some(explanation) => {
+                self.stats.redaction_stats.record(explanation);
+                (explanation.output.clone(), explanation.output != command)
+            }
+            None => (command.to_string(), false),
         };
 
         let entry = HistoryEntry {
@@ -137,27 +255,114 @@ impl HistoryManager {
                 None
             },
             deleted: false,
+            host: None,
+            session: None,
+            exit_code: None,
+            utc_offset: Some(local_offset(timestamp)),
         };
 
         // Check for duplicates if configured
-        if !self.config.shell_integration.log_duplicates && self.is_duplicate(&entry)? {
+        if skip_duplicates && self.is_duplicate(&entry)? {
             self.stats.duplicates_filtered += 1;
-            return Ok(());
+            return Ok(LogOutcome::Skipped);
         }
 
         self.write_entry(&entry)?;
         self.update_stats_for_entry(&entry);
 
-        // Trim history if it exceeds max entries
-        if self.config.max_entries > 0 && self.stats.total_entries > self.config.max_entries {
-            self.trim_history()?;
+        let rules = match &explanation {
+            Some(explanation) if was_redacted => explanation.applied_rules(),
+            _ => Vec::new(),
+        };
+        self.record_events(timestamp, &rules, &decision)?;
+
+        if decision.should_warn() {
+            Ok(LogOutcome::Warned {
+                rules: decision.rules_with(SecretPolicy::Warn),
+            })
+        } else {
+            Ok(LogOutcome::Logged)
+        }
+    }
+
+    /// Append the rules that fired and the policy actions taken to the
+    /// events file
+    fn record_events(
+        &self,
+        timestamp: DateTime<Utc>,
+        rules: &[&str],
+        decision: &PolicyDecision,
+    ) -> Result<()> {
+        let actions = decision.actions();
+        if rules.is_empty() && actions.is_empty() {
+            return Ok(());
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.events_file)?;
+        let timestamp = timestamp.format("%Y-%m-%d %H:%M:%S");
+
+        for rule in rules {
+            writeln!(file, "{} | {} | {}", timestamp, EVENT_RULE, rule)?;
+        }
+        for action in actions {
+            writeln!(
+                file,
+                "{} | {} | {}",
+                timestamp,
+                EVENT_POLICY,
+                action.as_str()
+            )?;
         }
 
         Ok(())
     }
 
+    /// Read `(kind, name, time)` from the events file
+    fn read_events(&self) -> Result<Vec<(String, String, DateTime<Utc>)>> {
+        if !self.events_file.exists() {
+            return Ok(Vec::new());
+        }
+
+        let reader = BufReader::new(File::open(&self.events_file)?);
+        let mut events = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            let parts: Vec<&str> = line.splitn(3, " | ").collect();
+            let [time, kind, name] = parts[..] else {
+                continue;
+            };
+            let Ok(time) = chrono::NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S") else {
+                continue;
+            };
+            events.push((kind.to_string(), name.to_string(), time.and_utc()));
+        }
+
+        Ok(events)
+    }
+
+    /// Hit counts per redaction rule, from the recorded redaction events
+    pub fn get_rule_hits(&self) -> Result<Vec<RuleHits>> {
+        let events = self
+            .read_events()?
+            .into_iter()
+            .filter(|(kind, _, _)| kind == EVENT_RULE)
+            .map(|(_, rule, time)| (rule, time));
+
+        Ok(RuleHits::summarize(events, Utc::now()))
+    }
+
     /// Import history from a shell history file
-    pub fn import_from_shell(&mut self, shell: &str, file_path: Option<PathBuf>) -> Result<usize> {
+    ///
+    /// With a `window`, only entries from within it are imported.
+    pub fn import_from_shell(
+        &mut self,
+        shell: &str,
+        file_path: Option<PathBuf>,
+        window: Option<TimeSpan>,
+    ) -> Result<usize> {
         let history_path = if let Some(path) = file_path {
             path
         } else {
@@ -177,6 +382,7 @@ impl HistoryManager {
         let reader = BufReader::new(file);
         let mut imported_count = 0;
         let mut seen_commands = HashSet::new();
+        let context = ExclusionContext::current();
 
         for line in reader.lines() {
             let line = line.unwrap_or_default();
@@ -200,6 +406,9 @@ impl HistoryManager {
                         continue;
                     }
                 }
+                if window.is_some_and(|window| !window.contains(entry.timestamp)) {
+                    continue;
+                }
 
                 // Check for duplicates if deduplication is enabled
                 if self.config.import.deduplicate {
@@ -209,8 +418,19 @@ impl HistoryManager {
                     }
                 }
 
-                self.write_entry(&entry)?;
-                imported_count += 1;
+                // Imported commands are redacted like logged ones, and
+                // written in this machine's timezone; duplicates are handled
+                // above
+                let outcome = self.record_command(
+                    &entry.command,
+                    entry.directory,
+                    entry.timestamp,
+                    &context,
+                    false,
+                )?;
+                if matches!(outcome, LogOutcome::Logged | LogOutcome::Warned { .. }) {
+                    imported_count += 1;
+                }
             }
         }
 
@@ -235,20 +455,46 @@ impl HistoryManager {
         Ok(entries)
     }
 
+    /// Get all history entries, oldest first, including archived ones
+    #[must_use = "Query results should be used"]
+    pub fn get_entries_with_archive(&self) -> Result<Vec<HistoryEntry>> {
+        let mut entries = self.get_archived_entries()?;
+        entries.extend(self.get_entries()?);
+        Ok(entries)
+    }
+
     /// Search history entries
     #[must_use = "Search results should be used"]
     pub fn search(&self, query: &str, directory_filter: Option<&str>) -> Result<Vec<HistoryEntry>> {
-        let entries = self.get_entries()?;
+        Ok(self.search_entries(self.get_entries()?, query, directory_filter))
+    }
+
+    /// Search history entries, including archived ones
+    #[must_use = "Search results should be used"]
+    pub fn search_with_archive(
+        &self,
+        query: &str,
+        directory_filter: Option<&str>,
+    ) -> Result<Vec<HistoryEntry>> {
+        Ok(self.search_entries(self.get_entries_with_archive()?, query, directory_filter))
+    }
+
+    fn search_entries(
+        &self,
+        entries: Vec<HistoryEntry>,
+        query: &str,
+        directory_filter: Option<&str>,
+    ) -> Vec<HistoryEntry> {
         let mut results = Vec::new();
 
         let query_lower = query.to_lowercase();
 
         for entry in entries {
             // Apply directory filter if specified
-            if let Some(dir_filter) = directory_filter {
-                if !entry.directory.contains(dir_filter) {
-                    continue;
-                }
+            if let Some(dir_filter) = directory_filter
+                && !entry.directory.contains(dir_filter)
+            {
+                continue;
             }
 
             // Check if command matches query
@@ -268,7 +514,7 @@ impl HistoryManager {
             }
         }
 
-        Ok(results)
+        results
     }
 
     /// Get unique commands for fuzzy search
@@ -293,42 +539,434 @@ impl HistoryManager {
         Ok(&self.stats)
     }
 
-    /// Clear all history
+    /// Move all history to the trash
     pub fn clear(&mut self) -> Result<()> {
+        let entries = self.get_entries()?;
+        self.trash_entries(&entries)?;
         std::fs::write(&self.history_file, "")?;
+        if self.events_file.exists() {
+            std::fs::remove_file(&self.events_file)?;
+        }
         self.stats = HistoryStats::default();
         Ok(())
     }
 
-    /// Trim history to max entries
+    /// Delete all history, including the trash and the archive, for good
+    pub fn purge(&mut self) -> Result<()> {
+        std::fs::write(&self.history_file, "")?;
+        if self.events_file.exists() {
+            std::fs::remove_file(&self.events_file)?;
+        }
+        self.empty_trash(None)?;
+        self.archive.remove_all()?;
+        if self.restored_file.exists() {
+            std::fs::remove_file(&self.restored_file)?;
+        }
+        self.stats = HistoryStats::default();
+        Ok(())
+    }
+
+    /// Append entries to the trash
+    fn trash_entries(&self, entries: &[HistoryEntry]) -> Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let deleted_at = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.trash_file)?;
+        for entry in entries {
+            writeln!(file, "{} | {}", deleted_at, self.format_entry(entry))?;
+        }
+        Ok(())
+    }
+
+    /// Entries in the trash with when they were deleted, in the order they
+    /// were deleted
+    pub fn get_trash(&self) -> Result<Vec<(DateTime<Utc>, HistoryEntry)>> {
+        Ok(self
+            .read_trash()?
+            .into_iter()
+            .map(|(_, deleted_at, entry)| (deleted_at, entry))
+            .collect())
+    }
+
+    /// Entries in the trash with their IDs, in the order they were deleted
+    ///
+    /// An entry's ID comes from a hash of its line in the trash, so it stays
+    /// the same when other entries are restored or expired.
+    fn read_trash(&self) -> Result<Vec<(i64, DateTime<Utc>, HistoryEntry)>> {
+        if !self.trash_file.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut trash = Vec::new();
+        for raw in std::fs::read_to_string(&self.trash_file)?.lines() {
+            let Some((deleted_at, line)) = raw.split_once(" | ") else {
+                continue;
+            };
+            let deleted_at = chrono::NaiveDateTime::parse_from_str(deleted_at, "%Y-%m-%d %H:%M:%S")
+                .map_err(|_| Error::InvalidTimestamp {
+                    timestamp: deleted_at.to_string(),
+                })?
+                .and_utc();
+            if let Some(mut entry) = self.parse_entry(line)? {
+                entry.deleted = false;
+                trash.push((trash_id(raw), deleted_at, entry));
+            }
+        }
+        Ok(trash)
+    }
+
+    /// Put the entry with the ID `id` in the trash back into the history;
+    /// false if there is no such entry, or if the ID is ambiguous
+    pub fn restore_from_trash(&mut self, id: i64) -> Result<bool> {
+        let trash = self.read_trash()?;
+        let Some(position) = trash.iter().position(|(entry_id, _, _)| *entry_id == id) else {
+            return Ok(false);
+        };
+        // Identical lines restore the same command; different ones sharing an
+        // ID cannot be told apart
+        let (_, first_deleted_at, first) = &trash[position];
+        if trash[position + 1..]
+            .iter()
+            .any(|(entry_id, deleted_at, entry)| {
+                *entry_id == id && (deleted_at, entry) != (first_deleted_at, first)
+            })
+        {
+            return Ok(false);
+        }
+
+        let mut trash: Vec<_> = trash
+            .into_iter()
+            .map(|(_, deleted_at, entry)| (deleted_at, entry))
+            .collect();
+        let (_, restored) = trash.remove(position);
+
+        // Keep the history in time order
+        let mut entries = self.get_entries()?;
+        let at = entries.partition_point(|entry| entry.timestamp <= restored.timestamp);
+        entries.insert(at, restored);
+        self.write_entries(&entries)?;
+        self.write_trash(&trash)?;
+
+        self.update_stats()?;
+        Ok(true)
+    }
+
+    /// Delete entries from the trash for good, optionally only those deleted
+    /// before `cutoff`, returning how many were removed
+    pub fn empty_trash(&mut self, cutoff: Option<DateTime<Utc>>) -> Result<usize> {
+        let trash = self.get_trash()?;
+        let (removed, kept): (Vec<_>, Vec<_>) = trash
+            .into_iter()
+            .partition(|(deleted_at, _)| cutoff.is_none_or(|cutoff| *deleted_at < cutoff));
+        if !removed.is_empty() {
+            self.write_trash(&kept)?;
+        }
+        Ok(removed.len())
+    }
+
+    /// Delete entries that have been in the trash longer than
+    /// `trash.retention_days`, if it is set
+    fn expire_trash(&mut self) -> Result<usize> {
+        let Some(days) = self.config.trash.retention_days else {
+            return Ok(0);
+        };
+        let cutoff = Utc::now() - chrono::Duration::days(i64::from(days));
+        self.empty_trash(Some(cutoff))
+    }
+
+    /// Replace the trash, zeroing the old contents so removed entries do not
+    /// linger in the file's blocks
+    fn write_trash(&self, trash: &[(DateTime<Utc>, HistoryEntry)]) -> Result<()> {
+        let mut content = String::new();
+        for (deleted_at, entry) in trash {
+            content.push_str(&format!(
+                "{} | {}\n",
+                deleted_at.format("%Y-%m-%d %H:%M:%S"),
+                self.format_entry(entry)
+            ));
+        }
+
+        secure_file::replace(&self.trash_file, content.as_bytes())
+    }
+
+    /// Entries expired by the retention policies, with their positions
+    ///
+    /// The file backend records neither tokens nor exit codes, so policies
+    /// that need them never match here.
+    fn expired_entries(&self) -> Result<Vec<(usize, Expired)>> {
+        if self.retention.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let now = Utc::now();
+        let mut expired = Vec::new();
+        for (idx, entry) in self.get_entries()?.into_iter().enumerate() {
+            let subject = RetentionSubject {
+                command: &entry.command,
+                timestamp: entry.timestamp,
+                has_tokens: false,
+                exit_code: None,
+            };
+            if let Some(policy) = self.retention.expired_by(&subject, now) {
+                let policy = policy.to_string();
+                expired.push((
+                    idx,
+                    Expired {
+                        command: entry.command,
+                        timestamp: entry.timestamp,
+                        policy,
+                    },
+                ));
+            }
+        }
+        Ok(expired)
+    }
+
+    /// Rewrite the history file with `entries`
+    fn write_entries(&self, entries: &[HistoryEntry]) -> Result<()> {
+        let mut content = String::new();
+        for entry in entries {
+            let deleted_marker = if entry.deleted { "[DELETED] " } else { "" };
+            content.push_str(&format!(
+                "{} | {} | {}{}\n",
+                timezone::format_logged(entry.timestamp, entry.utc_offset),
+                entry.directory,
+                deleted_marker,
+                entry.command
+            ));
+        }
+        secure_file::replace(&self.history_file, content.as_bytes())
+    }
+
+    /// Remove every entry whose command matches `pattern` from the file
+    ///
+    /// Entries marked `[DELETED]`, entries in the trash and archived entries
+    /// are matched too.
+    /// The remaining lines replace the file and the old contents are zeroed,
+    /// so the removed text is not left in the file's old blocks.
+    pub fn shred_matching(&mut self, pattern: &regex::Regex) -> Result<ShredReport> {
+        let content = std::fs::read_to_string(&self.history_file)?;
+        let mut kept = String::with_capacity(content.len());
+        let mut removed = Vec::new();
+
+        for line in content.lines() {
+            match self.parse_entry(line)? {
+                Some(entry) if pattern.is_match(&entry.command) => removed.push(entry.command),
+                _ => {
+                    kept.push_str(line);
+                    kept.push('\n');
+                }
+            }
+        }
+
+        let (trashed, kept_trash): (Vec<_>, Vec<_>) = self
+            .get_trash()?
+            .into_iter()
+            .partition(|(_, entry)| pattern.is_match(&entry.command));
+        if !trashed.is_empty() {
+            self.write_trash(&kept_trash)?;
+        }
+        let trash_content = if self.trash_file.exists() {
+            std::fs::read_to_string(&self.trash_file)?
+        } else {
+            String::new()
+        };
+        removed.extend(trashed.into_iter().map(|(_, entry)| entry.command));
+
+        for name in self.archive.segments()? {
+            let mut kept_lines = Vec::new();
+            let mut shredded = 0;
+            for line in self.archive.read(&name)? {
+                match self.parse_entry(&line)? {
+                    Some(entry) if pattern.is_match(&entry.command) => {
+                        removed.push(entry.command);
+                        shredded += 1;
+                    }
+                    _ => kept_lines.push(line),
+                }
+            }
+            if shredded > 0 {
+                self.archive.rewrite(&name, &kept_lines)?;
+            }
+        }
+
+        let mut report = ShredReport {
+            commands: removed.len(),
+            ..Default::default()
+        };
+        if removed.is_empty() {
+            return Ok(report);
+        }
+
+        secure_file::replace(&self.history_file, kept.as_bytes())?;
+
+        let written = std::fs::read_to_string(&self.history_file)?;
+        report.remnants = removed
+            .iter()
+            .filter(|command| {
+                written.contains(command.as_str()) || trash_content.contains(command.as_str())
+            })
+            .count();
+
+        self.update_stats()?;
+        Ok(report)
+    }
+
+    /// Trim history to max entries, moving the oldest entries to the
+    /// archive unless archiving is disabled
+    ///
+    /// Entries restored from the archive neither count against the limit
+    /// nor are rotated, or the next command logged would archive them again.
     fn trim_history(&mut self) -> Result<()> {
         let entries = self.get_entries()?;
-        let keep_count = self.config.max_entries;
-
-        if entries.len() <= keep_count {
+        let max_entries = self.config.max_entries;
+
+        let mut restored = self.restored_hashes()?;
+        let is_restored: Vec<bool> = entries
+            .iter()
+            .map(|entry| restored.contains(&line_hash(&self.format_entry(entry))))
+            .collect();
+        let counted = is_restored.iter().filter(|&&r| !r).count();
+        if counted <= max_entries {
             return Ok(());
         }
 
-        // Keep the most recent entries
-        let entries_to_keep = &entries[entries.len() - keep_count..];
+        let keep_count = if self.config.archive.enabled {
+            max_entries - max_entries / ARCHIVE_BATCH_DIVISOR
+        } else {
+            max_entries
+        };
 
-        // Rewrite the file with only the entries we want to keep
-        let file = OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .open(&self.history_file)?;
-        let mut writer = BufWriter::new(file);
+        // The oldest entries that were not restored are rotated
+        let mut to_rotate = counted - keep_count;
+        let mut rotated = Vec::new();
+        let mut kept = Vec::new();
+        for (entry, restored) in entries.into_iter().zip(is_restored) {
+            if !restored && to_rotate > 0 {
+                to_rotate -= 1;
+                rotated.push(entry);
+            } else {
+                kept.push(entry);
+            }
+        }
 
-        for entry in entries_to_keep {
-            writeln!(writer, "{}", self.format_entry(entry))?;
+        if self.config.archive.enabled {
+            let lines: Vec<String> = rotated
+                .iter()
+                .map(|entry| self.format_entry(entry))
+                .collect();
+            self.archive
+                .append(&Archive::segment_name(Utc::now().date_naive()), &lines)?;
         }
 
-        writer.flush()?;
+        // Rewrite the file with only the entries we want to keep
+        self.write_entries(&kept)?;
+
+        // Forget restored entries that have since been deleted
+        let present: HashSet<String> = kept
+            .iter()
+            .map(|entry| line_hash(&self.format_entry(entry)))
+            .collect();
+        restored.retain(|hash| present.contains(hash));
+        self.write_restored_hashes(&restored)?;
+
         self.update_stats()?;
 
         Ok(())
     }
 
+    /// Hashes of the history lines restored from the archive
+    fn restored_hashes(&self) -> Result<HashSet<String>> {
+        if !self.restored_file.exists() {
+            return Ok(HashSet::new());
+        }
+        Ok(std::fs::read_to_string(&self.restored_file)?
+            .lines()
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Replace the hashes of the restored history lines
+    fn write_restored_hashes(&self, hashes: &HashSet<String>) -> Result<()> {
+        if hashes.is_empty() {
+            if self.restored_file.exists() {
+                std::fs::remove_file(&self.restored_file)?;
+            }
+            return Ok(());
+        }
+
+        let mut content: Vec<&str> = hashes.iter().map(String::as_str).collect();
+        content.sort_unstable();
+        std::fs::write(&self.restored_file, content.join("\n") + "\n")?;
+        Ok(())
+    }
+
+    /// Archived entries, oldest segment first
+    pub fn get_archived_entries(&self) -> Result<Vec<HistoryEntry>> {
+        let mut entries = Vec::new();
+        for name in self.archive.segments()? {
+            for line in self.archive.read(&name)? {
+                if let Some(entry) = self.parse_entry(&line)? {
+                    entries.push(entry);
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Summaries of the archive segments, oldest first
+    pub fn list_archive(&self) -> Result<Vec<ArchiveSegment>> {
+        let mut segments = Vec::new();
+        for name in self.archive.segments()? {
+            let mut entries = Vec::new();
+            for line in self.archive.read(&name)? {
+                if let Some(entry) = self.parse_entry(&line)? {
+                    entries.push(entry);
+                }
+            }
+            segments.push(ArchiveSegment {
+                size: self.archive.size(&name)?,
+                entries: entries.len(),
+                first: entries.iter().map(|entry| entry.timestamp).min(),
+                last: entries.iter().map(|entry| entry.timestamp).max(),
+                name,
+            });
+        }
+        Ok(segments)
+    }
+
+    /// Move the entries of an archive segment back into the history,
+    /// returning how many were restored
+    ///
+    /// Restored entries are kept however long the history grows.
+    pub fn restore_archive(&mut self, name: &str) -> Result<usize> {
+        let mut restored = Vec::new();
+        let mut hashes = self.restored_hashes()?;
+        for line in self.archive.read(name)? {
+            if let Some(entry) = self.parse_entry(&line)? {
+                hashes.insert(line_hash(&self.format_entry(&entry)));
+                restored.push(entry);
+            }
+        }
+
+        // Keep the history in time order
+        let mut entries = self.get_entries()?;
+        let count = restored.len();
+        entries.extend(restored);
+        entries.sort_by_key(|entry| entry.timestamp);
+        self.write_entries(&entries)?;
+        self.write_restored_hashes(&hashes)?;
+        self.archive.rewrite(name, &[])?;
+
+        self.update_stats()?;
+        Ok(count)
+    }
+
     /// Write a single entry to the history file
     fn write_entry(&self, entry: &HistoryEntry) -> Result<()> {
         let mut file = OpenOptions::new()
@@ -342,7 +980,7 @@ impl HistoryManager {
 
     /// Format an entry for writing to file
     fn format_entry(&self, entry: &HistoryEntry) -> String {
-        let timestamp_str = entry.timestamp.format("%Y-%m-%d %H:%M:%S").to_string();
+        let timestamp_str = timezone::format_logged(entry.timestamp, entry.utc_offset);
         format!(
             "{} | {} | {}",
             timestamp_str, entry.directory, entry.command
@@ -361,11 +999,10 @@ impl HistoryManager {
         let mut command = parts[2].to_string();
 
         // Parse timestamp
-        let timestamp = chrono::NaiveDateTime::parse_from_str(timestamp_str, "%Y-%m-%d %H:%M:%S")
-            .map_err(|_| Error::InvalidTimestamp {
+        let (timestamp, utc_offset) =
+            timezone::parse_logged(timestamp_str).ok_or_else(|| Error::InvalidTimestamp {
                 timestamp: timestamp_str.to_string(),
-            })?
-            .and_utc();
+            })?;
 
         // Detect if the command was deleted
         let was_deleted = command.starts_with("[DELETED] ");
@@ -373,8 +1010,8 @@ impl HistoryManager {
             command = command.trim_start_matches("[DELETED] ").to_string();
         }
 
-        // Detect if the command was redacted by checking for redaction markers
-        let was_redacted = command.contains("<redacted>");
+        // Detect if the command was redacted by checking for placeholders
+        let was_redacted = self.redaction_engine.contains_placeholder(&command);
 
         Ok(Some(HistoryEntry {
             command,
@@ -383,6 +1020,10 @@ impl HistoryManager {
             redacted: was_redacted,
             deleted: was_deleted,
             original: None,
+            host: None,
+            session: None,
+            exit_code: None,
+            utc_offset,
         }))
     }
 
@@ -421,6 +1062,10 @@ impl HistoryManager {
                 redacted: was_redacted,
             deleted: false,
                 original: None,
+                host: None,
+                session: None,
+                exit_code: None,
+                utc_offset: None,
             }))
         } else {
             Ok(None)
@@ -449,6 +1094,10 @@ impl HistoryManager {
             redacted: was_redacted,
             deleted: false,
             original: None,
+            host: None,
+            session: None,
+            exit_code: None,
+            utc_offset: None,
         }))
     }
 
@@ -456,9 +1105,7 @@ impl HistoryManager {
     fn parse_fish_entry(&self, line: &str) -> Result<Option<HistoryEntry>> {
         // Fish format: "- cmd: command\n  when: timestamp\n  paths: [...]"
         // This is a simplified parser for the most common case
-        if line.starts_with("- cmd: ") {
-            let command = &line[7..]; // Remove "- cmd: "
-
+        if let Some(command) = line.strip_prefix("- cmd: ") {
             let (redacted_command, was_redacted) = if self.config.enable_redaction {
                 let original = command.to_string();
                 let redacted = self.redaction_engine.redact(command)?;
@@ -474,6 +1121,10 @@ impl HistoryManager {
                 redacted: was_redacted,
             deleted: false,
                 original: None,
+                host: None,
+                session: None,
+                exit_code: None,
+                utc_offset: None,
             }))
         } else {
             Ok(None)
@@ -490,8 +1141,7 @@ impl HistoryManager {
         // Only check the last 100 entries for performance
         let lines: Vec<String> = reader.lines().collect::<std::result::Result<Vec<_>, _>>()?;
         for line in lines.iter().rev().take(100) {
-            let line = line;
-            if let Some(parsed_entry) = self.parse_entry(&line)? {
+            if let Some(parsed_entry) = self.parse_entry(line)? {
                 recent_commands.push(parsed_entry.command);
             }
         }
@@ -521,6 +1171,14 @@ impl HistoryManager {
         self.stats.redacted_entries = redacted_count;
         self.stats.common_directories = common_directories;
 
+        let mut policy_actions = HashMap::new();
+        for (kind, action, _) in self.read_events()? {
+            if kind == EVENT_POLICY {
+                *policy_actions.entry(action).or_insert(0) += 1;
+            }
+        }
+        self.stats.redaction_stats.policy_actions = policy_actions;
+
         Ok(())
     }
 
@@ -548,6 +1206,10 @@ impl HistoryEntry {
             redacted: false,
             original: None,
             deleted: false,
+            host: None,
+            session: None,
+            exit_code: None,
+            utc_offset: None,
         }
     }
 
@@ -556,9 +1218,9 @@ impl HistoryEntry {
         &self.command
     }
 
-    /// Get formatted timestamp
-    pub fn formatted_timestamp(&self) -> String {
-        self.timestamp.format("%Y-%m-%d %H:%M:%S").to_string()
+    /// Get the timestamp formatted for display in `timezone`
+    pub fn formatted_timestamp(&self, timezone: &DisplayTimezone) -> String {
+        timezone.format(self.timestamp, self.utc_offset)
     }
 
     /// Get relative directory (basename)
@@ -581,6 +1243,10 @@ impl From<crate::database::CommandEntry> for HistoryEntry {
             redacted: cmd.redacted,
             original: None,
             deleted: false,  // Database entries aren't deleted by default
+            host: None,
+            session: Some(cmd.session_id.into_string()),
+            exit_code: cmd.exit_code,
+            utc_offset: cmd.utc_offset,
         }
     }
 }
@@ -602,7 +1268,7 @@ impl crate::backend::HistoryProvider for HistoryManager {
         self.search(query, None)
     }
 
-    fn log_command(&mut self, command: &str) -> Result<()> {
+    fn log_command(&mut self, command: &str) -> Result<LogOutcome> {
         self.log_command(command)
     }
 
@@ -610,43 +1276,87 @@ impl crate::backend::HistoryProvider for HistoryManager {
         self.clear()
     }
 
+    fn purge(&mut self) -> Result<()> {
+        self.purge()
+    }
+
     fn delete_entries(&mut self, indices: &[usize]) -> Result<usize> {
         if indices.is_empty() {
             return Ok(0);
         }
 
-        // Read all entries
-        let mut entries = self.get_entries()?;
-        let mut deleted_count = 0;
-
-        // Mark entries as deleted
-        for &idx in indices {
-            if let Some(entry) = entries.get_mut(idx) {
-                if !entry.deleted {
-                    entry.deleted = true;
-                    deleted_count += 1;
-                }
-            }
+        let selected: HashSet<usize> = indices.iter().copied().collect();
+        let (trashed, kept): (Vec<_>, Vec<_>) = self
+            .get_entries()?
+            .into_iter()
+            .enumerate()
+            .partition(|(idx, _)| selected.contains(idx));
+        let trashed: Vec<HistoryEntry> = trashed
+            .into_iter()
+            .map(|(_, mut entry)| {
+                entry.deleted = false;
+                entry
+            })
+            .collect();
+        let kept: Vec<HistoryEntry> = kept.into_iter().map(|(_, entry)| entry).collect();
+
+        self.trash_entries(&trashed)?;
+        self.write_entries(&kept)?;
+        self.update_stats()?;
+
+        Ok(trashed.len())
+    }
+
+    fn purge_entries(&mut self, indices: &[usize]) -> Result<usize> {
+        if indices.is_empty() {
+            return Ok(0);
         }
 
-        // Rewrite the history file with deleted markers
-        let file = File::create(&self.history_file)?;
-        let mut writer = BufWriter::new(file);
+        let selected: HashSet<usize> = indices.iter().copied().collect();
+        let entries = self.get_entries()?;
+        let before = entries.len();
+        let kept: Vec<HistoryEntry> = entries
+            .into_iter()
+            .enumerate()
+            .filter(|(idx, _)| !selected.contains(idx))
+            .map(|(_, entry)| entry)
+            .collect();
+        self.write_entries(&kept)?;
+        self.update_stats()?;
+
+        Ok(before - kept.len())
+    }
 
-        for entry in &entries {
-            let deleted_marker = if entry.deleted { "[DELETED] " } else { "" };
-            let line = format!(
-                "{} | {} | {}{}\n",
-                entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
-                entry.directory,
-                deleted_marker,
-                entry.command
-            );
-            writer.write_all(line.as_bytes())?;
+    fn apply_retention(&mut self, dry_run: bool) -> Result<Vec<Expired>> {
+        let (indices, expired): (Vec<usize>, Vec<Expired>) =
+            self.expired_entries()?.into_iter().unzip();
+        if !dry_run {
+            self.delete_entries(&indices)?;
         }
-        writer.flush()?;
+        Ok(expired)
+    }
+
+    fn list_trash(&self) -> Result<Vec<TrashEntry>> {
+        let mut trash: Vec<TrashEntry> = self
+            .read_trash()?
+            .into_iter()
+            .map(|(id, deleted_at, entry)| TrashEntry {
+                id,
+                entry,
+                deleted_at,
+                tokens: 0,
+            })
+            .collect();
+        trash.reverse();
+        Ok(trash)
+    }
+
+    fn restore_from_trash(&mut self, id: i64) -> Result<bool> {
+        self.restore_from_trash(id)
+    }
 
-        Ok(deleted_count)
+    fn empty_trash(&mut self) -> Result<usize> {
+        self.empty_trash(None)
     }
 }
 
@@ -658,9 +1368,11 @@ mod tests {
 
     fn test_config() -> Config {
         let temp_file = NamedTempFile::new().unwrap();
-        let mut config = Config::default();
-        config.history_file = temp_file.path().to_path_buf();
-        config.max_entries = 1000;
+        let mut config = Config {
+            history_file: temp_file.path().to_path_buf(),
+            max_entries: 1000,
+            ..Default::default()
+        };
         config.shell_integration.exclude_commands.clear(); // Don't exclude any commands in tests
         config
     }
@@ -698,6 +1410,103 @@ mod tests {
         assert!(!entries[0].command.contains("secret123"));
     }
 
+    #[test]
+    fn test_imports_are_redacted() {
+        let config = test_config();
+        let mut manager = HistoryManager::new(config).unwrap();
+
+        let zsh_history = NamedTempFile::new().unwrap();
+        std::fs::write(
+            zsh_history.path(),
+            ": 1700000000:0;password=secret123\n: 1700000001:0;echo hello\n",
+        )
+        .unwrap();
+        let count = manager
+            .import_from_shell("zsh", Some(zsh_history.path().to_path_buf()), None)
+            .unwrap();
+        assert_eq!(count, 2);
+
+        let entries = manager.get_entries().unwrap();
+        assert!(entries[0].command.contains("<redacted>"));
+        assert!(!entries[0].command.contains("secret123"));
+        assert_eq!(entries[1].command, "echo hello");
+    }
+
+    #[test]
+    fn test_templated_placeholders_mark_entries_redacted() {
+        let mut config = test_config();
+        config.redaction.placeholder_template = Some("<{type}:{hash8}>".to_string());
+        let mut manager = HistoryManager::new(config).unwrap();
+
+        manager.log_command("password=secret123").unwrap();
+        manager.log_command("echo hello").unwrap();
+
+        let entries = manager.get_entries().unwrap();
+        assert!(!entries[0].command.contains("<redacted>"));
+        assert!(entries[0].redacted);
+        assert!(!entries[1].redacted);
+
+        let query = crate::search::SearchQuery::new(String::new()).redacted_only();
+        let redacted = crate::search::SearchEngine::new()
+            .search_with_query(&entries, &query)
+            .unwrap();
+        assert_eq!(redacted.len(), 1);
+        assert_eq!(redacted[0].entry.command, entries[0].command);
+    }
+
+    #[test]
+    fn test_secret_policy_drop() {
+        let mut config = test_config();
+        config
+            .redaction
+            .policies
+            .insert("password".to_string(), SecretPolicy::Drop);
+        let mut manager = HistoryManager::new(config).unwrap();
+
+        let outcome = manager.log_command("password=secret123").unwrap();
+        assert_eq!(
+            outcome,
+            LogOutcome::Dropped {
+                rules: vec!["password".to_string()]
+            }
+        );
+        assert_eq!(
+            manager.log_command("echo hello").unwrap(),
+            LogOutcome::Logged
+        );
+
+        let entries = manager.get_entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            manager
+                .get_stats()
+                .unwrap()
+                .redaction_stats
+                .policy_actions
+                .get("drop"),
+            Some(&1)
+        );
+    }
+
+    #[test]
+    fn test_rule_hits_are_persisted() {
+        let config = test_config();
+        let mut manager = HistoryManager::new(config.clone()).unwrap();
+        manager.log_command("password=secret123").unwrap();
+        manager.log_command("echo hello").unwrap();
+        manager
+            .log_command("curl -u admin --password=secret456")
+            .unwrap();
+
+        // A fresh manager reads the hits back from disk
+        let manager = HistoryManager::new(config).unwrap();
+        let hits = manager.get_rule_hits().unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].rule_id, "password");
+        assert_eq!(hits[0].total, 2);
+        assert_eq!(hits[0].last_7_days, 2);
+    }
+
     #[test]
     fn test_duplicate_filtering() {
         let mut config = test_config();
@@ -771,10 +1580,83 @@ mod tests {
         assert_eq!(manager.get_entries().unwrap().len(), 0);
     }
 
+    #[test]
+    fn test_trash_and_restore() {
+        use crate::backend::HistoryProvider;
+
+        let config = test_config();
+        let mut manager = HistoryManager::new(config).unwrap();
+
+        let start = Utc::now() - chrono::Duration::minutes(10);
+        for (minute, command) in ["echo one", "echo two", "echo three"].iter().enumerate() {
+            let timestamp = start + chrono::Duration::minutes(minute as i64);
+            manager
+                .log_command_with_timestamp(command, Some(timestamp))
+                .unwrap();
+        }
+
+        assert_eq!(
+            HistoryProvider::delete_entries(&mut manager, &[1]).unwrap(),
+            1
+        );
+        let commands: Vec<String> = manager
+            .get_entries()
+            .unwrap()
+            .into_iter()
+            .map(|e| e.command)
+            .collect();
+        assert_eq!(commands, ["echo one", "echo three"]);
+
+        let trash = manager.list_trash().unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].entry.command, "echo two");
+        assert!(HistoryProvider::restore_from_trash(&mut manager, trash[0].id).unwrap());
+        let commands: Vec<String> = manager
+            .get_entries()
+            .unwrap()
+            .into_iter()
+            .map(|e| e.command)
+            .collect();
+        assert_eq!(commands, ["echo one", "echo two", "echo three"]);
+
+        // Clearing keeps everything restorable; purging does not
+        manager.clear().unwrap();
+        let trash = manager.list_trash().unwrap();
+        assert_eq!(trash.len(), 3);
+
+        // IDs from one listing stay valid after other entries are restored
+        let id_of = |command: &str| {
+            trash
+                .iter()
+                .find(|item| item.entry.command == command)
+                .unwrap()
+                .id
+        };
+        let (one, three) = (id_of("echo one"), id_of("echo three"));
+        assert!(HistoryProvider::restore_from_trash(&mut manager, one).unwrap());
+        assert!(HistoryProvider::restore_from_trash(&mut manager, three).unwrap());
+        let commands: Vec<String> = manager
+            .get_entries()
+            .unwrap()
+            .into_iter()
+            .map(|e| e.command)
+            .collect();
+        assert_eq!(commands, ["echo one", "echo three"]);
+        assert!(!HistoryProvider::restore_from_trash(&mut manager, one).unwrap());
+
+        manager.clear().unwrap();
+        assert_eq!(manager.list_trash().unwrap().len(), 3);
+        assert_eq!(manager.empty_trash(None).unwrap(), 3);
+        assert!(manager.list_trash().unwrap().is_empty());
+        assert!(!HistoryProvider::restore_from_trash(&mut manager, 1).unwrap());
+    }
+
     #[test]
     fn test_trim_history() {
         let mut config = test_config();
         config.max_entries = 2;
+        // Without archiving, trimmed entries are dropped
+        config.archive.enabled = false;
         let mut manager = HistoryManager::new(config).unwrap();
 
         manager.log_command("command1").unwrap();
@@ -786,4 +1668,89 @@ mod tests {
         assert_eq!(entries[0].command, "command2");
         assert_eq!(entries[1].command, "command3");
     }
+
+    #[test]
+    fn test_archive_rotation_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config();
+        config.history_file = dir.path().join("history.mhist");
+        config.max_entries = 10;
+        let mut manager = HistoryManager::new(config).unwrap();
+
+        let start = Utc::now() - chrono::Duration::days(1);
+        for i in 0..11 {
+            manager
+                .log_command_with_timestamp(
+                    &format!("command{}", i),
+                    Some(start + chrono::Duration::minutes(i)),
+                )
+                .unwrap();
+        }
+
+        // A tenth of the limit goes to the archive in one go
+        let entries = manager.get_entries().unwrap();
+        assert_eq!(entries.len(), 9);
+        assert_eq!(entries[0].command, "command2");
+        assert_eq!(manager.get_entries_with_archive().unwrap().len(), 11);
+        assert!(manager.search("command0", None).unwrap().is_empty());
+        assert_eq!(
+            manager.search_with_archive("command0", None).unwrap().len(),
+            1
+        );
+
+        let segments = manager.list_archive().unwrap();
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].entries, 2);
+        assert_eq!(
+            segments[0].first,
+            Some(entries[0].timestamp - chrono::Duration::minutes(2))
+        );
+
+        let pattern = regex::Regex::new("^command0$").unwrap();
+        assert_eq!(manager.shred_matching(&pattern).unwrap().commands, 1);
+
+        assert_eq!(manager.restore_archive(&segments[0].name).unwrap(), 1);
+        assert!(manager.list_archive().unwrap().is_empty());
+        let entries = manager.get_entries().unwrap();
+        assert_eq!(entries.len(), 10);
+        assert_eq!(entries[0].command, "command1");
+
+        // Restored entries survive later logging; only newer ones rotate
+        manager.log_command("command11").unwrap();
+        assert!(manager.list_archive().unwrap().is_empty());
+        manager.log_command("command12").unwrap();
+        let entries = manager.get_entries().unwrap();
+        assert_eq!(entries.len(), 10);
+        assert_eq!(entries[0].command, "command1");
+        assert_eq!(entries[1].command, "command4");
+        let archived: Vec<String> = manager
+            .get_archived_entries()
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.command)
+            .collect();
+        assert_eq!(archived, vec!["command2", "command3"]);
+    }
+
+    #[test]
+    fn test_shred_matching() {
+        let config = test_config();
+        let history_file = config.history_file.clone();
+        let mut manager = HistoryManager::new(config).unwrap();
+
+        manager.log_command("ssh deploy@prodbox-7").unwrap();
+        manager.log_command("echo hello").unwrap();
+        manager.log_command("scp notes deploy@prodbox-7:").unwrap();
+        crate::backend::HistoryProvider::delete_entries(&mut manager, &[2]).unwrap();
+
+        let pattern = regex::Regex::new("prodbox").unwrap();
+        let report = manager.shred_matching(&pattern).unwrap();
+        assert_eq!(report.commands, 2);
+        assert_eq!(report.remnants, 0);
+
+        let content = std::fs::read_to_string(&history_file).unwrap();
+        assert!(!content.contains("prodbox"));
+        assert!(!content.contains('\0'));
+        assert_eq!(manager.get_entries().unwrap().len(), 1);
+    }
 }
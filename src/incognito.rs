@@ -0,0 +1,103 @@
+//! Incognito mode for Mortimer
+//!
+//! Pauses logging for one shell session. The shell integration gives every
+//! interactive shell an id in [`SESSION_ENV`]; turning incognito on leaves a
+//! marker file named after that id, and `mortimer log` discards commands
+//! while the marker exists. Other shells keep logging.
+
+use crate::config::Config;
+use crate::error::{Error, Result};
+use std::fs;
+use std::path::PathBuf;
+
+/// Environment variable the shell integration sets to identify a session
+pub const SESSION_ENV: &str = "MORTIMER_SESSION";
+
+/// Incognito markers for the shell sessions of one history
+#[derive(Debug, Clone)]
+pub struct Incognito {
+    dir: PathBuf,
+}
+
+impl Incognito {
+    /// Markers for the history of `config`, kept next to the history file
+    pub fn from_config(config: &Config) -> Self {
+        let mut dir = config.history_file.clone().into_os_string();
+        dir.push(".incognito");
+        Self {
+            dir: PathBuf::from(dir),
+        }
+    }
+
+    /// Directory holding one marker file per incognito session
+    pub fn dir(&self) -> &PathBuf {
+        &self.dir
+    }
+
+    /// The session id of the calling shell, if the shell integration is loaded
+    pub fn current_session() -> Option<String> {
+        std::env::var(SESSION_ENV).ok().filter(|id| !id.is_empty())
+    }
+
+    /// Whether `session` is incognito
+    pub fn is_active(&self, session: &str) -> bool {
+        valid_session(session) && self.dir.join(session).exists()
+    }
+
+    /// Turn incognito on or off for `session`
+    pub fn set(&self, session: &str, active: bool) -> Result<()> {
+        if !valid_session(session) {
+            return Err(Error::invalid_arguments(format!(
+                "invalid session id '{}' in {}",
+                session, SESSION_ENV
+            )));
+        }
+
+        let marker = self.dir.join(session);
+        if active {
+            fs::create_dir_all(&self.dir)?;
+            fs::write(&marker, chrono::Utc::now().to_rfc3339())?;
+        } else if marker.exists() {
+            fs::remove_file(&marker)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Session ids become file names, so only allow plain names
+fn valid_session(session: &str) -> bool {
+    !session.is_empty()
+        && !session.starts_with('.')
+        && session
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_incognito_is_per_session() {
+        let dir = tempdir().unwrap();
+        let config = Config {
+            history_file: dir.path().join("history.mhist"),
+            ..Default::default()
+        };
+        let incognito = Incognito::from_config(&config);
+
+        assert!(!incognito.is_active("1234-5678"));
+        incognito.set("1234-5678", true).unwrap();
+        assert!(incognito.is_active("1234-5678"));
+        assert!(!incognito.is_active("4321-8765"));
+
+        incognito.set("1234-5678", false).unwrap();
+        assert!(!incognito.is_active("1234-5678"));
+        incognito.set("1234-5678", false).unwrap();
+
+        assert!(incognito.set("../escape", true).is_err());
+        assert!(!incognito.is_active(".."));
+    }
+}
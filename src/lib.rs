@@ -19,17 +19,32 @@
 
 use std::path::PathBuf;
 
+pub mod archive;
 pub mod backend;
 pub mod cli;
 pub mod config;
 pub mod database;
+pub mod dates;
+pub mod detector;
 pub mod error;
+pub mod exclusion;
+pub mod folding;
+pub mod fuzzy;
 pub mod history;
 pub mod history_db;
+pub mod incognito;
 pub mod manage_tui;
+pub mod placeholder;
 pub mod prelude;
+pub mod query;
 pub mod redaction;
+pub mod redaction_corpus;
+pub mod retention;
 pub mod search;
+pub mod secure_file;
+pub mod shell_history;
+pub mod timezone;
+pub mod token_bundle;
 pub mod types;
 
 pub use config::Config;
@@ -8,6 +8,8 @@
 
 use crate::error::Result;
 use crate::history::HistoryEntry;
+use crate::query::QueryContext;
+use crate::search::{SearchEngine, SearchQuery};
 use crossterm::{
     event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
     execute,
@@ -15,7 +17,7 @@ use crossterm::{
 };
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout, Rect},
+    layout::{Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
     text::{Line, Span},
     widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
@@ -46,6 +48,12 @@ pub struct ManagementUI {
     filter: String,
     /// Filtered indices
     filtered_indices: Vec<usize>,
+    /// What the filter query is parsed against
+    query_context: QueryContext,
+    /// Matches the filter query, with the configured Unicode folding
+    search_engine: SearchEngine,
+    /// Why the filter could not be parsed, if it could not
+    filter_error: Option<String>,
     /// Whether UI is running
     running: bool,
     /// Show help panel
@@ -53,7 +61,11 @@ pub struct ManagementUI {
 }
 
 impl ManagementUI {
-    pub fn new(entries: Vec<HistoryEntry>) -> Self {
+    pub fn new(
+        entries: Vec<HistoryEntry>,
+        query_context: QueryContext,
+        search_engine: SearchEngine,
+    ) -> Self {
         let filtered_indices: Vec<usize> = (0..entries.len()).collect();
         let mut ui = Self {
             entries,
@@ -62,6 +74,9 @@ impl ManagementUI {
             list_state: ListState::default(),
             filter: String::new(),
             filtered_indices,
+            query_context,
+            search_engine,
+            filter_error: None,
             running: true,
             show_help: false,
         };
@@ -70,19 +85,27 @@ impl ManagementUI {
     }
 
     /// Update filter and rebuild filtered indices
+    ///
+    /// The filter is a search query; while it does not parse, e.g. halfway
+    /// through typing a quote, the previous matches stay listed.
     fn update_filter(&mut self, filter: String) {
         self.filter = filter;
-        if self.filter.is_empty() {
+        self.filter_error = None;
+        if self.filter.trim().is_empty() {
             self.filtered_indices = (0..self.entries.len()).collect();
         } else {
-            let filter_lower = self.filter.to_lowercase();
-            self.filtered_indices = self
-                .entries
-                .iter()
-                .enumerate()
-                .filter(|(_, e)| e.command.to_lowercase().contains(&filter_lower))
-                .map(|(i, _)| i)
-                .collect();
+            let matches =
+                SearchQuery::parse(&self.filter, &self.query_context).and_then(|mut query| {
+                    query.fuzzy = false;
+                    self.search_engine.matching_indices(&self.entries, &query)
+                });
+            match matches {
+                Ok(indices) => self.filtered_indices = indices,
+                Err(e) => {
+                    self.filter_error = Some(e.to_string());
+                    return;
+                }
+            }
         }
         self.selected = 0;
         self.list_state.select(Some(0));
@@ -136,11 +159,9 @@ impl ManagementUI {
                 // Start search mode - for now just clear filter
                 self.update_filter(String::new());
             }
-            KeyCode::Char(c) if !self.filter.is_empty() || c == '/' => {
-                if c != '/' {
-                    self.filter.push(c);
-                    self.update_filter(self.filter.clone());
-                }
+            KeyCode::Char(c) if !self.filter.is_empty() && c != '/' => {
+                self.filter.push(c);
+                self.update_filter(self.filter.clone());
             }
             KeyCode::Backspace if !self.filter.is_empty() => {
                 self.filter.pop();
@@ -172,7 +193,9 @@ impl ManagementUI {
         };
 
         // Header
-        let title = if !self.filter.is_empty() {
+        let title = if let Some(error) = &self.filter_error {
+            format!("History Manager - Filter: {} ({})", self.filter, error)
+        } else if !self.filter.is_empty() {
             format!("History Manager - Filter: {} ({} matches)", self.filter, self.filtered_indices.len())
         } else {
             format!("History Manager ({} entries, {} marked for deletion)", self.entries.len(), self.to_delete.len())
@@ -188,7 +211,11 @@ impl ManagementUI {
             .iter()
             .map(|&idx| {
                 let entry = &self.entries[idx];
-                let timestamp = entry.timestamp.format("%Y-%m-%d %H:%M");
+                let timestamp = self.query_context.timezone.format_with(
+                    entry.timestamp,
+                    entry.utc_offset,
+                    "%Y-%m-%d %H:%M",
+                );
                 let marked = if self.to_delete.contains(&idx) { "[MARK] " } else { "" };
                 let deleted = if entry.deleted { "[DELETED] " } else { "" };
                 let redacted = if entry.redacted { "[R] " } else { "" };
@@ -237,22 +264,26 @@ impl ManagementUI {
                 .style(Style::default().fg(Color::Yellow))
                 .wrap(Wrap { trim: false });
             frame.render_widget(help, chunks[2]);
-        } else if let Some(&idx) = self.filtered_indices.get(self.selected) {
-            if let Some(entry) = self.entries.get(idx) {
-                let details = format!(
-                    "Command: {}\nDirectory: {}\nTimestamp: {}\nRedacted: {}\nMarked for deletion: {}",
-                    entry.command,
-                    entry.directory,
-                    entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
-                    if entry.redacted { "Yes" } else { "No" },
-                    if self.to_delete.contains(&idx) { "Yes" } else { "No" }
-                );
-                let details_widget = Paragraph::new(details)
-                    .block(Block::default().borders(Borders::ALL).title("Details"))
-                    .style(Style::default().fg(Color::Green))
-                    .wrap(Wrap { trim: false });
-                frame.render_widget(details_widget, chunks[2]);
-            }
+        } else if let Some(&idx) = self.filtered_indices.get(self.selected)
+            && let Some(entry) = self.entries.get(idx)
+        {
+            let details = format!(
+                "Command: {}\nDirectory: {}\nTimestamp: {}\nRedacted: {}\nMarked for deletion: {}",
+                entry.command,
+                entry.directory,
+                entry.formatted_timestamp(&self.query_context.timezone),
+                if entry.redacted { "Yes" } else { "No" },
+                if self.to_delete.contains(&idx) {
+                    "Yes"
+                } else {
+                    "No"
+                }
+            );
+            let details_widget = Paragraph::new(details)
+                .block(Block::default().borders(Borders::ALL).title("Details"))
+                .style(Style::default().fg(Color::Green))
+                .wrap(Wrap { trim: false });
+            frame.render_widget(details_widget, chunks[2]);
         }
     }
 
@@ -262,7 +293,11 @@ impl ManagementUI {
 }
 
 /// Run the management TUI and return indices to delete
-pub fn run_management_ui(entries: Vec<HistoryEntry>) -> Result<Vec<usize>> {
+pub fn run_management_ui(
+    entries: Vec<HistoryEntry>,
+    query_context: QueryContext,
+    search_engine: SearchEngine,
+) -> Result<Vec<usize>> {
     // Setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -270,22 +305,22 @@ pub fn run_management_ui(entries: Vec<HistoryEntry>) -> Result<Vec<usize>> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let mut ui = ManagementUI::new(entries);
+    let mut ui = ManagementUI::new(entries, query_context, search_engine);
 
     // Main event loop
     let result = (|| -> Result<()> {
         while ui.running {
             terminal.draw(|f| ui.render(f))?;
 
-            if event::poll(std::time::Duration::from_millis(100))? {
-                if let Event::Key(key) = event::read()? {
-                    // Check if Enter was pressed
-                    if matches!(key.code, KeyCode::Enter) {
-                        ui.running = false;
-                        break;
-                    }
-                    ui.handle_key(key);
+            if event::poll(std::time::Duration::from_millis(100))?
+                && let Event::Key(key) = event::read()?
+            {
+                // Check if Enter was pressed
+                if matches!(key.code, KeyCode::Enter) {
+                    ui.running = false;
+                    break;
                 }
+                ui.handle_key(key);
             }
         }
         Ok(())
@@ -0,0 +1,305 @@
+//! Correlatable placeholders for Mortimer
+//!
+//! With a placeholder template such as `<{type}:{hash8}>`, each redacted value
+//! is replaced by a keyed hash of the value itself. The same secret always
+//! produces the same placeholder, so commands that used it can be found
+//! without revealing it, while the hash cannot be checked against guesses
+//! without the local key.
+//!
+//! # Template variables
+//!
+//! - `{type}` - the token type: `password`, `api_key`, `secret` or
+//!   `bearer_token`, whichever rule found the value (see [`token_type`])
+//! - `{hash8}` - first 8 hex characters of the keyed hash
+//! - `{hash}` - the full keyed hash (64 hex characters)
+
+use crate::config::Config;
+use crate::error::{Error, Result};
+use hmac::{Hmac, Mac};
+use regex::Regex;
+use sha2::Sha256;
+use std::fs;
+use std::path::Path;
+
+/// Variables that may appear in a placeholder template
+const TEMPLATE_VARIABLES: &[&str] = &["type", "hash8", "hash"];
+
+/// Length in bytes of a generated placeholder key
+const KEY_LENGTH: usize = 32;
+
+/// Renders placeholders from a template and a local secret key
+#[derive(Clone)]
+pub struct PlaceholderTemplate {
+    template: String,
+    key: Vec<u8>,
+    matcher: Regex,
+}
+
+impl std::fmt::Debug for PlaceholderTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Never print the key
+        f.debug_struct("PlaceholderTemplate")
+            .field("template", &self.template)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PlaceholderTemplate {
+    /// Create a template with an explicit key
+    pub fn new(template: &str, key: Vec<u8>) -> Result<Self> {
+        Self::validate(template)?;
+        Ok(Self {
+            template: template.to_string(),
+            key,
+            matcher: Self::build_matcher(template)?,
+        })
+    }
+
+    /// Build the configured template, creating the local key on first use
+    ///
+    /// Returns `None` when no `placeholder_template` is configured.
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        let Some(template) = &config.redaction.placeholder_template else {
+            return Ok(None);
+        };
+
+        let key = load_or_create_key(&config.placeholder_key_path())?;
+        Ok(Some(Self::new(template, key)?))
+    }
+
+    /// Check that a template only uses known variables
+    pub fn validate(template: &str) -> Result<()> {
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let Some(end) = rest[start..].find('}') else {
+                return Err(Error::config_validation(
+                    "redaction.placeholder_template",
+                    "unclosed '{' in template",
+                ));
+            };
+            let name = &rest[start + 1..start + end];
+            if !TEMPLATE_VARIABLES.contains(&name) {
+                return Err(Error::config_validation(
+                    "redaction.placeholder_template".to_string(),
+                    format!(
+                        "unknown variable '{{{}}}', expected one of: {{type}}, {{hash8}}, {{hash}}",
+                        name
+                    ),
+                ));
+            }
+            rest = &rest[start + end + 1..];
+        }
+        Ok(())
+    }
+
+    /// Regex matching any placeholder this template can render
+    fn build_matcher(template: &str) -> Result<Regex> {
+        let mut pattern = regex::escape(template);
+        for (variable, replacement) in [
+            ("type", r"[\w:.-]+?"),
+            ("hash8", "[0-9a-f]{8}"),
+            ("hash", "[0-9a-f]{64}"),
+        ] {
+            pattern = pattern.replace(&regex::escape(&format!("{{{}}}", variable)), replacement);
+        }
+        Ok(Regex::new(&pattern)?)
+    }
+
+    /// Byte spans of placeholders already rendered into the text
+    pub fn find_rendered(&self, text: &str) -> Vec<(usize, usize)> {
+        self.matcher
+            .find_iter(text)
+            .map(|m| (m.start(), m.end()))
+            .collect()
+    }
+
+    /// Render the placeholder for a value found by the given rule or of the
+    /// given token type
+    pub fn render(&self, rule_id: &str, value: &str) -> String {
+        let hash = self.keyed_hash(value);
+        self.template
+            .replace("{type}", token_type(rule_id))
+            .replace("{hash8}", &hash[..8])
+            .replace("{hash}", &hash)
+    }
+
+    /// HMAC-SHA256 of the value under the local key, hex encoded
+    pub fn keyed_hash(&self, value: &str) -> String {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts keys of any length");
+        mac.update(value.as_bytes());
+        to_hex(&mac.finalize().into_bytes())
+    }
+}
+
+/// Token type of the values a redaction rule finds
+///
+/// Redaction rules, token extractors and detectors each name what they find
+/// their own way; placeholders use one vocabulary so the same secret gets the
+/// same placeholder whichever of them found it. Rule ids such as `custom:0`,
+/// `env:GITHUB_TOKEN` or `detector:tenant` are classified by their last part;
+/// anything unrecognised is a `secret`.
+pub fn token_type(rule_id: &str) -> &'static str {
+    let name = rule_id.rsplit(':').next().unwrap_or(rule_id).to_lowercase();
+    let has = |words: &[&str]| words.iter().any(|word| name.contains(word));
+
+    if has(&["bearer", "authorization"]) {
+        "bearer_token"
+    } else if has(&["password", "passwd", "pwd", "credentials"]) {
+        "password"
+    } else if has(&["secret", "private", "priv_key", "pem", "ssh", "cert"]) {
+        "secret"
+    } else if has(&["token", "key", "_pat"]) || name == "pat" {
+        "api_key"
+    } else {
+        "secret"
+    }
+}
+
+/// Read the placeholder key, generating and saving a new one if missing
+pub fn load_or_create_key(path: &Path) -> Result<Vec<u8>> {
+    if path.exists() {
+        let content = fs::read_to_string(path)?;
+        return from_hex(content.trim()).ok_or_else(|| {
+            Error::custom(format!("Invalid placeholder key file: {}", path.display()))
+        });
+    }
+
+    let mut key = vec![0u8; KEY_LENGTH];
+    getrandom::getrandom(&mut key)
+        .map_err(|e| Error::custom(format!("Failed to generate placeholder key: {}", e)))?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_private(path, &to_hex(&key))?;
+
+    Ok(key)
+}
+
+/// Write a file readable only by the current user
+fn write_private(path: &Path, content: &str) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(path)?;
+        file.write_all(content.as_bytes())?;
+    }
+
+    #[cfg(not(unix))]
+    fs::write(path, content)?;
+
+    Ok(())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.is_empty() || !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_render_is_stable_per_value() {
+        let template = PlaceholderTemplate::new("<{type}:{hash8}>", b"key".to_vec()).unwrap();
+
+        let first = template.render("password", "hunter2");
+        assert_eq!(first, template.render("password", "hunter2"));
+        assert_ne!(first, template.render("password", "hunter3"));
+        assert!(first.starts_with("<password:"));
+        assert_eq!(first.len(), "<password:>".len() + 8);
+
+        // A different key gives unrelated placeholders for the same value
+        let other = PlaceholderTemplate::new("<{type}:{hash8}>", b"other".to_vec()).unwrap();
+        assert_ne!(first, other.render("password", "hunter2"));
+    }
+
+    #[test]
+    fn test_rule_ids_share_token_types() {
+        // The engine's rule ids and the extractors' token types agree
+        for (rule_id, token_type) in [
+            ("password", "password"),
+            ("url_credentials", "password"),
+            ("postgresql_credentials", "password"),
+            ("api_key", "api_key"),
+            ("github_pat", "api_key"),
+            ("env:GITHUB_TOKEN", "api_key"),
+            ("aws_secret_access_key", "secret"),
+            ("pem_block", "secret"),
+            ("custom:0", "secret"),
+            ("bearer", "bearer_token"),
+            ("authorization_bearer", "bearer_token"),
+            ("bearer_token", "bearer_token"),
+        ] {
+            assert_eq!(super::token_type(rule_id), token_type, "{}", rule_id);
+        }
+
+        let template = PlaceholderTemplate::new("<{type}:{hash8}>", b"key".to_vec()).unwrap();
+        assert_eq!(
+            template.render("url_credentials", "hunter2"),
+            template.render("password", "hunter2")
+        );
+    }
+
+    #[test]
+    fn test_keyed_hash_matches_hmac_sha256() {
+        // RFC 4231 test case 2
+        let template = PlaceholderTemplate::new("{hash}", b"Jefe".to_vec()).unwrap();
+        assert_eq!(
+            template.render("", "what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_find_rendered() {
+        let template = PlaceholderTemplate::new("<{type}:{hash}>", b"key".to_vec()).unwrap();
+        let placeholder = template.render("custom:0", "hunter2");
+        let text = format!("echo {} <b>", placeholder);
+
+        assert_eq!(
+            template.find_rendered(&text),
+            vec![(5, 5 + placeholder.len())]
+        );
+        assert!(template.find_rendered("echo <b>").is_empty());
+    }
+
+    #[test]
+    fn test_validate_template() {
+        assert!(PlaceholderTemplate::validate("<{type}:{hash8}>").is_ok());
+        assert!(PlaceholderTemplate::validate("<redacted>").is_ok());
+        assert!(PlaceholderTemplate::validate("<{kind}:{hash8}>").is_err());
+        assert!(PlaceholderTemplate::validate("<{type").is_err());
+    }
+
+    #[test]
+    fn test_key_is_created_once() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("test.key");
+
+        let key = load_or_create_key(&path).unwrap();
+        assert_eq!(key.len(), KEY_LENGTH);
+        assert_eq!(load_or_create_key(&path).unwrap(), key);
+
+        fs::write(&path, "not hex").unwrap();
+        assert!(load_or_create_key(&path).is_err());
+    }
+}
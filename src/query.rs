@@ -0,0 +1,411 @@
+//! Search query language for Mortimer
+//!
+//! Parses one line of text into a [`SearchQuery`], so that every place that
+//! takes a search — `mortimer search`, `mortimer fzf` and the interactive
+//! pickers — accepts the same filters:
+//!
+//! - `docker build` — plain words, matched together as the search term
+//! - `"exact phrase"` — text the command must contain as written
+//! - `/regex/` — a regex the command must match
+//! - `-"phrase"` — text the command must not contain; a plain `-word` is
+//!   searched for as written, as commands are full of options like `-la`
+//! - `dir:~/src/api` — working directory contains the path
+//! - `host:ci-runner` — ran on this host
+//! - `session:current` — ran in this shell session, or one given by id
+//! - `after:"2 days ago"`, `before:2025-10-01` — time range
+//! - `date:yesterday`, `date:2025-10-01..2025-10-07` — within a date or range
+//! - `exit:0`, `-exit:0` — exit code is, or is not, the given one
+//! - `+redacted`, `-redacted` — only, or no, redacted commands
+//!
+//! Field values containing spaces are quoted: `after:"2 days ago"`. Words
+//! with an unknown `name:` prefix, such as `git@host:repo`, are plain words.
+//! Dates are read by [`crate::dates`], in the display timezone.
+
+use crate::dates::TimeSpan;
+use crate::error::{Error, Result};
+use crate::search::{ExitCodeFilter, SearchQuery};
+use crate::timezone::DisplayTimezone;
+use chrono::{DateTime, Utc};
+use std::path::PathBuf;
+
+/// What a query is parsed against
+#[derive(Debug, Clone)]
+pub struct QueryContext {
+    /// Time that relative dates count back from
+    pub now: DateTime<Utc>,
+    /// Timezone that dates are read in
+    pub timezone: DisplayTimezone,
+    /// Session that `session:current` refers to, if known
+    pub current_session: Option<String>,
+    /// Directory that `~` expands to
+    pub home: Option<PathBuf>,
+}
+
+impl QueryContext {
+    /// Context for the current process, with the given current session
+    pub fn new(current_session: Option<String>, timezone: DisplayTimezone) -> Self {
+        Self {
+            now: Utc::now(),
+            timezone,
+            current_session,
+            home: home::home_dir(),
+        }
+    }
+}
+
+/// A lexical piece of a query
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Phrase(String),
+    Regex(String),
+    Field(String, String),
+}
+
+/// Fields the language knows; other `name:value` words are plain words
+const FIELDS: &[&str] = &["dir", "host", "session", "after", "before", "date", "exit"];
+
+/// Parse `input` into a search query
+pub fn parse(input: &str, context: &QueryContext) -> Result<SearchQuery> {
+    let mut query = SearchQuery::new(String::new());
+    let mut words = Vec::new();
+    let mut window: Option<TimeSpan> = None;
+
+    for (negated, token) in tokenize(input)? {
+        match token {
+            Token::Word(word) if word == "redacted" && negated => query.exclude_redacted = true,
+            // Options such as `-la` are common in commands, so only phrases
+            // are excluded by a leading `-`
+            Token::Word(word) if negated => words.push(format!("-{}", word)),
+            Token::Phrase(phrase) if negated => query.excluded.push(phrase),
+            Token::Word(word) => words.push(word),
+            Token::Phrase(phrase) => query.phrases.push(phrase),
+            Token::Regex(pattern) => {
+                if negated {
+                    return Err(invalid("a /regex/ cannot be negated"));
+                }
+                regex::Regex::new(&pattern).map_err(|e| {
+                    Error::search_failed(format!("invalid regex /{}/: {}", pattern, e))
+                })?;
+                query.patterns.push(pattern);
+            }
+            Token::Field(name, value) if name == "exit" => {
+                let code = value
+                    .parse::<i32>()
+                    .map_err(|_| invalid(format!("exit: needs a number, got '{}'", value)))?;
+                query.exit_code = Some(if negated {
+                    ExitCodeFilter::IsNot(code)
+                } else {
+                    ExitCodeFilter::Is(code)
+                });
+            }
+            Token::Field(name, _) if negated => {
+                return Err(invalid(format!("{}: cannot be negated", name)));
+            }
+            Token::Field(name, value) => match name.as_str() {
+                "dir" => query.directory = Some(expand_home(&value, context)),
+                "host" => query.host = Some(value),
+                "session" if value == "current" => {
+                    query.session = Some(context.current_session.clone().ok_or_else(|| {
+                        invalid("session:current needs the shell integration, which sets MORTIMER_SESSION")
+                    })?);
+                }
+                "session" => query.session = Some(value),
+                "after" | "before" | "date" => {
+                    let span = parse_date(&name, &value, context)?;
+                    let bound = match name.as_str() {
+                        "after" => TimeSpan {
+                            end: TimeSpan::ALL.end,
+                            ..span
+                        },
+                        "before" => TimeSpan {
+                            start: TimeSpan::ALL.start,
+                            end: span.start,
+                        },
+                        _ => span,
+                    };
+                    window = Some(window.unwrap_or(TimeSpan::ALL).intersect(bound));
+                }
+                _ => unreachable!("unknown field {}", name),
+            },
+        }
+    }
+
+    // `+redacted` arrives as a plain word
+    words.retain(|word| {
+        let only_redacted = word == "+redacted";
+        query.redacted_only |= only_redacted;
+        !only_redacted
+    });
+    if query.redacted_only && query.exclude_redacted {
+        return Err(invalid("+redacted and -redacted cannot be combined"));
+    }
+
+    if let Some(window) = window {
+        if window.start > window.end {
+            return Err(invalid("the time filters leave no time to match"));
+        }
+        query.time_range = Some((window.start, window.end));
+    }
+
+    query.term = words.join(" ");
+    Ok(query)
+}
+
+fn invalid(reason: impl Into<String>) -> Error {
+    Error::search_failed(format!("invalid query: {}", reason.into()))
+}
+
+/// Split a query into tokens, each with whether it was negated with `-`
+fn tokenize(input: &str) -> Result<Vec<(bool, Token)>> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        if chars[pos].is_whitespace() {
+            pos += 1;
+            continue;
+        }
+
+        let negated = chars[pos] == '-' && chars.get(pos + 1).is_some_and(|c| !c.is_whitespace());
+        if negated {
+            pos += 1;
+        }
+
+        let token = match chars[pos] {
+            '"' => {
+                let (phrase, next) = read_quoted(&chars, pos)?;
+                pos = next;
+                if phrase.is_empty() {
+                    return Err(invalid("empty \"\" phrase"));
+                }
+                Token::Phrase(phrase)
+            }
+            '/' => match read_regex(&chars, pos) {
+                Some((pattern, next)) => {
+                    pos = next;
+                    Token::Regex(pattern)
+                }
+                None => {
+                    let (token, next) = read_word(&chars, pos)?;
+                    pos = next;
+                    token
+                }
+            },
+            _ => {
+                let (token, next) = read_word(&chars, pos)?;
+                pos = next;
+                token
+            }
+        };
+        tokens.push((negated, token));
+    }
+
+    Ok(tokens)
+}
+
+/// Read a plain word or a `field:value` starting at `start`, returning it
+/// and the position after it
+fn read_word(chars: &[char], start: usize) -> Result<(Token, usize)> {
+    let mut pos = start;
+    let mut word = String::new();
+    let mut field = None;
+    while pos < chars.len() && !chars[pos].is_whitespace() {
+        if chars[pos] == ':' && field.is_none() && FIELDS.contains(&word.as_str()) {
+            field = Some(std::mem::take(&mut word));
+            pos += 1;
+            if chars.get(pos) == Some(&'"') {
+                let (value, next) = read_quoted(chars, pos)?;
+                word = value;
+                pos = next;
+                break;
+            }
+            continue;
+        }
+        word.push(chars[pos]);
+        pos += 1;
+    }
+
+    let token = match field {
+        Some(name) if word.is_empty() => {
+            return Err(invalid(format!("{}: needs a value", name)));
+        }
+        Some(name) => Token::Field(name, word),
+        None => Token::Word(chars[start..pos].iter().collect()),
+    };
+    Ok((token, pos))
+}
+
+/// Read a `"quoted"` string starting at `start`, with `\"` and `\\` escapes,
+/// returning it and the position after the closing quote
+fn read_quoted(chars: &[char], start: usize) -> Result<(String, usize)> {
+    let mut text = String::new();
+    let mut pos = start + 1;
+    while pos < chars.len() {
+        match chars[pos] {
+            '"' => return Ok((text, pos + 1)),
+            '\\' if matches!(chars.get(pos + 1), Some('"') | Some('\\')) => {
+                text.push(chars[pos + 1]);
+                pos += 2;
+            }
+            c => {
+                text.push(c);
+                pos += 1;
+            }
+        }
+    }
+    Err(invalid("unterminated quote"))
+}
+
+/// Read a `/regex/` starting at `start`; `None` if the word is not one,
+/// e.g. a path such as `/usr/bin/env`
+fn read_regex(chars: &[char], start: usize) -> Option<(String, usize)> {
+    let mut pattern = String::new();
+    let mut pos = start + 1;
+    while pos < chars.len() {
+        match chars[pos] {
+            '\\' if chars.get(pos + 1) == Some(&'/') => {
+                pattern.push('/');
+                pos += 2;
+            }
+            '/' => {
+                let ends_word = chars.get(pos + 1).is_none_or(|c| c.is_whitespace());
+                return (ends_word && !pattern.is_empty()).then_some((pattern, pos + 1));
+            }
+            c => {
+                pattern.push(c);
+                pos += 1;
+            }
+        }
+    }
+    None
+}
+
+/// Expand a leading `~` in a directory filter
+fn expand_home(path: &str, context: &QueryContext) -> String {
+    match (path.strip_prefix('~'), &context.home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            format!("{}{}", home.display(), rest)
+        }
+        _ => path.to_string(),
+    }
+}
+
+/// Parse the date of an `after:`, `before:` or `date:` field
+fn parse_date(name: &str, value: &str, context: &QueryContext) -> Result<TimeSpan> {
+    context
+        .timezone
+        .parse_span(value, context.now)
+        .map_err(|e| match e {
+            Error::InvalidTimestamp { timestamp } => {
+                invalid(format!("{}: cannot read {}", name, timestamp))
+            }
+            e => e,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Local, TimeZone};
+
+    fn context() -> QueryContext {
+        QueryContext {
+            now: Local
+                .with_ymd_and_hms(2025, 10, 10, 12, 0, 0)
+                .unwrap()
+                .with_timezone(&Utc),
+            timezone: DisplayTimezone::Local,
+            current_session: Some("box:1234-5678".to_string()),
+            home: Some(PathBuf::from("/home/me")),
+        }
+    }
+
+    #[test]
+    fn test_parse_full_query() {
+        let context = context();
+        let query = parse(
+            r#"docker build dir:~/src/api host:ci-runner after:"2 days ago" exit:0 session:current -redacted "exact phrase" /v[0-9]+ (x|y)/"#,
+            &context,
+        )
+        .unwrap();
+
+        assert_eq!(query.term, "docker build");
+        assert_eq!(query.directory.as_deref(), Some("/home/me/src/api"));
+        assert_eq!(query.host.as_deref(), Some("ci-runner"));
+        assert_eq!(query.session.as_deref(), Some("box:1234-5678"));
+        assert_eq!(query.exit_code, Some(ExitCodeFilter::Is(0)));
+        assert!(query.exclude_redacted);
+        assert_eq!(query.phrases, ["exact phrase"]);
+        assert_eq!(query.patterns, ["v[0-9]+ (x|y)"]);
+        let (start, end) = query.time_range.unwrap();
+        assert_eq!(start, context.now - Duration::days(2));
+        assert_eq!(end, TimeSpan::ALL.end);
+    }
+
+    #[test]
+    fn test_parse_words_that_look_like_syntax() {
+        let context = context();
+        let query = parse(
+            r#"ssh git@host:repo /usr/bin/env -la -exit:1 -"tmp" +redacted"#,
+            &context,
+        )
+        .unwrap();
+        assert_eq!(query.term, "ssh git@host:repo /usr/bin/env -la");
+        assert_eq!(query.excluded, ["tmp"]);
+        assert_eq!(query.exit_code, Some(ExitCodeFilter::IsNot(1)));
+        assert!(query.redacted_only);
+        assert!(query.patterns.is_empty());
+
+        let query = parse("before:2025-10-01 after:yesterday", &context);
+        assert!(query.is_err());
+        let local = |day, hour, minute| {
+            Local
+                .with_ymd_and_hms(2025, 10, day, hour, minute, 0)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let query = parse("after:2025-10-01T14:00 before:today", &context).unwrap();
+        assert_eq!(query.time_range, Some((local(1, 14, 0), local(10, 0, 0))));
+        let query = parse("date:2025-10-01..2025-10-07 before:yesterday", &context).unwrap();
+        assert_eq!(query.time_range, Some((local(1, 0, 0), local(8, 0, 0))));
+        let query = parse("date:2025-10-01T14:00", &context).unwrap();
+        assert_eq!(query.time_range, Some((local(1, 14, 0), local(1, 14, 1))));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let context = context();
+        for input in [
+            "\"unterminated",
+            "dir:",
+            "exit:zero",
+            "after:someday",
+            "/[unclosed/",
+            "-dir:/tmp",
+            "\"\"",
+        ] {
+            let error = parse(input, &context).unwrap_err();
+            assert!(
+                matches!(error, Error::SearchFailed { .. }),
+                "{}: {}",
+                input,
+                error
+            );
+        }
+
+        let no_session = QueryContext {
+            current_session: None,
+            ..context
+        };
+        assert!(parse("session:current", &no_session).is_err());
+        assert_eq!(
+            parse("session:box:42", &no_session)
+                .unwrap()
+                .session
+                .as_deref(),
+            Some("box:42")
+        );
+    }
+}
@@ -0,0 +1,256 @@
+//! Redaction regression corpus for Mortimer
+//!
+//! A corpus is a plain text file of commands paired with what the redaction
+//! engine is expected to do with them, so a redaction policy can be kept
+//! under test alongside the configuration that defines it.
+//!
+//! # Format
+//!
+//! ```text
+//! # Comments and blank lines are ignored
+//! mysql password=hunter2 => mysql password=<redacted>
+//! redact: curl -H "Authorization: Bearer abc123def456"
+//! keep: git checkout 3f2a9c1
+//! ```
+//!
+//! - `<command> => <expected>` requires the exact redacted output
+//! - `redact: <command>` requires the command to be changed by redaction
+//! - `keep: <command>` requires the command to pass through unchanged
+
+use crate::error::{Error, Result};
+use crate::redaction::RedactionEngine;
+use std::path::{Path, PathBuf};
+
+/// Separator between a command and its expected output
+const EXPECT_SEPARATOR: &str = " => ";
+
+/// What a corpus case expects from the redaction engine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expectation {
+    /// The redacted output must equal this string
+    Exact(String),
+    /// The command must be changed by redaction
+    MustRedact,
+    /// The command must come out unchanged
+    MustNotRedact,
+}
+
+/// A single command from a corpus file
+#[derive(Debug, Clone)]
+pub struct CorpusCase {
+    /// Line number in the corpus file (1-based)
+    pub line: usize,
+    /// The command fed to the engine
+    pub command: String,
+    /// What the engine should produce
+    pub expectation: Expectation,
+}
+
+/// A corpus case whose expectation was not met
+#[derive(Debug, Clone)]
+pub struct CorpusFailure {
+    /// The case that failed
+    pub case: CorpusCase,
+    /// What the engine actually produced
+    pub actual: String,
+    /// Rules that were applied to the command
+    pub rules_applied: Vec<String>,
+}
+
+/// Outcome of running a corpus against an engine
+#[derive(Debug, Clone, Default)]
+pub struct CorpusReport {
+    /// Number of cases evaluated
+    pub total: usize,
+    /// Cases that did not meet their expectation
+    pub failures: Vec<CorpusFailure>,
+}
+
+/// A parsed redaction corpus
+#[derive(Debug, Clone)]
+pub struct RedactionCorpus {
+    /// Where the corpus was loaded from
+    pub path: PathBuf,
+    /// The cases in file order
+    pub cases: Vec<CorpusCase>,
+}
+
+impl RedactionCorpus {
+    /// Load and parse a corpus file
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Err(Error::custom(format!(
+                "Redaction corpus not found: {}",
+                path.display()
+            )));
+        }
+
+        let content = std::fs::read_to_string(path)?;
+        Self::parse(path, &content)
+    }
+
+    /// Parse corpus content
+    pub fn parse(path: &Path, content: &str) -> Result<Self> {
+        let mut cases = Vec::new();
+
+        for (index, raw) in content.lines().enumerate() {
+            let line = index + 1;
+            let trimmed = raw.trim();
+
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            let (command, expectation) = if let Some(command) = trimmed.strip_prefix("redact:") {
+                (command.trim(), Expectation::MustRedact)
+            } else if let Some(command) = trimmed.strip_prefix("keep:") {
+                (command.trim(), Expectation::MustNotRedact)
+            } else if let Some((command, expected)) = trimmed.split_once(EXPECT_SEPARATOR) {
+                (command, Expectation::Exact(expected.to_string()))
+            } else {
+                return Err(Error::invalid_arguments(format!(
+                    "{}:{}: expected '<command> => <expected>', 'redact: <command>' or 'keep: <command>'",
+                    path.display(),
+                    line
+                )));
+            };
+
+            if command.is_empty() {
+                return Err(Error::invalid_arguments(format!(
+                    "{}:{}: empty command",
+                    path.display(),
+                    line
+                )));
+            }
+
+            cases.push(CorpusCase {
+                line,
+                command: command.to_string(),
+                expectation,
+            });
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            cases,
+        })
+    }
+
+    /// Run every case through the engine
+    pub fn run(&self, engine: &RedactionEngine) -> Result<CorpusReport> {
+        let mut report = CorpusReport::default();
+
+        for case in &self.cases {
+            report.total += 1;
+
+            let explanation = engine.explain(&case.command)?;
+            let passed = match &case.expectation {
+                Expectation::Exact(expected) => explanation.output == *expected,
+                Expectation::MustRedact => explanation.output != case.command,
+                Expectation::MustNotRedact => explanation.output == case.command,
+            };
+
+            if !passed {
+                let mut rules_applied: Vec<String> = explanation
+                    .matches
+                    .iter()
+                    .filter(|m| m.suppressed.is_none())
+                    .map(|m| m.rule_id.clone())
+                    .collect();
+                rules_applied.dedup();
+
+                report.failures.push(CorpusFailure {
+                    case: case.clone(),
+                    actual: explanation.output,
+                    rules_applied,
+                });
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+impl CorpusReport {
+    /// Whether every case met its expectation
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl CorpusFailure {
+    /// The output the case expected, when it names one
+    pub fn expected(&self) -> Option<&str> {
+        match &self.case.expectation {
+            Expectation::Exact(expected) => Some(expected),
+            Expectation::MustNotRedact => Some(&self.case.command),
+            Expectation::MustRedact => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> RedactionCorpus {
+        RedactionCorpus::parse(Path::new("corpus.txt"), content).unwrap()
+    }
+
+    #[test]
+    fn test_parse_corpus() {
+        let corpus = parse(
+            "# policy tests\n\
+             \n\
+             mysql password=hunter2 => mysql password=<redacted>\n\
+             redact: export TOKEN=abc\n\
+             keep: git status\n",
+        );
+
+        assert_eq!(corpus.cases.len(), 3);
+        assert_eq!(corpus.cases[0].line, 3);
+        assert_eq!(corpus.cases[0].command, "mysql password=hunter2");
+        assert_eq!(
+            corpus.cases[0].expectation,
+            Expectation::Exact("mysql password=<redacted>".to_string())
+        );
+        assert_eq!(corpus.cases[1].expectation, Expectation::MustRedact);
+        assert_eq!(corpus.cases[2].command, "git status");
+        assert_eq!(corpus.cases[2].expectation, Expectation::MustNotRedact);
+    }
+
+    #[test]
+    fn test_parse_rejects_unannotated_lines() {
+        let result = RedactionCorpus::parse(Path::new("corpus.txt"), "echo hello\n");
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("corpus.txt:1"));
+    }
+
+    #[test]
+    fn test_run_reports_failures() {
+        let engine = RedactionEngine::new().unwrap();
+        let corpus = parse(
+            "mysql password=hunter2 => mysql password=<redacted>\n\
+             redact: echo hello\n\
+             keep: token=abc123\n\
+             keep: git status\n",
+        );
+
+        let report = corpus.run(&engine).unwrap();
+        assert_eq!(report.total, 4);
+        assert!(!report.passed());
+        assert_eq!(report.failures.len(), 2);
+
+        assert_eq!(report.failures[0].case.line, 2);
+        assert_eq!(report.failures[0].expected(), None);
+
+        assert_eq!(report.failures[1].case.line, 3);
+        assert_eq!(report.failures[1].expected(), Some("token=abc123"));
+        assert_eq!(report.failures[1].actual, "token=<redacted>");
+        assert!(
+            report.failures[1]
+                .rules_applied
+                .contains(&"token".to_string())
+        );
+    }
+}
@@ -0,0 +1,249 @@
+//! Retention policies for Mortimer
+//!
+//! Expires old commands by rule, e.g. "delete `ls` and `cd` after 30 days"
+//! or "keep commands with stored tokens for 90 days". Policies come from
+//! `retention.policies` and are tried in order: the first policy whose
+//! conditions match a command decides how long it is kept, and commands no
+//! policy matches are kept until deleted by hand. Expired commands are moved
+//! to the trash.
+//!
+//! `mortimer gc` applies the policies, and the CLI applies them when it
+//! opens the history, at most once a day.
+
+use crate::config::{Config, RetentionPolicyConfig};
+use crate::error::{Error, Result};
+use chrono::{DateTime, Duration, Utc};
+use regex::Regex;
+use std::path::PathBuf;
+
+/// How often the policies are applied when the history is opened
+const APPLY_INTERVAL_HOURS: i64 = 24;
+
+/// A stored command as the policies see it
+#[derive(Debug, Clone, Copy)]
+pub struct RetentionSubject<'a> {
+    pub command: &'a str,
+    pub timestamp: DateTime<Utc>,
+    pub has_tokens: bool,
+    /// `None` when no exit code was recorded
+    pub exit_code: Option<i32>,
+}
+
+/// A command that a policy has expired
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expired {
+    pub command: String,
+    pub timestamp: DateTime<Utc>,
+    /// Name of the policy that expired it
+    pub policy: String,
+}
+
+/// A compiled retention policy
+#[derive(Debug, Clone)]
+struct Policy {
+    name: String,
+    regex: Option<Regex>,
+    has_tokens: bool,
+    failed: bool,
+    max_age: Duration,
+    description: String,
+}
+
+impl Policy {
+    fn matches(&self, subject: &RetentionSubject) -> bool {
+        self.regex
+            .as_ref()
+            .is_none_or(|regex| regex.is_match(subject.command))
+            && (!self.has_tokens || subject.has_tokens)
+            && (!self.failed || subject.exit_code.is_some_and(|code| code != 0))
+    }
+}
+
+/// The retention policies of a configuration
+#[derive(Debug, Clone, Default)]
+pub struct RetentionPolicies {
+    policies: Vec<Policy>,
+}
+
+impl RetentionPolicies {
+    /// Compile the policies in `retention.policies`
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let policies = config
+            .retention
+            .policies
+            .iter()
+            .enumerate()
+            .map(|(index, policy)| compile_policy(index, policy))
+            .collect::<Result<_>>()?;
+        Ok(Self { policies })
+    }
+
+    /// Whether there are no policies, so nothing ever expires
+    pub fn is_empty(&self) -> bool {
+        self.policies.is_empty()
+    }
+
+    /// The name of the policy that has expired `subject` by `now`, if any
+    pub fn expired_by(&self, subject: &RetentionSubject, now: DateTime<Utc>) -> Option<&str> {
+        let policy = self
+            .policies
+            .iter()
+            .find(|policy| policy.matches(subject))?;
+        (subject.timestamp < now - policy.max_age).then_some(policy.name.as_str())
+    }
+
+    /// Name and summary of each policy, in the order they are tried
+    pub fn describe(&self) -> Vec<(String, String)> {
+        self.policies
+            .iter()
+            .map(|policy| (policy.name.clone(), policy.description.clone()))
+            .collect()
+    }
+}
+
+fn compile_policy(index: usize, config: &RetentionPolicyConfig) -> Result<Policy> {
+    let name = config
+        .name
+        .clone()
+        .unwrap_or_else(|| format!("policies[{}]", index));
+    let invalid = |reason: String| Error::config_validation(format!("retention.{}", name), reason);
+
+    if config.max_age_days == 0 {
+        return Err(invalid("max_age_days must be greater than 0".to_string()));
+    }
+
+    let mut conditions = Vec::new();
+    let regex = match &config.regex {
+        Some(pattern) => {
+            conditions.push(format!("regex {}", pattern));
+            Some(Regex::new(pattern).map_err(|e| invalid(e.to_string()))?)
+        }
+        None => None,
+    };
+    if config.has_tokens {
+        conditions.push("has tokens".to_string());
+    }
+    if config.failed {
+        conditions.push("failed".to_string());
+    }
+    if conditions.is_empty() {
+        conditions.push("any command".to_string());
+    }
+
+    Ok(Policy {
+        description: format!(
+            "{}: keep {} days",
+            conditions.join(", "),
+            config.max_age_days
+        ),
+        name,
+        regex,
+        has_tokens: config.has_tokens,
+        failed: config.failed,
+        max_age: Duration::days(i64::from(config.max_age_days)),
+    })
+}
+
+/// Records when the policies were last applied on open, so that logging a
+/// command does not scan the whole history every time
+#[derive(Debug, Clone)]
+pub struct RetentionSchedule {
+    stamp: PathBuf,
+}
+
+impl RetentionSchedule {
+    /// The schedule for the history of `config`, kept next to the history file
+    pub fn from_config(config: &Config) -> Self {
+        let mut stamp = config.history_file.clone().into_os_string();
+        stamp.push(".gc");
+        Self {
+            stamp: PathBuf::from(stamp),
+        }
+    }
+
+    /// Whether the policies are due to be applied again at `now`
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        std::fs::read_to_string(&self.stamp)
+            .ok()
+            .and_then(|stamp| DateTime::parse_from_rfc3339(stamp.trim()).ok())
+            .is_none_or(|last| {
+                now - last.with_timezone(&Utc) >= Duration::hours(APPLY_INTERVAL_HOURS)
+            })
+    }
+
+    /// Record that the policies were applied at `now`
+    pub fn mark(&self, now: DateTime<Utc>) -> Result<()> {
+        std::fs::write(&self.stamp, now.to_rfc3339())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn policy(regex: Option<&str>, has_tokens: bool, days: u32) -> RetentionPolicyConfig {
+        RetentionPolicyConfig {
+            name: None,
+            regex: regex.map(str::to_string),
+            has_tokens,
+            failed: false,
+            max_age_days: days,
+        }
+    }
+
+    #[test]
+    fn test_first_matching_policy_decides() {
+        let mut config = Config::default();
+        config.retention.policies = vec![
+            policy(Some("^(ls|cd|git status)"), false, 30),
+            policy(None, true, 90),
+        ];
+        let policies = RetentionPolicies::from_config(&config).unwrap();
+        let now = Utc::now();
+        let subject = |command, days, has_tokens| RetentionSubject {
+            command,
+            timestamp: now - Duration::days(days),
+            has_tokens,
+            exit_code: None,
+        };
+
+        assert_eq!(
+            policies.expired_by(&subject("ls -la", 31, false), now),
+            Some("policies[0]")
+        );
+        assert_eq!(
+            policies.expired_by(&subject("ls -la", 29, false), now),
+            None
+        );
+        // A younger `ls` with tokens is kept by the first policy
+        assert_eq!(policies.expired_by(&subject("ls -la", 29, true), now), None);
+        assert_eq!(
+            policies.expired_by(&subject("curl -u me:pw", 91, true), now),
+            Some("policies[1]")
+        );
+        assert_eq!(policies.expired_by(&subject("make", 400, false), now), None);
+
+        config.retention.policies = vec![policy(None, false, 0)];
+        assert!(RetentionPolicies::from_config(&config).is_err());
+        config.retention.policies = vec![policy(Some("[unclosed"), false, 1)];
+        assert!(RetentionPolicies::from_config(&config).is_err());
+    }
+
+    #[test]
+    fn test_schedule_runs_once_a_day() {
+        let dir = tempdir().unwrap();
+        let config = Config {
+            history_file: dir.path().join("history.mhist"),
+            ..Default::default()
+        };
+        let schedule = RetentionSchedule::from_config(&config);
+        let now = Utc::now();
+
+        assert!(schedule.is_due(now));
+        schedule.mark(now).unwrap();
+        assert!(!schedule.is_due(now + Duration::hours(23)));
+        assert!(schedule.is_due(now + Duration::hours(24)));
+    }
+}
@@ -3,11 +3,20 @@
 //! This module provides advanced search capabilities for command history,
 //! including fuzzy search, filtering, ranking, and result highlighting.
 
-use crate::error::Result;
+use crate::error::{Error, Result};
+use crate::folding::{Folded, Folding};
+use crate::fuzzy;
 use crate::history::HistoryEntry;
 use regex::Regex;
+use std::borrow::Cow;
 use std::collections::HashMap;
 
+/// Outcome of matching a term against a command: (matched, spans, score)
+type MatchOutcome = (bool, Vec<(usize, usize)>, f64);
+
+/// A query's match in an entry: (spans, score)
+type EntryMatch = (Vec<(usize, usize)>, f64);
+
 /// Search engine for history entries
 #[derive(Debug, Clone)]
 pub struct SearchEngine {
@@ -23,6 +32,10 @@ pub struct SearchEngine {
     pub max_results: usize,
     /// Whether to highlight matches in search results
     pub highlight_matches: bool,
+    /// Whether to match compatibility forms, such as `ﬁ` for `fi` (NFKC)
+    pub normalize_unicode: bool,
+    /// Whether accented letters match their plain forms
+    pub ignore_accents: bool,
 }
 
 /// Search query with various filters and options
@@ -32,7 +45,7 @@ pub struct SearchQuery {
     pub term: String,
     /// Optional directory filter
     pub directory: Option<String>,
-    /// Optional time range filter (start, end)
+    /// Optional time range filter (start, end), `end` not included
     pub time_range: Option<(chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>)>,
     /// Whether to use fuzzy matching
     pub fuzzy: bool,
@@ -44,6 +57,40 @@ pub struct SearchQuery {
     pub redacted_only: bool,
     /// Maximum number of results to return
     pub limit: Option<usize>,
+    /// Whether to leave out redacted commands
+    pub exclude_redacted: bool,
+    /// Phrases the command must contain as written
+    pub phrases: Vec<String>,
+    /// Regexes the command must match
+    pub patterns: Vec<String>,
+    /// Words and phrases the command must not contain
+    pub excluded: Vec<String>,
+    /// Host the command must have run on
+    pub host: Option<String>,
+    /// Session the command must have run in
+    pub session: Option<String>,
+    /// Exit code the command must have had
+    pub exit_code: Option<ExitCodeFilter>,
+}
+
+/// Exit code condition of a search query
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCodeFilter {
+    /// The command exited with this code
+    Is(i32),
+    /// The command exited with any other code
+    IsNot(i32),
+}
+
+impl ExitCodeFilter {
+    /// Whether a recorded exit code passes; unrecorded codes never do
+    pub fn matches(&self, exit_code: Option<i32>) -> bool {
+        match (self, exit_code) {
+            (ExitCodeFilter::Is(expected), Some(code)) => code == *expected,
+            (ExitCodeFilter::IsNot(expected), Some(code)) => code != *expected,
+            (_, None) => false,
+        }
+    }
 }
 
 /// Search result with metadata
@@ -82,6 +129,8 @@ impl SearchEngine {
             include_timestamps: false,
             max_results: 1000,
             highlight_matches: true,
+            normalize_unicode: false,
+            ignore_accents: false,
         }
     }
 
@@ -101,20 +150,18 @@ impl SearchEngine {
             include_timestamps,
             max_results,
             highlight_matches,
+            normalize_unicode: false,
+            ignore_accents: false,
         }
     }
 
     /// Search through history entries with a simple query
     pub fn search(&self, entries: &[HistoryEntry], query: &str) -> Result<Vec<SearchResult>> {
         let search_query = SearchQuery {
-            term: query.to_string(),
-            directory: None,
-            time_range: None,
             fuzzy: self.fuzzy_search,
             case_sensitive: self.case_sensitive,
-            regex: false,
-            redacted_only: false,
             limit: Some(self.max_results),
+            ..SearchQuery::new(query.to_string())
         };
 
         self.search_with_query(entries, &search_query)
@@ -125,48 +172,36 @@ impl SearchEngine {
         &self,
         entries: &[HistoryEntry],
         query: &SearchQuery,
+    ) -> Result<Vec<SearchResult>> {
+        self.search_each(entries.iter().map(Cow::Borrowed), query)
+    }
+
+    /// Search through history entries as they are read, keeping only the
+    /// ones that match
+    pub fn search_stream(
+        &self,
+        entries: impl IntoIterator<Item = HistoryEntry>,
+        query: &SearchQuery,
+    ) -> Result<Vec<SearchResult>> {
+        self.search_each(entries.into_iter().map(Cow::Owned), query)
+    }
+
+    fn search_each<'a>(
+        &self,
+        entries: impl Iterator<Item = Cow<'a, HistoryEntry>>,
+        query: &SearchQuery,
     ) -> Result<Vec<SearchResult>> {
         let start_time = std::time::Instant::now();
         let mut results = Vec::new();
-        let mut stats = SearchStats::default();
+        let mut _stats = SearchStats::default();
 
-        // Compile regex if needed
-        let regex = if query.regex {
-            Some(if query.case_sensitive {
-                Regex::new(&query.term)?
-            } else {
-                Regex::new(&format!("(?i){}", query.term))?
-            })
-        } else {
-            None
-        };
-
-        // Prepare search term
-        let search_term = if query.case_sensitive {
-            query.term.clone()
-        } else {
-            query.term.to_lowercase()
-        };
+        let compiled = CompiledQuery::new(query, self.folding(query))?;
 
         for entry in entries {
-            stats.total_searched += 1;
+            _stats.total_searched += 1;
 
-            // Apply filters
-            if !self.matches_filters(entry, query) {
-                continue;
-            }
-
-            // Check for match
-            let (is_match, matches, score) = if let Some(ref regex) = regex {
-                self.regex_match(&entry.command, regex)?
-            } else if query.fuzzy {
-                self.fuzzy_match(&entry.command, &search_term, query.case_sensitive)
-            } else {
-                self.exact_match(&entry.command, &search_term, query.case_sensitive)
-            };
-
-            if is_match {
-                stats.matches_found += 1;
+            if let Some((matches, score)) = self.match_entry(&entry, query, &compiled)? {
+                _stats.matches_found += 1;
 
                 let highlighted = if self.highlight_matches && !matches.is_empty() {
                     Some(self.highlight_command(&entry.command, &matches))
@@ -175,7 +210,7 @@ impl SearchEngine {
                 };
 
                 results.push(SearchResult {
-                    entry: entry.clone(),
+                    entry: entry.into_owned(),
                     score,
                     highlighted,
                     matches,
@@ -196,12 +231,80 @@ impl SearchEngine {
             results.truncate(limit);
         }
 
-        stats.results_returned = results.len();
-        stats.search_time_ms = start_time.elapsed().as_millis() as u64;
+        _stats.results_returned = results.len();
+        _stats.search_time_ms = start_time.elapsed().as_millis() as u64;
 
         Ok(results)
     }
 
+    /// Positions of the entries that match a query, in their original order
+    ///
+    /// For pickers that keep their own ordering; the query limit is ignored.
+    pub fn matching_indices(
+        &self,
+        entries: &[HistoryEntry],
+        query: &SearchQuery,
+    ) -> Result<Vec<usize>> {
+        let compiled = CompiledQuery::new(query, self.folding(query))?;
+        let mut indices = Vec::new();
+        for (idx, entry) in entries.iter().enumerate() {
+            if self.match_entry(entry, query, &compiled)?.is_some() {
+                indices.push(idx);
+            }
+        }
+        Ok(indices)
+    }
+
+    /// Match one entry against a query, returning the matched spans and the
+    /// score if it matches
+    fn match_entry(
+        &self,
+        entry: &HistoryEntry,
+        query: &SearchQuery,
+        compiled: &CompiledQuery,
+    ) -> Result<Option<EntryMatch>> {
+        if !self.matches_filters(entry, query) {
+            return Ok(None);
+        }
+
+        let command = compiled.folding.fold(&entry.command);
+        let (is_match, mut matches, score) = if let Some(ref regex) = compiled.regex {
+            self.regex_match(&entry.command, regex)?
+        } else if compiled.term.is_empty() {
+            (true, Vec::new(), 0.0)
+        } else if query.fuzzy {
+            self.fuzzy_match(&command, &compiled.term)
+        } else {
+            self.exact_match(&command, &compiled.term)
+        };
+        if !is_match {
+            return Ok(None);
+        }
+
+        for word in &compiled.excluded {
+            let (found, _, _) = self.exact_match(&command, word);
+            if found {
+                return Ok(None);
+            }
+        }
+        for phrase in &compiled.phrases {
+            let (found, spans, _) = self.exact_match(&command, phrase);
+            if !found {
+                return Ok(None);
+            }
+            matches.extend(spans);
+        }
+        for pattern in &compiled.patterns {
+            let (found, spans, _) = self.regex_match(&entry.command, pattern)?;
+            if !found {
+                return Ok(None);
+            }
+            matches.extend(spans);
+        }
+
+        Ok(Some((merge_spans(matches), score)))
+    }
+
     /// Search for commands that contain sensitive data
     pub fn search_redacted(&self, entries: &[HistoryEntry]) -> Result<Vec<SearchResult>> {
         let mut results = Vec::new();
@@ -218,7 +321,7 @@ impl SearchEngine {
         }
 
         // Sort by timestamp (descending)
-        results.sort_by(|a, b| b.entry.timestamp.cmp(&a.entry.timestamp));
+        results.sort_by_key(|result| std::cmp::Reverse(result.entry.timestamp));
 
         if results.len() > self.max_results {
             results.truncate(self.max_results);
@@ -247,7 +350,7 @@ impl SearchEngine {
         }
 
         // Sort by timestamp (descending)
-        results.sort_by(|a, b| b.entry.timestamp.cmp(&a.entry.timestamp));
+        results.sort_by_key(|result| std::cmp::Reverse(result.entry.timestamp));
 
         if results.len() > self.max_results {
             results.truncate(self.max_results);
@@ -265,7 +368,7 @@ impl SearchEngine {
         }
 
         let mut sorted_commands: Vec<(String, usize)> = command_counts.into_iter().collect();
-        sorted_commands.sort_by(|a, b| b.1.cmp(&a.1));
+        sorted_commands.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
 
         if sorted_commands.len() > self.max_results {
             sorted_commands.truncate(self.max_results);
@@ -286,7 +389,7 @@ impl SearchEngine {
         }
 
         let mut sorted_directories: Vec<(String, usize)> = directory_counts.into_iter().collect();
-        sorted_directories.sort_by(|a, b| b.1.cmp(&a.1));
+        sorted_directories.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
 
         if sorted_directories.len() > self.max_results {
             sorted_directories.truncate(self.max_results);
@@ -298,39 +401,65 @@ impl SearchEngine {
     /// Check if an entry matches the query filters
     fn matches_filters(&self, entry: &HistoryEntry, query: &SearchQuery) -> bool {
         // Directory filter
-        if let Some(ref dir_filter) = query.directory {
-            if !entry.directory.contains(dir_filter) {
-                return false;
-            }
+        if let Some(ref dir_filter) = query.directory
+            && !entry.directory.contains(dir_filter)
+        {
+            return false;
         }
 
         // Time range filter
-        if let Some((start, end)) = query.time_range {
-            if entry.timestamp < start || entry.timestamp > end {
-                return false;
-            }
+        if let Some((start, end)) = query.time_range
+            && (entry.timestamp < start || entry.timestamp >= end)
+        {
+            return false;
         }
 
         // Redacted filter
         if query.redacted_only && !entry.redacted {
             return false;
         }
+        if query.exclude_redacted && entry.redacted {
+            return false;
+        }
+
+        if let Some(ref host) = query.host
+            && !entry
+                .host
+                .as_deref()
+                .is_some_and(|entry_host| entry_host.eq_ignore_ascii_case(host))
+        {
+            return false;
+        }
+
+        if let Some(ref session) = query.session
+            && entry.session.as_deref() != Some(session.as_str())
+        {
+            return false;
+        }
+
+        if let Some(filter) = query.exit_code
+            && !filter.matches(entry.exit_code)
+        {
+            return false;
+        }
 
         true
     }
 
-    /// Perform exact string matching
-    fn exact_match(
-        &self,
-        command: &str,
-        search_term: &str,
-        case_sensitive: bool,
-    ) -> (bool, Vec<(usize, usize)>, f64) {
-        let haystack = if case_sensitive {
-            command
-        } else {
-            &command.to_lowercase()
-        };
+    /// How a query folds text before matching
+    fn folding(&self, query: &SearchQuery) -> Folding {
+        Folding {
+            case_sensitive: query.case_sensitive,
+            normalize: self.normalize_unicode,
+            ignore_accents: self.ignore_accents,
+        }
+    }
+
+    /// Perform exact string matching of a folded term
+    ///
+    /// The spans are byte offsets in the command as written.
+    fn exact_match(&self, command: &Folded, search_term: &str) -> MatchOutcome {
+        let haystack = command.as_str();
 
         let mut matches = Vec::new();
         let mut start = 0;
@@ -338,7 +467,7 @@ impl SearchEngine {
 
         while let Some(pos) = haystack[start..].find(search_term) {
             let actual_pos = start + pos;
-            matches.push((actual_pos, actual_pos + search_term.len()));
+            matches.push(command.original_span(actual_pos, actual_pos + search_term.len()));
             start = actual_pos + search_term.len();
             match_count += 1;
         }
@@ -348,7 +477,7 @@ impl SearchEngine {
             // Higher score for more matches and exact matches at the beginning
             let base_score = match_count as f64;
             let position_bonus = if matches[0].0 == 0 { 0.5 } else { 0.0 };
-            let length_ratio = search_term.len() as f64 / command.len() as f64;
+            let length_ratio = search_term.len() as f64 / haystack.len() as f64;
             base_score + position_bonus + length_ratio
         } else {
             0.0
@@ -357,72 +486,20 @@ impl SearchEngine {
         (is_match, matches, score)
     }
 
-    /// Perform fuzzy matching using a simple algorithm
-    fn fuzzy_match(
-        &self,
-        command: &str,
-        search_term: &str,
-        case_sensitive: bool,
-    ) -> (bool, Vec<(usize, usize)>, f64) {
-        let haystack = if case_sensitive {
-            command.to_string()
-        } else {
-            command.to_lowercase()
-        };
-
-        let needle = if case_sensitive {
-            search_term.to_string()
-        } else {
-            search_term.to_lowercase()
-        };
-
-        // Simple fuzzy matching: check if all characters in search term appear in order
-        let mut matches = Vec::new();
-        let mut haystack_pos = 0;
-        let mut needle_pos = 0;
-        let mut match_start = None;
-
-        let haystack_chars: Vec<char> = haystack.chars().collect();
-        let needle_chars: Vec<char> = needle.chars().collect();
-
-        while haystack_pos < haystack_chars.len() && needle_pos < needle_chars.len() {
-            if haystack_chars[haystack_pos] == needle_chars[needle_pos] {
-                if match_start.is_none() {
-                    match_start = Some(haystack_pos);
-                }
-                needle_pos += 1;
-                if needle_pos == needle_chars.len() {
-                    // Found all characters
-                    matches.push((match_start.unwrap(), haystack_pos + 1));
-                    break;
-                }
-            }
-            haystack_pos += 1;
+    /// Perform fuzzy matching, keeping the best alignment of the term
+    ///
+    /// See [`crate::fuzzy`] for how alignments are scored. The spans cover
+    /// exactly the matched characters.
+    fn fuzzy_match(&self, command: &Folded, search_term: &str) -> MatchOutcome {
+        match fuzzy::best_match(command, search_term) {
+            // Fuzzy matches score lower than exact matches
+            Some(found) => (true, found.spans, found.score * 0.8),
+            None => (false, Vec::new(), 0.0),
         }
-
-        let is_match = needle_pos == needle_chars.len();
-        let score = if is_match {
-            // Calculate score based on how close the match is to exact
-            let match_length = if let Some(start) = match_start {
-                haystack_pos - start + 1
-            } else {
-                haystack.len()
-            };
-            let exact_ratio = needle.len() as f64 / match_length as f64;
-            exact_ratio * 0.8 // Fuzzy matches score lower than exact matches
-        } else {
-            0.0
-        };
-
-        (is_match, matches, score)
     }
 
     /// Perform regex matching
-    fn regex_match(
-        &self,
-        command: &str,
-        regex: &Regex,
-    ) -> Result<(bool, Vec<(usize, usize)>, f64)> {
+    fn regex_match(&self, command: &str, regex: &Regex) -> Result<MatchOutcome> {
         let mut matches = Vec::new();
 
         for mat in regex.find_iter(command) {
@@ -474,6 +551,72 @@ impl SearchEngine {
     }
 }
 
+/// The parts of a query prepared once per search
+struct CompiledQuery {
+    /// Regex for the term, in regex mode
+    regex: Option<Regex>,
+    /// How commands and terms are folded before matching
+    folding: Folding,
+    /// The term, folded
+    term: String,
+    phrases: Vec<String>,
+    excluded: Vec<String>,
+    patterns: Vec<Regex>,
+}
+
+impl CompiledQuery {
+    fn new(query: &SearchQuery, folding: Folding) -> Result<Self> {
+        let fold = |text: &String| folding.fold_term(text);
+
+        let regex = if query.regex {
+            Some(if query.case_sensitive {
+                Regex::new(&query.term)?
+            } else {
+                Regex::new(&format!("(?i){}", query.term))?
+            })
+        } else {
+            None
+        };
+
+        let patterns = query
+            .patterns
+            .iter()
+            .map(|pattern| {
+                let source = if query.case_sensitive {
+                    pattern.clone()
+                } else {
+                    format!("(?i){}", pattern)
+                };
+                Regex::new(&source).map_err(|e| {
+                    Error::search_failed(format!("invalid regex /{}/: {}", pattern, e))
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            regex,
+            folding,
+            term: fold(&query.term),
+            phrases: query.phrases.iter().map(fold).collect(),
+            excluded: query.excluded.iter().map(fold).collect(),
+            patterns,
+        })
+    }
+}
+
+/// Sort spans and merge the ones that overlap, so they can be highlighted
+fn merge_spans(mut spans: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+    spans.sort_unstable();
+    let mut merged: Vec<(usize, usize)> = Vec::with_capacity(spans.len());
+    for (start, end) in spans {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
 impl Default for SearchEngine {
     fn default() -> Self {
         Self::new()
@@ -492,9 +635,23 @@ impl SearchQuery {
             regex: false,
             redacted_only: false,
             limit: None,
+            exclude_redacted: false,
+            phrases: Vec::new(),
+            patterns: Vec::new(),
+            excluded: Vec::new(),
+            host: None,
+            session: None,
+            exit_code: None,
         }
     }
 
+    /// Parse a query written in the search query language
+    ///
+    /// See [`crate::query`] for the syntax.
+    pub fn parse(input: &str, context: &crate::query::QueryContext) -> Result<Self> {
+        crate::query::parse(input, context)
+    }
+
     /// Set directory filter
     pub fn with_directory(mut self, directory: String) -> Self {
         self.directory = Some(directory);
@@ -555,6 +712,11 @@ mod tests {
                 directory: "/home/user".to_string(),
                 redacted: false,
                 original: None,
+                deleted: false,
+                host: None,
+                session: None,
+                exit_code: None,
+                utc_offset: None,
             },
             HistoryEntry {
                 command: "ls -la".to_string(),
@@ -562,6 +724,11 @@ mod tests {
                 directory: "/home/user/documents".to_string(),
                 redacted: false,
                 original: None,
+                deleted: false,
+                host: None,
+                session: None,
+                exit_code: None,
+                utc_offset: None,
             },
             HistoryEntry {
                 command: "password=<redacted>".to_string(),
@@ -569,6 +736,11 @@ mod tests {
                 directory: "/home/user".to_string(),
                 redacted: true,
                 original: Some("password=secret123".to_string()),
+                deleted: false,
+                host: None,
+                session: None,
+                exit_code: None,
+                utc_offset: None,
             },
             HistoryEntry {
                 command: "echo Hello World".to_string(),
@@ -576,6 +748,11 @@ mod tests {
                 directory: "/tmp".to_string(),
                 redacted: false,
                 original: None,
+                deleted: false,
+                host: None,
+                session: None,
+                exit_code: None,
+                utc_offset: None,
             },
         ]
     }
@@ -609,6 +786,16 @@ mod tests {
         let results = engine.search(&entries, "eh").unwrap();
         assert!(!results.is_empty());
         // Should match "echo" commands
+
+        // Only the matched characters are highlighted: the "e" of "echo" and
+        // the word start "H" rather than the "h" inside "echo"
+        let top = &results[0];
+        assert_eq!(top.entry.command, "echo Hello World");
+        assert_eq!(top.matches, vec![(0, 1), (5, 6)]);
+        assert_eq!(
+            top.highlighted.as_deref(),
+            Some("\x1b[1;33me\x1b[0mcho \x1b[1;33mH\x1b[0mello World")
+        );
     }
 
     #[test]
@@ -645,6 +832,94 @@ mod tests {
         assert!(results[0].entry.directory.contains("/home/user"));
     }
 
+    #[test]
+    fn test_structured_query() {
+        let engine = SearchEngine::new();
+        let mut entries = create_test_entries();
+        entries[0].host = Some("ci-runner".to_string());
+        entries[0].exit_code = Some(0);
+        entries[3].host = Some("laptop".to_string());
+        entries[3].exit_code = Some(1);
+        let ctx = crate::query::QueryContext::new(None, Default::default());
+        let search = |input: &str| -> Vec<String> {
+            let query = SearchQuery::parse(input, &ctx).unwrap();
+            let mut commands: Vec<String> = engine
+                .search_with_query(&entries, &query)
+                .unwrap()
+                .into_iter()
+                .map(|result| result.entry.command)
+                .collect();
+            commands.sort();
+            commands
+        };
+
+        assert_eq!(search("echo host:CI-RUNNER"), ["echo hello world"]);
+        assert_eq!(search("echo -exit:0"), ["echo Hello World"]);
+        assert_eq!(
+            search("\"o hello\""),
+            ["echo Hello World", "echo hello world"]
+        );
+        assert_eq!(search("\"echo world\""), Vec::<String>::new());
+        assert_eq!(search("/^ls -l/"), ["ls -la"]);
+        assert_eq!(search("echo -\"hello\""), Vec::<String>::new());
+        assert_eq!(search("-redacted dir:/home/user/documents"), ["ls -la"]);
+
+        // A phrase alone still reports where it matched
+        let query = SearchQuery::parse("\"ls -la\"", &ctx).unwrap();
+        let results = engine.search_with_query(&entries, &query).unwrap();
+        assert_eq!(results[0].matches, vec![(0, 6)]);
+        assert!(SearchQuery::parse("/[unclosed/", &ctx).is_err());
+    }
+
+    #[test]
+    fn test_unicode_search() {
+        let mut engine = SearchEngine::new();
+        let template = create_test_entries().remove(0);
+        let entries: Vec<HistoryEntry> = ["echo İstanbul", "cp ~/Straße.txt .", "open résumé.pdf"]
+            .iter()
+            .map(|command| HistoryEntry {
+                command: command.to_string(),
+                ..template.clone()
+            })
+            .collect();
+        let matched = |engine: &SearchEngine, term: &str, fuzzy: bool| -> Vec<String> {
+            let query = SearchQuery {
+                fuzzy,
+                ..SearchQuery::new(term.to_string())
+            };
+            engine
+                .search_with_query(&entries, &query)
+                .unwrap()
+                .iter()
+                .flat_map(|result| {
+                    result
+                        .matches
+                        .iter()
+                        .map(|&(start, end)| result.entry.command[start..end].to_string())
+                })
+                .collect()
+        };
+
+        // Folding changes lengths; spans still land on the original text
+        assert_eq!(matched(&engine, "stanbul", false), vec!["stanbul"]);
+        assert_eq!(matched(&engine, "İSTANBUL", false), vec!["İstanbul"]);
+        assert_eq!(matched(&engine, "STRASSE", false), vec!["Straße"]);
+        assert_eq!(matched(&engine, "strasse", true), vec!["Straße"]);
+        assert_eq!(matched(&engine, "RÉSUMÉ", false), vec!["résumé"]);
+        assert!(matched(&engine, "resume", false).is_empty());
+
+        let results = engine.search(&entries, "stanbul").unwrap();
+        assert_eq!(
+            results[0].highlighted.as_deref(),
+            Some("echo İ\x1b[1;33mstanbul\x1b[0m")
+        );
+
+        engine.ignore_accents = true;
+        assert_eq!(matched(&engine, "resume", false), vec!["résumé"]);
+        assert_eq!(matched(&engine, "istanbul", false), vec!["İstanbul"]);
+        assert_eq!(matched(&engine, "rsm", true), vec!["r", "s", "m"]);
+    }
+
     #[test]
     fn test_frequent_commands() {
         let engine = SearchEngine::new();
@@ -656,6 +931,11 @@ mod tests {
             directory: "/home/user".to_string(),
             redacted: false,
             original: None,
+            deleted: false,
+            host: None,
+            session: None,
+            exit_code: None,
+            utc_offset: None,
         });
 
         let frequent = engine.get_frequent_commands(&entries).unwrap();
@@ -695,6 +975,11 @@ mod tests {
                 directory: "/home/user".to_string(),
                 redacted: false,
                 original: None,
+                deleted: false,
+                host: None,
+                session: None,
+                exit_code: None,
+                utc_offset: None,
             },
             HistoryEntry {
                 command: "some echo command".to_string(), // Should score lower
@@ -702,6 +987,11 @@ mod tests {
                 directory: "/home/user".to_string(),
                 redacted: false,
                 original: None,
+                deleted: false,
+                host: None,
+                session: None,
+                exit_code: None,
+                utc_offset: None,
             },
         ];
 
@@ -709,5 +999,38 @@ mod tests {
         assert_eq!(results.len(), 2);
         // First result should have higher score
         assert!(results[0].score >= results[1].score);
+
+        // Fuzzy matches rank word starts and consecutive runs first
+        let ranked = |commands: &[&str], term: &str| -> Vec<String> {
+            let entries: Vec<HistoryEntry> = commands
+                .iter()
+                .map(|command| HistoryEntry {
+                    command: command.to_string(),
+                    ..entries[0].clone()
+                })
+                .collect();
+            engine
+                .search(&entries, term)
+                .unwrap()
+                .into_iter()
+                .map(|result| result.entry.command)
+                .collect()
+        };
+        assert_eq!(
+            ranked(&["cargo test --all", "gist", "git status"], "gst"),
+            vec!["git status", "gist", "cargo test --all"]
+        );
+        assert_eq!(
+            ranked(&["vim src/domain.rs", "vim src/main.rs"], "main"),
+            vec!["vim src/main.rs", "vim src/domain.rs"]
+        );
+        assert_eq!(
+            ranked(&["xenortic", "npm run test"], "nrt"),
+            vec!["npm run test", "xenortic"]
+        );
+        assert_eq!(
+            ranked(&["kubectl apply -f pod.yml", "kubectl logs -f pod"], "klf"),
+            vec!["kubectl logs -f pod", "kubectl apply -f pod.yml"]
+        );
     }
 }
@@ -0,0 +1,105 @@
+//! Replacing files whose removed contents must not survive
+//!
+//! History files are rewritten when commands are shredded, trashed or
+//! restored. The new contents are written to a temporary file next to the
+//! original and synced first; only then are the old contents zeroed in place,
+//! so they do not linger in the file's blocks, and the temporary file renamed
+//! over the original. A crash at any point leaves the kept contents on disk:
+//! in the original until it is zeroed, and in the temporary file until it is
+//! renamed.
+
+use crate::error::Result;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Replace the contents of `path`, zeroing the old contents
+///
+/// The file keeps its permissions. A missing file is created.
+pub fn replace(path: &Path, contents: &[u8]) -> Result<()> {
+    let temp = temp_path(path);
+    let mut file = File::create(&temp)?;
+    if let Ok(metadata) = fs::metadata(path) {
+        file.set_permissions(metadata.permissions())?;
+    }
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+
+    zero(path)?;
+    fs::rename(&temp, path)?;
+    sync_parent(path)
+}
+
+/// Remove `path`, zeroing its contents first
+pub fn remove(path: &Path) -> Result<()> {
+    zero(path)?;
+    fs::remove_file(path)?;
+    sync_parent(path)
+}
+
+/// Overwrite the contents of `path` with zeros, if it exists
+fn zero(path: &Path) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let mut file = OpenOptions::new().write(true).truncate(false).open(path)?;
+    let len = file.metadata()?.len();
+    file.write_all(&vec![0u8; len as usize])?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// The temporary file the new contents of `path` are written to
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.mortimer-tmp", name))
+}
+
+/// Make a rename or removal in the directory of `path` durable
+fn sync_parent(path: &Path) -> Result<()> {
+    #[cfg(unix)]
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        File::open(parent)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replace_and_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".mhist");
+
+        replace(&path, b"first\n").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"first\n");
+
+        replace(&path, b"second, longer\n").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"second, longer\n");
+        assert!(!temp_path(&path).exists());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+            replace(&path, b"kept\n").unwrap();
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        remove(&path).unwrap();
+        assert!(!path.exists());
+    }
+}
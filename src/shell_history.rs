@@ -0,0 +1,552 @@
+//! Native shell history files for Mortimer
+//!
+//! Mortimer only redacts the commands it logs itself; the shell's own
+//! history file still holds every command in plaintext. This module reads
+//! zsh, bash and fish history files record by record so they can be scanned
+//! for secrets, and writes them back byte for byte except for the commands
+//! that were redacted.
+//!
+//! # Formats
+//!
+//! - zsh: plain lines, or `: <start>:<duration>;<command>` with extended
+//!   history. Multi-line commands continue on lines ending in `\`.
+//! - bash: one command per line, optionally preceded by `#<timestamp>`.
+//! - fish: YAML-like records starting with `- cmd: <command>`, where
+//!   newlines and backslashes in the command are escaped.
+
+use crate::error::{Error, Result};
+use crate::redaction::RedactionEngine;
+use chrono::Utc;
+use regex::Regex;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Shells whose history files can be scanned and scrubbed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Zsh,
+    Bash,
+    Fish,
+}
+
+impl Shell {
+    /// Parse a shell name as used in the configuration
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "zsh" => Ok(Shell::Zsh),
+            "bash" => Ok(Shell::Bash),
+            "fish" => Ok(Shell::Fish),
+            _ => Err(Error::invalid_arguments(format!(
+                "unsupported shell '{}', expected zsh, bash or fish",
+                name
+            ))),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Shell::Zsh => "zsh",
+            Shell::Bash => "bash",
+            Shell::Fish => "fish",
+        }
+    }
+
+    /// Guess the shell from the name of its history file
+    pub fn detect(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?;
+        if name.contains("zsh") || name == ".histfile" {
+            Some(Shell::Zsh)
+        } else if name.contains("bash") {
+            Some(Shell::Bash)
+        } else if name.contains("fish") {
+            Some(Shell::Fish)
+        } else {
+            None
+        }
+    }
+
+    /// Turn a command as stored in the file into the command the user typed
+    fn decode(self, raw: &str) -> String {
+        match self {
+            Shell::Zsh => raw.replace("\\\n", "\n"),
+            Shell::Bash => raw.to_string(),
+            Shell::Fish => {
+                let mut command = String::with_capacity(raw.len());
+                let mut chars = raw.chars();
+                while let Some(c) = chars.next() {
+                    if c != '\\' {
+                        command.push(c);
+                        continue;
+                    }
+                    match chars.next() {
+                        Some('\\') => command.push('\\'),
+                        Some('n') => command.push('\n'),
+                        Some(other) => {
+                            command.push('\\');
+                            command.push(other);
+                        }
+                        None => command.push('\\'),
+                    }
+                }
+                command
+            }
+        }
+    }
+
+    /// Inverse of [`Shell::decode`]
+    fn encode(self, command: &str) -> String {
+        match self {
+            Shell::Zsh => command.replace('\n', "\\\n"),
+            Shell::Bash => command.to_string(),
+            Shell::Fish => command.replace('\\', "\\\\").replace('\n', "\\n"),
+        }
+    }
+}
+
+/// A rule that fired on a command in a history file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryFinding {
+    /// Line where the command starts, counting from 1
+    pub line: usize,
+    pub rule_id: String,
+    /// The command with redactions applied
+    pub redacted: String,
+}
+
+/// A piece of a history file
+enum Segment {
+    /// Bytes written back unchanged: timestamps, metadata and line endings
+    Raw(Vec<u8>),
+    /// One command, as it appears in the file
+    Command {
+        line: usize,
+        raw: String,
+        replacement: Option<String>,
+    },
+}
+
+/// A shell history file split into commands and everything around them
+pub struct NativeHistory {
+    shell: Shell,
+    segments: Vec<Segment>,
+    /// The file as it was read, to detect concurrent writes before saving
+    original: Vec<u8>,
+    unreadable_lines: Vec<usize>,
+}
+
+impl NativeHistory {
+    /// Read and parse a history file
+    pub fn load(shell: Shell, path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Err(Error::HistoryFileNotFound {
+                path: path.to_path_buf(),
+            });
+        }
+        Ok(Self::parse(shell, fs::read(path)?))
+    }
+
+    /// Parse the contents of a history file
+    pub fn parse(shell: Shell, content: Vec<u8>) -> Self {
+        let zsh_extended = Regex::new(r"^: \d+:\d+;").expect("valid regex");
+        let mut segments = Vec::new();
+        let mut unreadable_lines = Vec::new();
+
+        let mut lines = content.split_inclusive(|&b| b == b'\n').peekable();
+        let mut line = 0;
+        while let Some(first) = lines.next() {
+            line += 1;
+            let start_line = line;
+
+            // zsh continues multi-line commands on lines ending in a backslash
+            let mut record = first.to_vec();
+            if shell == Shell::Zsh {
+                while record.ends_with(b"\\\n") && lines.peek().is_some() {
+                    record.extend_from_slice(lines.next().unwrap_or_default());
+                    line += 1;
+                }
+            }
+
+            let (body, ending) = match record.strip_suffix(b"\n") {
+                Some(body) => (body, &b"\n"[..]),
+                None => (&record[..], &b""[..]),
+            };
+            let Ok(body) = std::str::from_utf8(body) else {
+                unreadable_lines.push(start_line);
+                segments.push(Segment::Raw(record.clone()));
+                continue;
+            };
+
+            let prefix_len = match shell {
+                Shell::Zsh => Some(zsh_extended.find(body).map_or(0, |m| m.end())),
+                Shell::Bash => {
+                    let is_timestamp = body
+                        .strip_prefix('#')
+                        .is_some_and(|t| !t.is_empty() && t.bytes().all(|b| b.is_ascii_digit()));
+                    (!is_timestamp).then_some(0)
+                }
+                Shell::Fish => body.starts_with("- cmd: ").then_some("- cmd: ".len()),
+            };
+
+            match prefix_len {
+                Some(prefix_len) if prefix_len < body.len() => {
+                    segments.push(Segment::Raw(body.as_bytes()[..prefix_len].to_vec()));
+                    segments.push(Segment::Command {
+                        line: start_line,
+                        raw: body[prefix_len..].to_string(),
+                        replacement: None,
+                    });
+                    segments.push(Segment::Raw(ending.to_vec()));
+                }
+                _ => segments.push(Segment::Raw(record.clone())),
+            }
+        }
+
+        Self {
+            shell,
+            segments,
+            original: content,
+            unreadable_lines,
+        }
+    }
+
+    /// The shell that wrote the file
+    pub fn shell(&self) -> Shell {
+        self.shell
+    }
+
+    /// Number of commands in the file
+    pub fn command_count(&self) -> usize {
+        self.commands().count()
+    }
+
+    /// Lines that were left alone because they are not valid UTF-8
+    pub fn unreadable_lines(&self) -> &[usize] {
+        &self.unreadable_lines
+    }
+
+    /// Line and decoded text of every command
+    fn commands(&self) -> impl Iterator<Item = (usize, String)> + '_ {
+        self.segments.iter().filter_map(|segment| match segment {
+            Segment::Command { line, raw, .. } => Some((*line, self.shell.decode(raw))),
+            Segment::Raw(_) => None,
+        })
+    }
+
+    /// List every rule that fires on a command, one finding per rule
+    pub fn scan(&self, engine: &RedactionEngine) -> Result<Vec<HistoryFinding>> {
+        let mut findings = Vec::new();
+        for (line, command) in self.commands() {
+            let explanation = engine.explain(&command)?;
+            for rule_id in explanation.applied_rules() {
+                findings.push(HistoryFinding {
+                    line,
+                    rule_id: rule_id.to_string(),
+                    redacted: explanation.output.clone(),
+                });
+            }
+        }
+        Ok(findings)
+    }
+
+    /// Redact every command, returning how many changed
+    pub fn redact(&mut self, engine: &RedactionEngine) -> Result<usize> {
+        let mut changed = 0;
+        for segment in &mut self.segments {
+            if let Segment::Command {
+                raw, replacement, ..
+            } = segment
+            {
+                let command = self.shell.decode(raw);
+                let redacted = engine.redact(&command)?;
+                if redacted != command {
+                    *replacement = Some(redacted);
+                    changed += 1;
+                }
+            }
+        }
+        Ok(changed)
+    }
+
+    /// The file contents with any redactions applied
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut content = Vec::with_capacity(self.original.len());
+        for segment in &self.segments {
+            match segment {
+                Segment::Raw(bytes) => content.extend_from_slice(bytes),
+                Segment::Command {
+                    raw,
+                    replacement: None,
+                    ..
+                } => content.extend_from_slice(raw.as_bytes()),
+                Segment::Command {
+                    replacement: Some(command),
+                    ..
+                } => content.extend_from_slice(self.shell.encode(command).as_bytes()),
+            }
+        }
+        content
+    }
+
+    /// Replace the file with the redacted contents, keeping a backup
+    ///
+    /// The new contents are written to a temporary file next to the original
+    /// and renamed over it, so the history is never left half written. Fails
+    /// without touching the file if it changed since it was loaded. Returns
+    /// the path of the backup, which still holds the original secrets.
+    pub fn save(&self, path: &Path) -> Result<PathBuf> {
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| Error::invalid_arguments(format!("Not a file: {}", path.display())))?
+            .to_string_lossy()
+            .to_string();
+        let backup = path.with_file_name(format!(
+            "{}.mortimer-backup-{}",
+            file_name,
+            Utc::now().format("%Y%m%d%H%M%S")
+        ));
+
+        fs::copy(path, &backup)?;
+        self.overwrite(path)?;
+        Ok(backup)
+    }
+
+    /// Replace the file with the new contents, without keeping a backup
+    ///
+    /// For removing commands whose plaintext should not survive anywhere.
+    /// Fails without touching the file if it changed since it was loaded.
+    pub fn overwrite(&self, path: &Path) -> Result<()> {
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| Error::invalid_arguments(format!("Not a file: {}", path.display())))?
+            .to_string_lossy()
+            .to_string();
+        let temp = path.with_file_name(format!(".{}.mortimer-tmp", file_name));
+
+        let permissions = fs::metadata(path)?.permissions();
+        let mut file = fs::File::create(&temp)?;
+        file.set_permissions(permissions)?;
+        file.write_all(&self.to_bytes())?;
+        file.sync_all()?;
+        drop(file);
+
+        // The shell may have appended to the file in the meantime
+        if fs::read(path)? != self.original {
+            fs::remove_file(&temp)?;
+            return Err(Error::custom(format!(
+                "{} changed while it was being scrubbed; close other shells and try again",
+                path.display()
+            )));
+        }
+
+        fs::rename(&temp, path)?;
+        Ok(())
+    }
+
+    /// Remove the most recent record of each command, with its timestamp
+    /// and metadata lines, returning how many were found
+    pub fn forget(&mut self, commands: &[String]) -> usize {
+        let mut forgotten = 0;
+        for command in commands {
+            let found = self.segments.iter().rposition(|segment| match segment {
+                Segment::Command { raw, .. } => self.shell.decode(raw) == *command,
+                Segment::Raw(_) => false,
+            });
+            let Some(index) = found else {
+                continue;
+            };
+
+            // Every command sits between its prefix and its line ending
+            let mut start = index - 1;
+            let mut end = index + 1;
+            let is_raw = |segment: &Segment, test: &dyn Fn(&[u8]) -> bool| match segment {
+                Segment::Raw(bytes) => test(bytes),
+                Segment::Command { .. } => false,
+            };
+            if self.shell == Shell::Bash
+                && start > 0
+                && is_raw(&self.segments[start - 1], &|bytes| {
+                    bytes.len() > 2
+                        && bytes[0] == b'#'
+                        && bytes[1..].trim_ascii_end().iter().all(u8::is_ascii_digit)
+                })
+            {
+                start -= 1;
+            }
+            if self.shell == Shell::Fish {
+                while end + 1 < self.segments.len()
+                    && is_raw(&self.segments[end + 1], &|bytes| bytes.starts_with(b"  "))
+                {
+                    end += 1;
+                }
+            }
+
+            self.segments.drain(start..=end);
+            forgotten += 1;
+        }
+        forgotten
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const ZSH: &str = ": 1700000000:0;ls -la\n\
+                       : 1700000001:0;mysql password=hunter22\n\
+                       : 1700000002:3;echo one \\\n\
+                       token=abc123def456\n\
+                       : 1700000003:0;git status\n";
+
+    const BASH: &str =
+        "#1700000000\nls -la\n#1700000001\nexport API_KEY=sk_live_abcdef123456\necho done";
+
+    const FISH: &str = "- cmd: ls -la\n  when: 1700000000\n\
+                        - cmd: echo a\\\\b\\npassword=hunter22\n  when: 1700000001\n  paths:\n    - /tmp\n";
+
+    #[test]
+    fn test_unchanged_files_round_trip() {
+        for (shell, content) in [(Shell::Zsh, ZSH), (Shell::Bash, BASH), (Shell::Fish, FISH)] {
+            let history = NativeHistory::parse(shell, content.as_bytes().to_vec());
+            assert_eq!(history.to_bytes(), content.as_bytes(), "{:?}", shell);
+        }
+        assert_eq!(
+            NativeHistory::parse(Shell::Zsh, ZSH.as_bytes().to_vec()).command_count(),
+            4
+        );
+        assert_eq!(
+            NativeHistory::parse(Shell::Bash, BASH.as_bytes().to_vec()).command_count(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_scan_reports_lines_and_rules() {
+        let engine = RedactionEngine::new().unwrap();
+        let history = NativeHistory::parse(Shell::Zsh, ZSH.as_bytes().to_vec());
+        let findings = history.scan(&engine).unwrap();
+
+        let lines: Vec<usize> = findings.iter().map(|f| f.line).collect();
+        assert_eq!(lines, vec![2, 3]);
+        assert_eq!(findings[0].rule_id, "password");
+        assert_eq!(findings[0].redacted, "mysql password=<redacted>");
+        assert_eq!(findings[1].redacted, "echo one \ntoken=<redacted>");
+    }
+
+    #[test]
+    fn test_redact_keeps_format() {
+        let engine = RedactionEngine::new().unwrap();
+
+        let mut zsh = NativeHistory::parse(Shell::Zsh, ZSH.as_bytes().to_vec());
+        assert_eq!(zsh.redact(&engine).unwrap(), 2);
+        assert_eq!(
+            String::from_utf8(zsh.to_bytes()).unwrap(),
+            ": 1700000000:0;ls -la\n\
+             : 1700000001:0;mysql password=<redacted>\n\
+             : 1700000002:3;echo one \\\n\
+             token=<redacted>\n\
+             : 1700000003:0;git status\n"
+        );
+
+        let mut bash = NativeHistory::parse(Shell::Bash, BASH.as_bytes().to_vec());
+        assert_eq!(bash.redact(&engine).unwrap(), 1);
+        let scrubbed = String::from_utf8(bash.to_bytes()).unwrap();
+        assert!(scrubbed.starts_with("#1700000000\nls -la\n#1700000001\nexport API_KEY="));
+        assert!(!scrubbed.contains("sk_live_abcdef123456"));
+        assert!(scrubbed.ends_with("\necho done"));
+
+        let mut fish = NativeHistory::parse(Shell::Fish, FISH.as_bytes().to_vec());
+        assert_eq!(fish.redact(&engine).unwrap(), 1);
+        assert_eq!(
+            String::from_utf8(fish.to_bytes()).unwrap(),
+            "- cmd: ls -la\n  when: 1700000000\n\
+             - cmd: echo a\\\\b\\npassword=<redacted>\n  when: 1700000001\n  paths:\n    - /tmp\n"
+        );
+    }
+
+    #[test]
+    fn test_invalid_utf8_lines_are_left_alone() {
+        let first = b": 1700000000:0;echo \x83\xa9 password=hunter22\n";
+        let mut content = first.to_vec();
+        content.extend_from_slice(b": 1700000001:0;mysql password=hunter22\n");
+
+        let engine = RedactionEngine::new().unwrap();
+        let mut history = NativeHistory::parse(Shell::Zsh, content);
+        assert_eq!(history.unreadable_lines(), &[1]);
+        assert_eq!(history.redact(&engine).unwrap(), 1);
+        assert!(history.to_bytes().starts_with(first));
+    }
+
+    #[test]
+    fn test_save_keeps_backup_and_detects_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join(".zsh_history");
+        fs::write(&path, ZSH).unwrap();
+
+        let engine = RedactionEngine::new().unwrap();
+        let mut history = NativeHistory::load(Shell::Zsh, &path).unwrap();
+        history.redact(&engine).unwrap();
+        let backup = history.save(&path).unwrap();
+
+        assert_eq!(fs::read_to_string(&backup).unwrap(), ZSH);
+        assert!(!fs::read_to_string(&path).unwrap().contains("hunter22"));
+
+        // A write by the shell after loading aborts the save
+        let mut history = NativeHistory::load(Shell::Zsh, &backup).unwrap();
+        history.redact(&engine).unwrap();
+        fs::write(&backup, format!("{}: 1700000004:0;ls\n", ZSH)).unwrap();
+        assert!(history.save(&backup).is_err());
+        assert!(fs::read_to_string(&backup).unwrap().contains("hunter22"));
+    }
+
+    #[test]
+    fn test_forget_removes_whole_records() {
+        let forget = |shell, content: &str, command: &str| {
+            let mut history = NativeHistory::parse(shell, content.as_bytes().to_vec());
+            assert_eq!(history.forget(&[command.to_string()]), 1);
+            String::from_utf8(history.to_bytes()).unwrap()
+        };
+
+        assert_eq!(
+            forget(Shell::Zsh, ZSH, "mysql password=hunter22"),
+            ": 1700000000:0;ls -la\n\
+             : 1700000002:3;echo one \\\n\
+             token=abc123def456\n\
+             : 1700000003:0;git status\n"
+        );
+        assert_eq!(
+            forget(Shell::Bash, BASH, "export API_KEY=sk_live_abcdef123456"),
+            "#1700000000\nls -la\necho done"
+        );
+        assert_eq!(
+            forget(Shell::Fish, FISH, "echo a\\b\npassword=hunter22"),
+            "- cmd: ls -la\n  when: 1700000000\n"
+        );
+
+        let mut history = NativeHistory::parse(Shell::Zsh, ZSH.as_bytes().to_vec());
+        assert_eq!(history.forget(&["not there".to_string()]), 0);
+        assert_eq!(history.to_bytes(), ZSH.as_bytes());
+    }
+
+    #[test]
+    fn test_detect_shell() {
+        assert_eq!(
+            Shell::detect(Path::new("/home/u/.zsh_history")),
+            Some(Shell::Zsh)
+        );
+        assert_eq!(
+            Shell::detect(Path::new("/home/u/.histfile")),
+            Some(Shell::Zsh)
+        );
+        assert_eq!(
+            Shell::detect(Path::new("/home/u/.bash_history")),
+            Some(Shell::Bash)
+        );
+        assert_eq!(
+            Shell::detect(Path::new("/home/u/.local/share/fish/fish_history")),
+            Some(Shell::Fish)
+        );
+        assert_eq!(Shell::detect(Path::new("/tmp/history.txt")), None);
+    }
+}
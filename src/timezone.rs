@@ -0,0 +1,214 @@
+//! Timezones for showing and recording times
+//!
+//! Times are kept in UTC. Each command also records the UTC offset it was
+//! logged at, so histories merged from machines in different zones still
+//! know the local time every command ran at. `display.timezone` decides the
+//! zone times are shown in:
+//!
+//! - `local` (the default) — the system timezone
+//! - `UTC`, or a fixed offset such as `+02:00`
+//! - an IANA name such as `Europe/Berlin`
+//! - `logged` — each command at the offset it was logged at
+//!
+//! Dates typed into time filters are read in the same zone.
+
+use crate::config::Config;
+use crate::dates::{self, TimeSpan};
+use crate::error::{Error, Result};
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+
+/// Format of times in listings
+const DISPLAY_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Format of logged times in history files; the offset is left out for
+/// times whose offset is unknown
+const LOGGED_FORMAT: &str = "%Y-%m-%d %H:%M:%S %:z";
+
+/// The zone that times are shown in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DisplayTimezone {
+    /// The system timezone
+    #[default]
+    Local,
+    /// A fixed offset from UTC, UTC itself included
+    Fixed(FixedOffset),
+    /// A zone from the IANA database
+    Named(Tz),
+    /// The offset each command was logged at, or the system timezone when
+    /// that is unknown
+    Logged,
+}
+
+impl DisplayTimezone {
+    /// The zone set by `display.timezone`
+    pub fn from_config(config: &Config) -> Result<Self> {
+        match &config.display.timezone {
+            Some(name) => Self::parse(name)
+                .ok_or_else(|| {
+                    format!(
+                        "unknown timezone '{}'; use local, logged, UTC, an offset such as +02:00 or a name such as Europe/Berlin",
+                        name
+                    )
+                })
+                .map_err(|reason| Error::config_validation("display.timezone".to_string(), reason)),
+            None => Ok(Self::Local),
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        match name.trim() {
+            "" | "local" => Some(Self::Local),
+            "logged" => Some(Self::Logged),
+            "UTC" | "utc" | "Z" => Some(Self::Fixed(FixedOffset::east_opt(0)?)),
+            name => name
+                .parse::<FixedOffset>()
+                .map(Self::Fixed)
+                .ok()
+                .or_else(|| name.parse::<Tz>().map(Self::Named).ok()),
+        }
+    }
+
+    /// Show `time` as `YYYY-MM-DD HH:MM:SS`; `logged_offset` is the offset
+    /// it was logged at, if known
+    pub fn format(&self, time: DateTime<Utc>, logged_offset: Option<i32>) -> String {
+        self.format_with(time, logged_offset, DISPLAY_FORMAT)
+    }
+
+    /// Show `time` with a `strftime` format
+    pub fn format_with(
+        &self,
+        time: DateTime<Utc>,
+        logged_offset: Option<i32>,
+        format: &str,
+    ) -> String {
+        match self.offset_for(logged_offset) {
+            Some(offset) => time.with_timezone(&offset).format(format).to_string(),
+            None => match self {
+                Self::Named(tz) => time.with_timezone(tz).format(format).to_string(),
+                _ => time.with_timezone(&Local).format(format).to_string(),
+            },
+        }
+    }
+
+    /// `time` as RFC 3339 in this zone, for exports
+    pub fn to_rfc3339(&self, time: DateTime<Utc>, logged_offset: Option<i32>) -> String {
+        match self.offset_for(logged_offset) {
+            Some(offset) => time.with_timezone(&offset).to_rfc3339(),
+            None => match self {
+                Self::Named(tz) => time.with_timezone(tz).to_rfc3339(),
+                _ => time.with_timezone(&Local).to_rfc3339(),
+            },
+        }
+    }
+
+    /// Parse a date or range of dates from a time filter, read in this zone
+    pub fn parse_span(&self, input: &str, now: DateTime<Utc>) -> Result<TimeSpan> {
+        match self {
+            Self::Local | Self::Logged => dates::parse_span(input, &now.with_timezone(&Local)),
+            Self::Fixed(offset) => dates::parse_span(input, &now.with_timezone(offset)),
+            Self::Named(tz) => dates::parse_span(input, &now.with_timezone(tz)),
+        }
+    }
+
+    /// The fixed offset to show a time at, if this zone has one
+    fn offset_for(&self, logged_offset: Option<i32>) -> Option<FixedOffset> {
+        match self {
+            Self::Fixed(offset) => Some(*offset),
+            Self::Logged => logged_offset.and_then(FixedOffset::east_opt),
+            Self::Local | Self::Named(_) => None,
+        }
+    }
+}
+
+/// Offset of the system timezone from UTC at `time`, in seconds
+pub fn local_offset(time: DateTime<Utc>) -> i32 {
+    time.with_timezone(&Local).offset().local_minus_utc()
+}
+
+/// Write a logged time for a history file: the wall-clock time at the
+/// offset it was logged at, e.g. `2025-10-01 14:00:00 +02:00`
+pub fn format_logged(time: DateTime<Utc>, offset: Option<i32>) -> String {
+    match offset.and_then(FixedOffset::east_opt) {
+        Some(offset) => time
+            .with_timezone(&offset)
+            .format(LOGGED_FORMAT)
+            .to_string(),
+        None => time.format(DISPLAY_FORMAT).to_string(),
+    }
+}
+
+/// Read a time written by [`format_logged`], with its offset; times without
+/// an offset are UTC, as older versions wrote them
+pub fn parse_logged(text: &str) -> Option<(DateTime<Utc>, Option<i32>)> {
+    if let Ok(time) = DateTime::parse_from_str(text, LOGGED_FORMAT) {
+        return Some((
+            time.with_timezone(&Utc),
+            Some(time.offset().local_minus_utc()),
+        ));
+    }
+    NaiveDateTime::parse_from_str(text, DISPLAY_FORMAT)
+        .ok()
+        .map(|time| (time.and_utc(), None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_display_zones() {
+        let time = Utc.with_ymd_and_hms(2025, 7, 1, 7, 30, 0).unwrap();
+        let zone = |name: &str| {
+            let mut config = Config::default();
+            config.display.timezone = Some(name.to_string());
+            DisplayTimezone::from_config(&config)
+        };
+
+        assert_eq!(
+            zone("UTC").unwrap().format(time, Some(3600)),
+            "2025-07-01 07:30:00"
+        );
+        assert_eq!(
+            zone("-05:00").unwrap().format(time, None),
+            "2025-07-01 02:30:00"
+        );
+        assert_eq!(
+            zone("Europe/Berlin").unwrap().format(time, None),
+            "2025-07-01 09:30:00"
+        );
+        assert_eq!(
+            zone("Asia/Tokyo").unwrap().to_rfc3339(time, None),
+            "2025-07-01T16:30:00+09:00"
+        );
+        let logged = zone("logged").unwrap();
+        assert_eq!(logged.format(time, Some(-7 * 3600)), "2025-07-01 00:30:00");
+        assert!(zone("Mars/Olympus_Mons").is_err());
+
+        // Dates in filters are read in the display zone
+        let span = zone("Asia/Tokyo")
+            .unwrap()
+            .parse_span("2025-07-01", time)
+            .unwrap();
+        assert_eq!(
+            span.start,
+            Utc.with_ymd_and_hms(2025, 6, 30, 15, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_logged_times_round_trip() {
+        let time = Utc.with_ymd_and_hms(2025, 10, 1, 12, 0, 0).unwrap();
+
+        let line = format_logged(time, Some(2 * 3600));
+        assert_eq!(line, "2025-10-01 14:00:00 +02:00");
+        assert_eq!(parse_logged(&line), Some((time, Some(2 * 3600))));
+
+        // Lines written before offsets were recorded are UTC
+        let legacy = format_logged(time, None);
+        assert_eq!(legacy, "2025-10-01 12:00:00");
+        assert_eq!(parse_logged(&legacy), Some((time, None)));
+        assert_eq!(parse_logged("yesterday"), None);
+    }
+}
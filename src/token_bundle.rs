@@ -0,0 +1,166 @@
+//! Encrypted token bundles for Mortimer
+//!
+//! A bundle carries stored tokens between machines without writing them out
+//! in plaintext. The key is derived from a passphrase with Argon2id and the
+//! tokens are sealed with ChaCha20-Poly1305, so a wrong passphrase or a
+//! modified file is detected rather than imported.
+//!
+//! # Layout
+//!
+//! ```text
+//! magic (16) | salt (16) | nonce (12) | ciphertext + tag
+//! ```
+//!
+//! The magic and salt are authenticated as associated data. Command IDs are
+//! local to one database, so each token names its command by timestamp,
+//! directory and command text instead.
+
+use crate::error::{Error, Result};
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+
+/// File signature and format version
+const MAGIC: &[u8; 16] = b"MORTIMER-TOKENS1";
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+const HEADER_LENGTH: usize = MAGIC.len() + SALT_LENGTH + NONCE_LENGTH;
+
+/// Identifies a command independently of its database row ID
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommandIdentity {
+    /// RFC 3339 timestamp as stored in the database
+    pub timestamp: String,
+    pub directory: String,
+    /// The command as stored, i.e. with placeholders
+    pub command: String,
+}
+
+/// A stored token together with the command it belongs to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundledToken {
+    pub command: CommandIdentity,
+    pub token_type: String,
+    pub placeholder: String,
+    pub original_value: String,
+    /// RFC 3339 time the token was stored
+    pub created_at: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BundleContents {
+    tokens: Vec<BundledToken>,
+}
+
+/// Encrypt tokens into a bundle with a key derived from the passphrase
+pub fn seal(tokens: &[BundledToken], passphrase: &str) -> Result<Vec<u8>> {
+    let mut salt = [0u8; SALT_LENGTH];
+    let mut nonce = [0u8; NONCE_LENGTH];
+    getrandom::getrandom(&mut salt)
+        .and_then(|_| getrandom::getrandom(&mut nonce))
+        .map_err(|e| Error::custom(format!("Failed to generate bundle nonce: {}", e)))?;
+
+    let mut bundle = Vec::with_capacity(HEADER_LENGTH);
+    bundle.extend_from_slice(MAGIC);
+    bundle.extend_from_slice(&salt);
+    bundle.extend_from_slice(&nonce);
+
+    let plaintext = serde_json::to_vec(&BundleContents {
+        tokens: tokens.to_vec(),
+    })?;
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?);
+    let ciphertext = cipher
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &plaintext,
+                aad: &bundle[..MAGIC.len() + SALT_LENGTH],
+            },
+        )
+        .map_err(|_| Error::custom("Failed to encrypt token bundle"))?;
+
+    bundle.extend_from_slice(&ciphertext);
+    Ok(bundle)
+}
+
+/// Decrypt and verify a bundle
+pub fn open(bundle: &[u8], passphrase: &str) -> Result<Vec<BundledToken>> {
+    if bundle.len() < HEADER_LENGTH || &bundle[..MAGIC.len()] != MAGIC {
+        return Err(Error::custom("Not a mortimer token bundle"));
+    }
+
+    let salt = &bundle[MAGIC.len()..MAGIC.len() + SALT_LENGTH];
+    let nonce = &bundle[MAGIC.len() + SALT_LENGTH..HEADER_LENGTH];
+
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, salt)?);
+    let plaintext = cipher
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: &bundle[HEADER_LENGTH..],
+                aad: &bundle[..MAGIC.len() + SALT_LENGTH],
+            },
+        )
+        .map_err(|_| {
+            Error::custom("Cannot decrypt token bundle: wrong passphrase or corrupted file")
+        })?;
+
+    let contents: BundleContents = serde_json::from_slice(&plaintext)?;
+    Ok(contents.tokens)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| Error::custom(format!("Failed to derive bundle key: {}", e)))?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_token() -> BundledToken {
+        BundledToken {
+            command: CommandIdentity {
+                timestamp: "2024-01-01T12:00:00+00:00".to_string(),
+                directory: "/home/user".to_string(),
+                command: "mysql -p <password:1>".to_string(),
+            },
+            token_type: "password".to_string(),
+            placeholder: "<password:1>".to_string(),
+            original_value: "hunter22".to_string(),
+            created_at: "2024-01-01T12:00:00+00:00".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_seal_and_open() {
+        let tokens = vec![sample_token()];
+        let bundle = seal(&tokens, "correct horse").unwrap();
+
+        assert!(bundle.starts_with(MAGIC));
+        assert!(!String::from_utf8_lossy(&bundle).contains("hunter22"));
+        assert_eq!(open(&bundle, "correct horse").unwrap(), tokens);
+    }
+
+    #[test]
+    fn test_open_rejects_wrong_passphrase_and_tampering() {
+        let bundle = seal(&[sample_token()], "correct horse").unwrap();
+        assert!(open(&bundle, "wrong horse").is_err());
+
+        // The salt is authenticated too
+        let mut tampered = bundle.clone();
+        tampered[MAGIC.len()] ^= 1;
+        assert!(open(&tampered, "correct horse").is_err());
+
+        let mut tampered = bundle;
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(open(&tampered, "correct horse").is_err());
+
+        assert!(open(b"plain text", "correct horse").is_err());
+    }
+}